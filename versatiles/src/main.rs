@@ -3,6 +3,7 @@
 //! VersaTiles is a command-line tool for converting, probing, and serving map tiles in various formats.
 //!
 //! ## Subcommands
+//! - **Compare**: Compare two tile containers tile by tile.
 //! - **Convert**: Convert between different tile containers.
 //! - **Probe**: Show information about a tile container.
 //! - **Serve**: Serve tiles via HTTP.
@@ -32,6 +33,8 @@ use clap::{Parser, Subcommand};
 use clap_verbosity_flag::{ErrorLevel, Verbosity};
 use versatiles_container as container;
 use versatiles_core::*;
+use versatiles_geometry as geometry;
+use versatiles_image as image;
 use versatiles_pipeline as pipeline;
 
 /// Command-line interface for VersaTiles
@@ -55,6 +58,9 @@ struct Cli {
 /// Define subcommands for the command-line interface
 #[derive(Subcommand, Debug)]
 enum Commands {
+	/// Compare two tile containers
+	Compare(tools::compare::Subcommand),
+
 	#[clap(alias = "converter")]
 	/// Convert between different tile containers
 	Convert(tools::convert::Subcommand),
@@ -86,6 +92,7 @@ fn main() -> Result<()> {
 /// Helper function for running subcommands
 fn run(cli: Cli) -> Result<()> {
 	match &cli.command {
+		Commands::Compare(arguments) => tools::compare::run(arguments),
 		Commands::Convert(arguments) => tools::convert::run(arguments),
 		Commands::Help(arguments) => tools::help::run(arguments),
 		Commands::Probe(arguments) => tools::probe::run(arguments),
@@ -126,6 +133,18 @@ mod tests {
 		assert!(err.starts_with("versatiles "));
 	}
 
+	/// Test for subcommand 'compare'
+	#[test]
+	fn compare_subcommand() {
+		let output = run_command(vec!["versatiles", "compare"])
+			.unwrap_err()
+			.to_string();
+		assert!(
+			output.starts_with("Compare two tile containers"),
+			"{output}"
+		);
+	}
+
 	/// Test for subcommand 'convert'
 	#[test]
 	fn convert_subcommand() {
@@ -0,0 +1,541 @@
+mod vector_tile;
+
+use crate::{
+	container::get_reader,
+	types::{Blob, TileBBox, TileCoord3, TileFormat, TilesReaderTrait},
+	utils::{decompress, parse_json, JsonValue, PrettyPrint},
+};
+use anyhow::{bail, ensure, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use vector_tile::{LayerDiff, VectorTileDiff};
+
+/// number of tiles per side of a block, that is loaded from both containers at once
+const BLOCK_SIZE: u32 = 64;
+
+/// maximum number of example coordinates listed per zoom level and kind of difference
+const MAX_EXAMPLES: usize = 10;
+
+#[derive(clap::Args, Debug)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
+pub struct Subcommand {
+	/// original tile container
+	/// supported container formats are: *.versatiles, *.tar, *.pmtiles, *.mbtiles or a directory
+	#[arg(required = true, verbatim_doc_comment)]
+	file1: String,
+
+	/// tile container that is compared with the original one
+	#[arg(required = true)]
+	file2: String,
+
+	/// minimum zoom level
+	#[arg(long, value_name = "int")]
+	min_zoom: Option<u8>,
+
+	/// maximum zoom level
+	#[arg(long, value_name = "int")]
+	max_zoom: Option<u8>,
+
+	/// maximum number of missing, extra and changed tiles that is still accepted.
+	/// If there are more differences, the command exits with an error.
+	#[arg(long, value_name = "int", default_value_t = 0, verbatim_doc_comment)]
+	max_diff: u64,
+
+	/// compare vector tiles semantically:
+	/// ignore the order of layers, features and properties and report differences per layer
+	#[arg(long, verbatim_doc_comment)]
+	semantic: bool,
+}
+
+#[tokio::main]
+pub async fn run(arguments: &Subcommand) -> Result<()> {
+	eprintln!("compare {:?} with {:?}", arguments.file1, arguments.file2);
+
+	let reader1 = get_reader(&arguments.file1).await?;
+	let reader2 = get_reader(&arguments.file2).await?;
+
+	let diff = compare_containers(
+		reader1.as_ref(),
+		reader2.as_ref(),
+		arguments.min_zoom.unwrap_or(0),
+		arguments.max_zoom.unwrap_or(31),
+		arguments.semantic,
+	)
+	.await?;
+
+	diff.print(&mut PrettyPrint::new()).await;
+
+	let count = diff.count_tile_differences();
+	if count > arguments.max_diff {
+		bail!(
+			"found {count} tile differences, but only {} are allowed",
+			arguments.max_diff
+		);
+	}
+
+	Ok(())
+}
+
+/// Differences between the tiles of both containers at one zoom level.
+/// "missing" tiles exist only in the first container, "extra" tiles only in the second one.
+#[derive(Debug, Default, PartialEq)]
+pub struct LevelDiff {
+	pub level: u8,
+	pub equal: u64,
+	/// tiles that differ byte-wise, but have the same content (only in semantic mode)
+	pub equivalent: u64,
+	pub changed: Vec<TileCoord3>,
+	pub missing: Vec<TileCoord3>,
+	pub extra: Vec<TileCoord3>,
+}
+
+impl LevelDiff {
+	fn new(level: u8) -> LevelDiff {
+		LevelDiff {
+			level,
+			..Default::default()
+		}
+	}
+
+	pub fn count_differences(&self) -> u64 {
+		(self.changed.len() + self.missing.len() + self.extra.len()) as u64
+	}
+}
+
+/// Result of comparing two tile containers.
+#[derive(Debug, Default, PartialEq)]
+pub struct ContainerDiff {
+	/// differences in format, compression and bbox pyramid
+	pub parameters: Vec<String>,
+	/// differences in the meta data
+	pub meta: Vec<String>,
+	pub levels: Vec<LevelDiff>,
+	/// semantic differences per vector tile layer (only in semantic mode)
+	pub vector_layers: BTreeMap<String, LayerDiff>,
+}
+
+impl ContainerDiff {
+	pub fn count_tile_differences(&self) -> u64 {
+		self.levels.iter().map(|l| l.count_differences()).sum()
+	}
+
+	pub async fn print(&self, print: &mut PrettyPrint) {
+		let cat = print.get_category("parameters").await;
+		if self.parameters.is_empty() {
+			cat.add_value(&"equal").await;
+		}
+		for text in self.parameters.iter() {
+			cat.add_warning(text).await;
+		}
+
+		let cat = print.get_category("meta_data").await;
+		if self.meta.is_empty() {
+			cat.add_value(&"equal").await;
+		}
+		for text in self.meta.iter() {
+			cat.add_warning(text).await;
+		}
+
+		let mut cat = print.get_category("tiles").await;
+		for level in self.levels.iter() {
+			let mut list = cat.get_list(&format!("level {}", level.level)).await;
+			list.add_key_value("equal", &level.equal).await;
+			if level.equivalent > 0 {
+				list.add_key_value("equivalent", &level.equivalent).await;
+			}
+			for (name, coords) in [
+				("changed", &level.changed),
+				("missing", &level.missing),
+				("extra", &level.extra),
+			] {
+				if coords.is_empty() {
+					list.add_key_value(name, &0usize).await;
+					continue;
+				}
+				let examples = list.get_list(&format!("{name}: {}", coords.len())).await;
+				for coord in coords.iter().take(MAX_EXAMPLES) {
+					examples.add_value(coord).await;
+				}
+				if coords.len() > MAX_EXAMPLES {
+					examples.add_value(&"...").await;
+				}
+			}
+		}
+		cat.add_key_value("total differences", &self.count_tile_differences())
+			.await;
+
+		if !self.vector_layers.is_empty() {
+			let mut cat = print.get_category("vector layers").await;
+			for (name, layer) in self.vector_layers.iter() {
+				let list = cat.get_list(&format!("layer {name:?}")).await;
+				if layer.missing > 0 {
+					list
+						.add_key_value(
+							"tiles without this layer in second container",
+							&layer.missing,
+						)
+						.await;
+				}
+				if layer.extra > 0 {
+					list
+						.add_key_value("tiles without this layer in first container", &layer.extra)
+						.await;
+				}
+				if layer.changed > 0 {
+					list.add_key_value("changed tiles", &layer.changed).await;
+					list
+						.add_key_value(
+							"feature count",
+							&format!("{} -> {}", layer.features1, layer.features2),
+						)
+						.await;
+					list
+						.add_key_value("changed geometries", &layer.changed_geometries)
+						.await;
+					list
+						.add_key_value("changed properties", &layer.changed_properties)
+						.await;
+				}
+				if !layer.missing_keys.is_empty() {
+					list
+						.add_key_value("missing property keys", &layer.missing_keys)
+						.await;
+				}
+				if !layer.extra_keys.is_empty() {
+					list
+						.add_key_value("extra property keys", &layer.extra_keys)
+						.await;
+				}
+			}
+		}
+	}
+}
+
+/// Compares two tile containers tile by tile, using the union of both bbox pyramids.
+/// Tiles are compared after decompression, so different tile compressions do not count as difference.
+/// In semantic mode, byte-wise different vector tiles are decoded and compared layer by layer.
+pub async fn compare_containers(
+	reader1: &dyn TilesReaderTrait,
+	reader2: &dyn TilesReaderTrait,
+	min_zoom: u8,
+	max_zoom: u8,
+	semantic: bool,
+) -> Result<ContainerDiff> {
+	let parameters1 = reader1.get_parameters();
+	let parameters2 = reader2.get_parameters();
+
+	if semantic {
+		ensure!(
+			parameters1.tile_format == TileFormat::PBF && parameters2.tile_format == TileFormat::PBF,
+			"semantic comparison is only possible for vector tiles (pbf), but the tile formats are {} and {}",
+			parameters1.tile_format,
+			parameters2.tile_format
+		);
+	}
+
+	let mut diff = ContainerDiff {
+		parameters: compare_parameters(reader1, reader2),
+		meta: compare_meta(&reader1.get_meta()?, &reader2.get_meta()?)?,
+		..Default::default()
+	};
+	let mut vector_diff = VectorTileDiff::default();
+
+	for level in min_zoom..=max_zoom.min(31) {
+		let bbox1 = parameters1.bbox_pyramid.get_level_bbox(level);
+		let bbox2 = parameters2.bbox_pyramid.get_level_bbox(level);
+
+		let mut union = bbox1.clone();
+		union.include_bbox(bbox2);
+		if union.is_empty() {
+			continue;
+		}
+
+		let mut level_diff = LevelDiff::new(level);
+		for block in union.iter_bbox_grid(BLOCK_SIZE) {
+			let mut tiles1: HashMap<TileCoord3, Blob> = HashMap::new();
+			for (coord, blob) in get_block_tiles(reader1, &block, bbox1).await? {
+				tiles1.insert(coord, blob);
+			}
+
+			let mut tiles2 = get_block_tiles(reader2, &block, bbox2).await?;
+			tiles2.sort_by_cached_key(|(coord, _)| coord.get_sort_index());
+
+			for (coord, blob2) in tiles2 {
+				match tiles1.remove(&coord) {
+					Some(blob1) => {
+						if blob1 == blob2 {
+							level_diff.equal += 1;
+						} else if semantic {
+							let tile_diff = VectorTileDiff::from_blobs(&blob1, &blob2)
+								.with_context(|| format!("Failed to compare tile {coord:?}"))?;
+							if tile_diff.is_empty() {
+								level_diff.equivalent += 1;
+							} else {
+								level_diff.changed.push(coord);
+								vector_diff.merge(tile_diff);
+							}
+						} else {
+							level_diff.changed.push(coord);
+						}
+					}
+					None => level_diff.extra.push(coord),
+				}
+			}
+
+			let mut missing: Vec<TileCoord3> = tiles1.into_keys().collect();
+			missing.sort_by_cached_key(|coord| coord.get_sort_index());
+			level_diff.missing.append(&mut missing);
+		}
+
+		diff.levels.push(level_diff);
+	}
+
+	diff.vector_layers = vector_diff.layers;
+
+	Ok(diff)
+}
+
+/// Reads all decompressed tiles of a block that are inside the bbox of the container.
+async fn get_block_tiles(
+	reader: &dyn TilesReaderTrait,
+	block: &TileBBox,
+	bbox: &TileBBox,
+) -> Result<Vec<(TileCoord3, Blob)>> {
+	let mut block = block.clone();
+	block.intersect_bbox(bbox);
+	if block.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let compression = reader.get_parameters().tile_compression;
+	reader
+		.get_bbox_tile_stream(block)
+		.await
+		.collect()
+		.await
+		.into_iter()
+		.map(|(coord, blob)| Ok((coord, decompress(blob, &compression)?)))
+		.collect()
+}
+
+fn compare_parameters(
+	reader1: &dyn TilesReaderTrait,
+	reader2: &dyn TilesReaderTrait,
+) -> Vec<String> {
+	let parameters1 = reader1.get_parameters();
+	let parameters2 = reader2.get_parameters();
+	let mut result = Vec::new();
+
+	if parameters1.tile_format != parameters2.tile_format {
+		result.push(format!(
+			"tile format: {} != {}",
+			parameters1.tile_format, parameters2.tile_format
+		));
+	}
+
+	if parameters1.tile_compression != parameters2.tile_compression {
+		result.push(format!(
+			"tile compression: {} != {}",
+			parameters1.tile_compression, parameters2.tile_compression
+		));
+	}
+
+	for level in 0..=31 {
+		let bbox1 = parameters1.bbox_pyramid.get_level_bbox(level);
+		let bbox2 = parameters2.bbox_pyramid.get_level_bbox(level);
+		if bbox1.is_empty() && bbox2.is_empty() {
+			continue;
+		}
+		if bbox1 != bbox2 {
+			result.push(format!("bbox level {level}: {bbox1:?} != {bbox2:?}"));
+		}
+	}
+
+	result
+}
+
+/// Compares the meta data. If both are JSON objects, the differing top level keys are reported.
+fn compare_meta(meta1: &Option<Blob>, meta2: &Option<Blob>) -> Result<Vec<String>> {
+	let (meta1, meta2) = match (meta1, meta2) {
+		(None, None) => return Ok(vec![]),
+		(Some(_), None) => {
+			return Ok(vec![String::from(
+				"meta data is missing in second container",
+			)])
+		}
+		(None, Some(_)) => {
+			return Ok(vec![String::from(
+				"meta data is missing in first container",
+			)])
+		}
+		(Some(meta1), Some(meta2)) => (meta1, meta2),
+	};
+
+	if meta1 == meta2 {
+		return Ok(vec![]);
+	}
+
+	if let (Ok(JsonValue::Object(object1)), Ok(JsonValue::Object(object2))) =
+		(parse_json(meta1.as_str()), parse_json(meta2.as_str()))
+	{
+		let mut result = Vec::new();
+		for (key, value1) in object1.iter() {
+			match object2.get(key) {
+				Some(value2) if value1 == value2 => {}
+				Some(_) => result.push(format!("meta data key {key:?} is changed")),
+				None => result.push(format!("meta data key {key:?} is missing")),
+			}
+		}
+		for key in object2.keys() {
+			if !object1.contains_key(key) {
+				result.push(format!("meta data key {key:?} is extra"));
+			}
+		}
+		return Ok(result);
+	}
+
+	Ok(vec![format!(
+		"meta data differs ({} bytes != {} bytes)",
+		meta1.len(),
+		meta2.len()
+	)])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		container::{MockTilesReader, MockTilesReaderProfile},
+		types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
+	};
+
+	fn get_reader(
+		format: TileFormat,
+		compression: TileCompression,
+		bbox: [u32; 4],
+	) -> Result<MockTilesReader> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_full(2);
+		bbox_pyramid.set_level_bbox(TileBBox::new(3, bbox[0], bbox[1], bbox[2], bbox[3])?);
+		MockTilesReader::new_mock(TilesReaderParameters::new(
+			format,
+			compression,
+			bbox_pyramid,
+		))
+	}
+
+	#[tokio::test]
+	async fn compare_equal() -> Result<()> {
+		let reader1 = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let reader2 = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+
+		let diff = compare_containers(&reader1, &reader2, 0, 31, false).await?;
+		assert_eq!(diff.count_tile_differences(), 0);
+		assert!(diff.parameters.is_empty());
+		assert!(diff.meta.is_empty());
+		assert_eq!(diff.levels.len(), 5);
+		assert_eq!(diff.levels[4].equal, 256);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn compare_compression() -> Result<()> {
+		let reader1 = get_reader(TileFormat::PBF, TileCompression::Gzip, [0, 0, 7, 7])?;
+		let reader2 = get_reader(TileFormat::PBF, TileCompression::Brotli, [0, 0, 7, 7])?;
+
+		let diff = compare_containers(&reader1, &reader2, 0, 31, false).await?;
+		assert_eq!(diff.parameters, ["tile compression: gzip != brotli"]);
+		assert_eq!(diff.count_tile_differences(), 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn compare_bbox() -> Result<()> {
+		let reader1 = get_reader(TileFormat::JSON, TileCompression::Gzip, [0, 0, 3, 7])?;
+		let reader2 = get_reader(TileFormat::JSON, TileCompression::Gzip, [2, 0, 5, 7])?;
+
+		let diff = compare_containers(&reader1, &reader2, 2, 3, false).await?;
+		assert_eq!(
+			diff.parameters,
+			["bbox level 3: 3: [0,0,3,7] (32) != 3: [2,0,5,7] (32)"]
+		);
+		assert_eq!(diff.levels.len(), 2);
+		assert_eq!(diff.levels[0].count_differences(), 0);
+
+		let level = &diff.levels[1];
+		assert_eq!(level.level, 3);
+		assert_eq!(level.equal, 16);
+		assert_eq!(level.changed.len(), 0);
+		assert_eq!(level.missing.len(), 16);
+		assert_eq!(level.extra.len(), 16);
+		assert_eq!(level.missing[0], TileCoord3::new(0, 0, 3)?);
+		assert_eq!(level.extra[0], TileCoord3::new(4, 0, 3)?);
+		assert_eq!(diff.count_tile_differences(), 32);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn compare_format() -> Result<()> {
+		let reader1 = get_reader(TileFormat::PNG, TileCompression::Uncompressed, [0, 0, 7, 7])?;
+		let reader2 = get_reader(
+			TileFormat::WEBP,
+			TileCompression::Uncompressed,
+			[0, 0, 7, 7],
+		)?;
+
+		let diff = compare_containers(&reader1, &reader2, 3, 3, false).await?;
+		assert_eq!(diff.parameters, ["tile format: png != webp"]);
+		assert_eq!(diff.levels[0].changed.len(), 64);
+		assert_eq!(diff.count_tile_differences(), 64);
+
+		let mut print = PrettyPrint::new();
+		diff.print(&mut print).await;
+		let text = print.as_string().await;
+		assert!(text.contains("   level 3:\n      equal: 0\n      changed: 64:\n"));
+		assert!(text.contains("         \"...\"\n      missing: 0\n      extra: 0\n"));
+		assert!(text.contains("   total differences: 64\n"));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn compare_semantic() -> Result<()> {
+		let reader1 = get_reader(TileFormat::PBF, TileCompression::Gzip, [0, 0, 7, 7])?;
+		let reader2 = get_reader(TileFormat::PBF, TileCompression::Brotli, [0, 0, 7, 3])?;
+
+		let diff = compare_containers(&reader1, &reader2, 3, 3, true).await?;
+		assert_eq!(diff.levels[0].equal, 32);
+		assert_eq!(diff.levels[0].missing.len(), 32);
+		assert!(diff.vector_layers.is_empty());
+
+		let reader3 = get_reader(TileFormat::PNG, TileCompression::Gzip, [0, 0, 7, 7])?;
+		let error = compare_containers(&reader1, &reader3, 3, 3, true)
+			.await
+			.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"semantic comparison is only possible for vector tiles (pbf), but the tile formats are pbf and png"
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn compare_meta_json() -> Result<()> {
+		let meta1 = Some(Blob::from(r#"{"a":1,"b":2,"c":3}"#));
+		let meta2 = Some(Blob::from(r#"{ "b": 2, "c": 4, "d": 5, "a": 1 }"#));
+		assert_eq!(
+			compare_meta(&meta1, &meta2)?,
+			[
+				"meta data key \"c\" is changed",
+				"meta data key \"d\" is extra"
+			]
+		);
+		assert_eq!(
+			compare_meta(&Some(Blob::from("abc")), &Some(Blob::from("abcd")))?,
+			["meta data differs (3 bytes != 4 bytes)"]
+		);
+		assert_eq!(
+			compare_meta(&meta1, &None)?,
+			["meta data is missing in second container"]
+		);
+		assert!(compare_meta(&None, &None)?.is_empty());
+		Ok(())
+	}
+}
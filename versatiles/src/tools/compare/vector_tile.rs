@@ -0,0 +1,296 @@
+//! semantic comparison of vector tiles
+//!
+//! Both tiles are decoded and compared layer by layer. The order of layers, features and
+//! property tables is ignored, so re-encoded tiles with identical content are reported as equal.
+
+use crate::types::Blob;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use versatiles_geometry::{
+	vector_tile::{VectorTile, VectorTileLayer},
+	GeoFeature,
+};
+
+/// Semantic differences of one layer, summed up over all compared tiles.
+#[derive(Debug, Default, PartialEq)]
+pub struct LayerDiff {
+	/// number of tiles in which the layer exists only in the first container
+	pub missing: u64,
+	/// number of tiles in which the layer exists only in the second container
+	pub extra: u64,
+	/// number of tiles in which the layer exists in both containers but differs
+	pub changed: u64,
+	/// number of features in the first container (only changed layers are counted)
+	pub features1: u64,
+	/// number of features in the second container (only changed layers are counted)
+	pub features2: u64,
+	/// number of geometries without an identical counterpart in the other tile
+	pub changed_geometries: u64,
+	/// number of property sets without an identical counterpart in the other tile
+	pub changed_properties: u64,
+	/// property keys that are used only in the first container
+	pub missing_keys: BTreeSet<String>,
+	/// property keys that are used only in the second container
+	pub extra_keys: BTreeSet<String>,
+}
+
+impl LayerDiff {
+	fn add(&mut self, other: LayerDiff) {
+		self.missing += other.missing;
+		self.extra += other.extra;
+		self.changed += other.changed;
+		self.features1 += other.features1;
+		self.features2 += other.features2;
+		self.changed_geometries += other.changed_geometries;
+		self.changed_properties += other.changed_properties;
+		self.missing_keys.extend(other.missing_keys);
+		self.extra_keys.extend(other.extra_keys);
+	}
+}
+
+/// Semantic differences between vector tiles, grouped by layer name.
+#[derive(Debug, Default, PartialEq)]
+pub struct VectorTileDiff {
+	pub layers: BTreeMap<String, LayerDiff>,
+}
+
+impl VectorTileDiff {
+	/// Decodes and compares two uncompressed PBF tiles.
+	pub fn from_blobs(blob1: &Blob, blob2: &Blob) -> Result<VectorTileDiff> {
+		let tile1 = VectorTile::from_blob(blob1).context("Failed to decode first vector tile")?;
+		let tile2 = VectorTile::from_blob(blob2).context("Failed to decode second vector tile")?;
+
+		let mut layers1: BTreeMap<&str, &VectorTileLayer> =
+			tile1.layers.iter().map(|l| (l.name.as_str(), l)).collect();
+
+		let mut diff = VectorTileDiff::default();
+
+		for layer2 in tile2.layers.iter() {
+			let layer_diff = match layers1.remove(layer2.name.as_str()) {
+				Some(layer1) => compare_layers(layer1, layer2)?,
+				None => LayerDiff {
+					extra: 1,
+					..Default::default()
+				},
+			};
+			diff.add_layer(&layer2.name, layer_diff);
+		}
+
+		for name in layers1.into_keys() {
+			diff.add_layer(
+				name,
+				LayerDiff {
+					missing: 1,
+					..Default::default()
+				},
+			);
+		}
+
+		Ok(diff)
+	}
+
+	/// Returns true if no layer differs.
+	pub fn is_empty(&self) -> bool {
+		self.layers.is_empty()
+	}
+
+	/// Sums up the differences of another tile.
+	pub fn merge(&mut self, other: VectorTileDiff) {
+		for (name, layer_diff) in other.layers {
+			self.add_layer(&name, layer_diff);
+		}
+	}
+
+	fn add_layer(&mut self, name: &str, layer_diff: LayerDiff) {
+		if layer_diff == LayerDiff::default() {
+			return;
+		}
+		self
+			.layers
+			.entry(name.to_string())
+			.or_default()
+			.add(layer_diff);
+	}
+}
+
+fn compare_layers(layer1: &VectorTileLayer, layer2: &VectorTileLayer) -> Result<LayerDiff> {
+	let features1 = layer1
+		.to_features()
+		.with_context(|| format!("Failed to decode features of layer {:?}", layer1.name))?;
+	let features2 = layer2
+		.to_features()
+		.with_context(|| format!("Failed to decode features of layer {:?}", layer2.name))?;
+
+	let feature_key = |f: &GeoFeature| format!("{:?} {:?} {:?}", f.id, f.geometry, f.properties);
+	if layer1.extent == layer2.extent
+		&& count_unmatched(&features1, &features2, feature_key) == 0
+		&& count_unmatched(&features2, &features1, feature_key) == 0
+	{
+		return Ok(LayerDiff::default());
+	}
+
+	let geometry_key = |f: &GeoFeature| format!("{:?}", f.geometry);
+	let properties_key = |f: &GeoFeature| format!("{:?}", f.properties);
+
+	let keys1 = get_property_keys(&features1);
+	let keys2 = get_property_keys(&features2);
+
+	Ok(LayerDiff {
+		changed: 1,
+		features1: features1.len() as u64,
+		features2: features2.len() as u64,
+		changed_geometries: count_unmatched(&features1, &features2, geometry_key)
+			.max(count_unmatched(&features2, &features1, geometry_key)),
+		changed_properties: count_unmatched(&features1, &features2, properties_key)
+			.max(count_unmatched(&features2, &features1, properties_key)),
+		missing_keys: keys1.difference(&keys2).cloned().collect(),
+		extra_keys: keys2.difference(&keys1).cloned().collect(),
+		..Default::default()
+	})
+}
+
+/// Counts the features in `a` that have no counterpart with the same key in `b`.
+fn count_unmatched<F>(a: &[GeoFeature], b: &[GeoFeature], key: F) -> u64
+where
+	F: Fn(&GeoFeature) -> String,
+{
+	let mut counts: HashMap<String, i64> = HashMap::new();
+	for feature in b {
+		*counts.entry(key(feature)).or_default() += 1;
+	}
+	let mut unmatched = 0;
+	for feature in a {
+		let count = counts.entry(key(feature)).or_default();
+		if *count > 0 {
+			*count -= 1;
+		} else {
+			unmatched += 1;
+		}
+	}
+	unmatched
+}
+
+fn get_property_keys(features: &[GeoFeature]) -> BTreeSet<String> {
+	features
+		.iter()
+		.flat_map(|f| f.properties.iter().map(|(k, _)| k.to_string()))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use versatiles_geometry::{vector_tile::VectorTileFeature, GeoProperties, GeoValue, Geometry};
+
+	fn new_layer(name: &str, features: Vec<(Geometry, Vec<(&str, GeoValue)>)>) -> VectorTileLayer {
+		let mut layer = VectorTileLayer::new(name.to_string(), 4096, 2);
+		for (geometry, properties) in features {
+			layer.add_vector_tile_features(
+				VectorTileFeature::from_geometry(None, vec![], geometry).unwrap(),
+				GeoProperties::from(properties),
+			);
+		}
+		layer
+	}
+
+	fn point(x: i32, y: i32) -> Geometry {
+		Geometry::new_point([x, y])
+	}
+
+	fn line(x: i32, y: i32) -> Geometry {
+		Geometry::new_line_string(vec![[0, 0], [x, y]])
+	}
+
+	fn new_blob(layers: Vec<VectorTileLayer>) -> Blob {
+		VectorTile::new(layers).to_blob().unwrap()
+	}
+
+	#[test]
+	fn equal_but_reordered() -> Result<()> {
+		let blob1 = new_blob(vec![
+			new_layer(
+				"poi",
+				vec![
+					(point(1, 2), vec![("name", GeoValue::from("a"))]),
+					(point(3, 4), vec![("kind", GeoValue::from("b"))]),
+				],
+			),
+			new_layer("streets", vec![(line(5, 6), vec![])]),
+		]);
+		let blob2 = new_blob(vec![
+			new_layer("streets", vec![(line(5, 6), vec![])]),
+			new_layer(
+				"poi",
+				vec![
+					(point(3, 4), vec![("kind", GeoValue::from("b"))]),
+					(point(1, 2), vec![("name", GeoValue::from("a"))]),
+				],
+			),
+		]);
+		assert_ne!(blob1, blob2);
+
+		let diff = VectorTileDiff::from_blobs(&blob1, &blob2)?;
+		assert!(diff.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn layer_differences() -> Result<()> {
+		let blob1 = new_blob(vec![
+			new_layer(
+				"poi",
+				vec![
+					(point(1, 2), vec![("name", GeoValue::from("a"))]),
+					(point(3, 4), vec![("kind", GeoValue::from("b"))]),
+				],
+			),
+			new_layer("water", vec![(line(1, 1), vec![])]),
+			new_layer("streets", vec![(line(5, 6), vec![])]),
+		]);
+		let blob2 = new_blob(vec![
+			new_layer(
+				"poi",
+				vec![
+					(point(1, 2), vec![("name", GeoValue::from("a"))]),
+					(point(3, 5), vec![("type", GeoValue::from("b"))]),
+					(point(7, 8), vec![("name", GeoValue::from("c"))]),
+				],
+			),
+			new_layer("buildings", vec![]),
+			new_layer("streets", vec![(line(5, 6), vec![])]),
+		]);
+
+		let mut diff = VectorTileDiff::from_blobs(&blob1, &blob2)?;
+		assert_eq!(
+			diff.layers.keys().collect::<Vec<_>>(),
+			["buildings", "poi", "water"]
+		);
+		assert_eq!(
+			diff.layers["poi"],
+			LayerDiff {
+				changed: 1,
+				features1: 2,
+				features2: 3,
+				changed_geometries: 2,
+				changed_properties: 2,
+				missing_keys: BTreeSet::from([String::from("kind")]),
+				extra_keys: BTreeSet::from([String::from("type")]),
+				..Default::default()
+			}
+		);
+		assert_eq!(diff.layers["water"].missing, 1);
+		assert_eq!(diff.layers["buildings"].extra, 1);
+
+		diff.merge(VectorTileDiff::from_blobs(&blob1, &blob2)?);
+		assert_eq!(diff.layers["poi"].changed, 2);
+		assert_eq!(diff.layers["poi"].features2, 6);
+		assert_eq!(diff.layers["water"].missing, 2);
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_tile() {
+		let blob = new_blob(vec![]);
+		assert!(VectorTileDiff::from_blobs(&Blob::from("invalid"), &blob).is_err());
+	}
+}
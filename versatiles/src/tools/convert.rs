@@ -1,9 +1,13 @@
 use crate::{
-	container::{convert_tiles_container, get_reader, TilesConverterParameters},
+	container::{
+		convert_tiles_container, get_reader, DirectoryTilesWriter, MBTilesSchema, MBTilesWriter,
+		TilesConvertReader, TilesConverterParameters,
+	},
 	types::{TileBBoxPyramid, TileCompression},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use log::trace;
+use std::env;
 
 #[derive(clap::Args, Debug)]
 #[command(arg_required_else_help = true, disable_version_flag = true)]
@@ -56,6 +60,16 @@ pub struct Subcommand {
 	/// override the compression of the input source, e.g. to handle gzipped tiles in a tar, that do not end in .gz
 	#[arg(long, value_enum, value_name = "COMPRESSION")]
 	override_input_compression: Option<TileCompression>,
+
+	/// store each unique tile only once when writing *.mbtiles,
+	/// using the tables "map" and "images" and a view "tiles"
+	#[arg(long)]
+	deduplicate_mbtiles: bool,
+
+	/// update an existing *.mbtiles file or directory instead of replacing it:
+	/// tiles are added or replaced, bbox and metadata are merged
+	#[arg(long)]
+	update: bool,
 }
 
 #[tokio::main]
@@ -78,7 +92,30 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 		arguments.flip_y,
 		arguments.swap_xy,
 	);
-	convert_tiles_container(reader, cp, &arguments.output_file).await?;
+
+	let path = env::current_dir()?.join(&arguments.output_file);
+	let is_mbtiles = arguments.output_file.ends_with(".mbtiles");
+
+	if arguments.update && path.exists() {
+		let mut converter = TilesConvertReader::new_from_reader(reader, cp)?;
+		if path.is_dir() {
+			DirectoryTilesWriter::update_path(&mut converter, &path).await?;
+		} else if is_mbtiles {
+			MBTilesWriter::update_path(&mut converter, &path).await?;
+		} else {
+			bail!("--update is only supported for *.mbtiles files and directories");
+		}
+	} else if arguments.deduplicate_mbtiles {
+		ensure!(
+			is_mbtiles,
+			"--deduplicate-mbtiles requires an *.mbtiles output file"
+		);
+		let mut converter = TilesConvertReader::new_from_reader(reader, cp)?;
+		MBTilesWriter::write_to_path_with_schema(&mut converter, &path, MBTilesSchema::Deduplicated)
+			.await?;
+	} else {
+		convert_tiles_container(reader, cp, &arguments.output_file).await?;
+	}
 
 	Ok(())
 }
@@ -1,5 +1,6 @@
 //! cli tools
 
+pub mod compare;
 pub mod convert;
 pub mod help;
 pub mod probe;
@@ -1,4 +1,4 @@
-use crate::{container::get_reader, types::ProbeDepth};
+use crate::{container::get_reader, types::ProbeDepth, utils::PrettyPrint};
 use anyhow::Result;
 
 #[derive(clap::Args, Debug)]
@@ -15,6 +15,10 @@ pub struct Subcommand {
 	/// -ddd: scans all tile contents
 	#[arg(long, short, action = clap::ArgAction::Count, verbatim_doc_comment)]
 	deep: u8,
+
+	/// print the results as one JSON document to stdout
+	#[arg(long)]
+	json: bool,
 }
 
 #[tokio::main]
@@ -30,7 +34,13 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 		3..=255 => ProbeDepth::TileContents,
 	};
 
-	reader.probe(level).await?;
+	if arguments.json {
+		let mut print = PrettyPrint::new_json();
+		reader.probe_with(level, &mut print).await?;
+		println!("{}", print.as_json().await.unwrap().stringify());
+	} else {
+		reader.probe(level).await?;
+	}
 
 	Ok(())
 }
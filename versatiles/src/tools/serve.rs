@@ -1,12 +1,21 @@
-use super::server::{TileServer, Url};
+use super::server::{
+	get_id_from_path, parse_base_url, parse_header, reload_on_change, AccessLog, AccessLogFormat,
+	BaseUrl, ServerConfig, StaticSourceConfig, TileServer, TileSourceConfig, TileSourceInput, Url,
+	UrlScheme,
+};
 use crate::{
-	container::{get_reader, TilesConvertReader, TilesConverterParameters},
+	container::{
+		get_reader, PipelineReader, TilesConvertReader, TilesConverterParameters, TilesOverzoomReader,
+	},
 	types::{TileCompression, TilesReaderTrait},
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
-use std::path::Path;
-use tokio::time::{sleep, Duration};
+use std::path::{Path, PathBuf};
+use tokio::{
+	sync::mpsc,
+	time::{timeout_at, Duration, Instant},
+};
 
 #[derive(clap::Args, Debug)]
 #[command(
@@ -23,9 +32,41 @@ pub struct Subcommand {
 	///    e.g. ".../ukraine.versatiles" will be served at url "/tiles/ukraine/..."
 	/// You can also configure a different id for each file using:
 	///    "[id]file", "file[id]" or "file#id"
-	#[arg(num_args = 1.., required = true, verbatim_doc_comment)]
+	/// Raster tiles can be requested as .png, .jpg or .webp and are transcoded if needed.
+	/// Without an extension, e.g. "/tiles/$id/{z}/{x}/{y}", the format is chosen by the Accept header.
+	/// Vector tiles can also be requested as .geojson, e.g. for debugging.
+	/// GIS clients can use the WMTS capabilities at "/tiles/$id/wmts?SERVICE=WMTS&REQUEST=GetCapabilities".
+	#[arg(num_args = 1.., required_unless_present = "config", verbatim_doc_comment)]
 	pub tile_sources: Vec<String>,
 
+	/// Load the server configuration from a JSON file, e.g.:
+	///    {
+	///      "listen": ["0.0.0.0:8080"],
+	///      "allowed_origins": ["https://example.org"],
+	///      "max_age": 86400,
+	///      "tile_sources": [
+	///        { "id": "osm", "path": "osm.versatiles" },
+	///        { "id": "debug", "vpl": "from_debug format=pbf", "flip_y": false }
+	///      ],
+	///      "static_sources": [{ "path": "styles.tar", "prefix": "/assets/styles" }]
+	///    }
+	/// Sources and flags given on the command line are added to the config.
+	/// If the config defines "listen", --ip and --port are ignored.
+	#[arg(short = 'c', long, verbatim_doc_comment)]
+	pub config: Option<PathBuf>,
+
+	/// Reload the sources when the config file changes.
+	/// Sources are also reloaded on SIGHUP or with the admin API, see --admin-token.
+	/// Requests that already started are finished with the old sources.
+	/// Changes of "listen", "metrics", "cache_size", "access_log" and "admin_token" need a restart.
+	#[arg(long, requires = "config", verbatim_doc_comment)]
+	pub watch: bool,
+
+	/// Enable the admin API with this bearer token, e.g. to reload the sources with:
+	///    curl -X POST -H "Authorization: Bearer $TOKEN" http://localhost:8080/api/admin/reload
+	#[arg(long, value_name = "TOKEN", verbatim_doc_comment)]
+	pub admin_token: Option<String>,
+
 	/// Serve via socket ip.
 	#[arg(short = 'i', long, default_value = "0.0.0.0")]
 	pub ip: String,
@@ -41,6 +82,41 @@ pub struct Subcommand {
 	#[arg(short = 's', long = "static", verbatim_doc_comment)]
 	pub static_content: Vec<String>,
 
+	/// Also serve the local container files themselves at "/files/$id.$extension",
+	/// e.g. for PMTiles clients that read them with range requests.
+	#[arg(long, verbatim_doc_comment)]
+	pub expose_containers: bool,
+
+	/// Allow cross-origin requests only from this origin, e.g. "https://example.org".
+	/// Can be used multiple times. By default every origin is allowed.
+	#[arg(long = "allowed-origin", value_name = "ORIGIN", verbatim_doc_comment)]
+	pub allowed_origins: Vec<String>,
+
+	/// max-age of the Cache-Control header in seconds [default: 2419200]
+	#[arg(long, value_name = "SECONDS")]
+	pub max_age: Option<u64>,
+
+	/// Add a header to every response, e.g. "X-Served-By: versatiles".
+	/// Can be used multiple times.
+	#[arg(long = "header", value_name = "HEADER", verbatim_doc_comment)]
+	pub headers: Vec<String>,
+
+	/// Start of the absolute URLs in "tiles.json" and the WMTS capabilities,
+	/// e.g. "https://tiles.example.org". By default it is built from the Host header.
+	#[arg(
+		long,
+		value_name = "URL",
+		conflicts_with = "trust_proxy",
+		verbatim_doc_comment
+	)]
+	pub base_url: Option<String>,
+
+	/// Take the start of the absolute URLs in "tiles.json" and the WMTS capabilities
+	/// from the X-Forwarded-Host, X-Forwarded-Proto and Host headers.
+	/// Only use this behind a reverse proxy that sets these headers.
+	#[arg(long, verbatim_doc_comment)]
+	pub trust_proxy: bool,
+
 	/// Shutdown server automatically after x milliseconds.
 	#[arg(long)]
 	pub auto_shutdown: Option<u64>,
@@ -53,6 +129,19 @@ pub struct Subcommand {
 	#[arg(long)]
 	pub flip_y: bool,
 
+	/// serve tiles beyond the maximum zoom level of the sources, up to this zoom level.
+	/// Vector tiles are rescaled and clipped, raster tiles are cropped and scaled up.
+	#[arg(long, value_name = "ZOOM", verbatim_doc_comment)]
+	pub overzoom: Option<u8>,
+
+	/// how tile coordinates are encoded in the url of the sources:
+	///    "xyz" (default): "/tiles/$id/{z}/{x}/{y}"
+	///    "tms": like "xyz", but rows are counted from the bottom
+	///    "zyx": "/tiles/$id/{z}/{y}/{x}"
+	///    "quadkey": "/tiles/$id/q/{quadkey}", as used by Bing Maps
+	#[arg(long, value_enum, value_name = "SCHEME", verbatim_doc_comment)]
+	pub url_scheme: Option<UrlScheme>,
+
 	/// use minimal recompression to reduce server response time
 	#[arg(long)]
 	pub fast: bool,
@@ -61,20 +150,131 @@ pub struct Subcommand {
 	#[arg(long)]
 	pub disable_api: bool,
 
+	/// serve request metrics for Prometheus at "/metrics"
+	#[arg(long)]
+	pub metrics: bool,
+
+	/// keep up to this many megabytes of tiles in memory, in the encodings they were sent.
+	/// Cache statistics are available at "/api/cache".
+	#[arg(long, value_name = "MB", verbatim_doc_comment)]
+	pub cache_size: Option<u64>,
+
+	/// write an access log with one line per request
+	#[arg(long, value_enum, value_name = "FORMAT")]
+	pub access_log: Option<AccessLogFormat>,
+
+	/// write the access log to this file instead of stderr.
+	/// Send SIGUSR1 to reopen the file after it was rotated.
+	#[arg(long, value_name = "FILE", verbatim_doc_comment)]
+	pub access_log_file: Option<PathBuf>,
+
 	/// override the compression of the input source, e.g. to handle gzipped tiles in a tar, that do not end in .gz
-	/// (deprecated in favor of a better solution that does not yet exist)
+	/// (only needed if the compression detected from a sample tile is wrong)
 	#[arg(long, value_enum, value_name = "COMPRESSION")]
 	override_input_compression: Option<TileCompression>,
 }
 
 #[tokio::main]
 pub async fn run(arguments: &Subcommand) -> Result<()> {
-	let mut server: TileServer = TileServer::new(
-		&arguments.ip,
-		arguments.port,
-		!arguments.fast,
-		!arguments.disable_api,
-	);
+	let config = load_config(arguments)?;
+
+	let mut server = create_server(&config).await?;
+
+	let mut list: Vec<(String, String)> = server.get_url_mapping().await;
+	list.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+	list
+		.iter()
+		.for_each(|(url, source)| eprintln!("   {:30}  <-  {}", url.to_owned() + "*", source));
+
+	let (sender, mut receiver) = mpsc::channel(8);
+	if let Some(token) = &config.admin_token {
+		server.set_admin_token(token, sender.clone());
+	}
+
+	server.start().await?;
+
+	#[cfg(unix)]
+	super::server::reload_on_signal(sender.clone())?;
+	if arguments.watch {
+		let path = arguments
+			.config
+			.as_ref()
+			.context("--watch needs --config")?;
+		reload_on_change(path, Duration::from_secs(2), sender.clone());
+	}
+
+	let deadline = arguments
+		.auto_shutdown
+		.map(|milliseconds| Instant::now() + Duration::from_millis(milliseconds));
+
+	loop {
+		let request = match deadline {
+			Some(deadline) => timeout_at(deadline, receiver.recv()).await.ok().flatten(),
+			None => receiver.recv().await,
+		};
+		let Some(request) = request else {
+			break;
+		};
+
+		log::info!("reloading sources, triggered by {}", request.trigger);
+		let result = reload_sources(arguments, &mut server).await;
+		request.finish(result);
+	}
+
+	Ok(())
+}
+
+fn load_config(arguments: &Subcommand) -> Result<ServerConfig> {
+	let mut config = match &arguments.config {
+		Some(path) => ServerConfig::from_path(path)?,
+		None => ServerConfig::default(),
+	};
+	add_arguments_to_config(arguments, &mut config)?;
+	Ok(config)
+}
+
+// opens all sources again, if that fails the old sources are kept
+async fn reload_sources(arguments: &Subcommand, server: &mut TileServer) -> Result<()> {
+	let config = load_config(arguments)?;
+	let new_server = create_server_with_sources(&config).await?;
+	server.replace_sources(new_server).await
+}
+
+fn add_arguments_to_config(arguments: &Subcommand, config: &mut ServerConfig) -> Result<()> {
+	if config.listen.is_empty() {
+		config.listen.push((arguments.ip.clone(), arguments.port));
+	}
+	if !arguments.allowed_origins.is_empty() {
+		config.allowed_origins = Some(arguments.allowed_origins.clone());
+	}
+	if arguments.max_age.is_some() {
+		config.max_age = arguments.max_age;
+	}
+	for header in arguments.headers.iter() {
+		config.headers.push(parse_header(header)?);
+	}
+	if let Some(url) = &arguments.base_url {
+		config.base_url = parse_base_url(url)?;
+	}
+	if arguments.trust_proxy {
+		config.base_url = BaseUrl::FromProxy;
+	}
+	config.fast |= arguments.fast;
+	config.disable_api |= arguments.disable_api;
+	config.expose_containers |= arguments.expose_containers;
+	config.metrics |= arguments.metrics;
+	if arguments.cache_size.is_some() {
+		config.cache_size = arguments.cache_size;
+	}
+	if arguments.access_log.is_some() {
+		config.access_log = arguments.access_log;
+	}
+	if arguments.access_log_file.is_some() {
+		config.access_log_file = arguments.access_log_file.clone();
+	}
+	if arguments.admin_token.is_some() {
+		config.admin_token = arguments.admin_token.clone();
+	}
 
 	let tile_patterns: Vec<Regex> = [
 		r"^\[(?P<id>[^\]]+?)\](?P<url>.*)$",
@@ -105,31 +305,18 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 			.unwrap();
 
 		let url: &str = capture.name("url").unwrap().as_str();
-		let id: &str = match capture.name("id") {
-			None => url
-				.split(&['/', '\\'])
-				.last()
-				.unwrap()
-				.split('.')
-				.next()
-				.unwrap(),
-			Some(m) => m.as_str(),
+		let id: String = match capture.name("id") {
+			None => get_id_from_path(url),
+			Some(m) => m.as_str().to_owned(),
 		};
 
-		let mut reader = get_reader(url).await?;
-
-		if arguments.override_input_compression.is_some() {
-			reader.override_compression(arguments.override_input_compression.unwrap())
-		}
-
-		if arguments.flip_y || arguments.swap_xy {
-			let mut cp = TilesConverterParameters::new_default();
-			cp.flip_y = arguments.flip_y;
-			cp.swap_xy = arguments.swap_xy;
-			reader = TilesConvertReader::new_from_reader(reader, cp)?.boxed();
-		}
-
-		server.add_tile_source(Url::new(&format!("/tiles/{id}/")), reader)?;
+		let mut source = TileSourceConfig::new(&id, url);
+		source.flip_y = arguments.flip_y;
+		source.swap_xy = arguments.swap_xy;
+		source.overzoom = arguments.overzoom;
+		source.scheme = arguments.url_scheme.unwrap_or_default();
+		source.override_compression = arguments.override_input_compression;
+		config.tile_sources.push(source);
 	}
 
 	for argument in arguments.static_content.iter() {
@@ -146,26 +333,103 @@ pub async fn run(arguments: &Subcommand) -> Result<()> {
 			Some(m) => m.as_str(),
 		};
 
-		server.add_static_source(Path::new(filename), Url::new(url_prefix))?;
+		config.static_sources.push(StaticSourceConfig {
+			path: PathBuf::from(filename),
+			prefix: url_prefix.to_owned(),
+			options: Default::default(),
+		});
 	}
 
-	let mut list: Vec<(String, String)> = server.get_url_mapping().await;
-	list.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-	list
-		.iter()
-		.for_each(|(url, source)| eprintln!("   {:30}  <-  {}", url.to_owned() + "*", source));
+	Ok(())
+}
 
-	server.start().await?;
+async fn create_server(config: &ServerConfig) -> Result<TileServer> {
+	let mut server = create_server_with_sources(config).await?;
+	for (ip, port) in config.listen.iter().skip(1) {
+		server.add_address(ip, *port);
+	}
+	if config.metrics {
+		server.enable_metrics();
+	}
+	if let Some(cache_size) = config.cache_size.filter(|size| *size > 0) {
+		server.set_cache_size((cache_size as usize) << 20);
+	}
+	if config.access_log.is_some() || config.access_log_file.is_some() {
+		let format = config.access_log.unwrap_or(AccessLogFormat::Combined);
+		server.set_access_log(AccessLog::new(format, config.access_log_file.as_deref())?);
+	}
+	Ok(server)
+}
+
+// everything that can be replaced in a running server: the sources and the response options
+async fn create_server_with_sources(config: &ServerConfig) -> Result<TileServer> {
+	let (ip, port) = config.listen.first().context("no listen address defined")?;
+	let mut server: TileServer = TileServer::new(ip, *port, !config.fast, !config.disable_api);
+	if let Some(origins) = &config.allowed_origins {
+		server.set_allowed_origins(origins.clone());
+	}
+	if let Some(max_age) = config.max_age {
+		server.set_max_age(max_age);
+	}
+	for (name, value) in config.headers.iter() {
+		server.add_header(name.clone(), value.clone());
+	}
+	server.set_base_url(config.base_url.clone());
 
-	if let Some(milliseconds) = arguments.auto_shutdown {
-		sleep(Duration::from_millis(milliseconds)).await
-	} else {
-		loop {
-			sleep(Duration::from_secs(60)).await
+	for source in config.tile_sources.iter() {
+		let id = &source.id;
+		let mut reader = match &source.input {
+			TileSourceInput::Path(path) => get_reader(path).await,
+			TileSourceInput::Vpl { vpl, dir } => PipelineReader::open_str(vpl, dir)
+				.await
+				.map(|reader| reader.boxed()),
+		}
+		.with_context(|| format!("failed to open tile source \"{id}\""))?;
+
+		if let Some(compression) = source.override_compression {
+			reader.override_compression(compression)
+		}
+
+		if source.flip_y || source.swap_xy {
+			let mut cp = TilesConverterParameters::new_default();
+			cp.flip_y = source.flip_y;
+			cp.swap_xy = source.swap_xy;
+			reader = TilesConvertReader::new_from_reader(reader, cp)?.boxed();
+		}
+
+		if let Some(max_zoom) = source.overzoom {
+			reader = TilesOverzoomReader::new_from_reader(reader, max_zoom)
+				.with_context(|| format!("failed to overzoom tile source \"{id}\""))?
+				.boxed();
+		}
+
+		server.add_tile_source_with_options(
+			Url::new(&format!("/tiles/{id}/")),
+			reader,
+			source.options.clone(),
+			source.scheme,
+		)?;
+
+		if config.expose_containers {
+			if let TileSourceInput::Path(path) = &source.input {
+				let path = Path::new(path);
+				if path.is_file() {
+					let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("bin");
+					server.add_static_file(path, Url::new(&format!("/files/{id}.{extension}")))?;
+				}
+			}
 		}
 	}
 
-	Ok(())
+	for source in config.static_sources.iter() {
+		server.add_static_source_with_options(
+			&source.path,
+			Url::new(&source.prefix),
+			source.options.clone(),
+		)?;
+	}
+
+	Ok(server)
 }
 
 #[allow(unused_imports)]
@@ -189,6 +453,31 @@ mod tests {
 		.unwrap();
 	}
 
+	#[test]
+	fn test_config() {
+		let dir = assert_fs::TempDir::new().unwrap();
+		let config = dir.path().join("config.json");
+		std::fs::write(
+			&config,
+			r#"{
+				"listen": ["127.0.0.1:65003"],
+				"max_age": 60,
+				"tile_sources": [{ "id": "debug", "vpl": "from_debug format=png" }]
+			}"#,
+		)
+		.unwrap();
+
+		run_command(vec![
+			"versatiles",
+			"serve",
+			"--config",
+			config.to_str().unwrap(),
+			"--auto-shutdown",
+			"500",
+		])
+		.unwrap();
+	}
+
 	#[test]
 	fn test_remote() {
 		run_command(vec![
@@ -0,0 +1,357 @@
+//! access log of the server with one line per request
+//!
+//! Supported formats are the Common and Combined Log Format, known from Apache and nginx,
+//! and JSON lines, which also contain the content encoding, the duration and the source id.
+//! A log file can be reopened, e.g. with `SIGUSR1` after it was moved by logrotate.
+//!
+//! Lines are written by a dedicated thread, so requests never wait for the disk.
+
+use crate::utils::JsonValue;
+use anyhow::{bail, Context, Result};
+use std::{
+	fs::{File, OpenOptions},
+	io::{BufWriter, Write},
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	sync::mpsc,
+	thread,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::oneshot;
+
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum AccessLogFormat {
+	/// Common Log Format
+	Common,
+	/// Combined Log Format, with referer and user agent
+	Combined,
+	/// one JSON object per line
+	Json,
+}
+
+impl AccessLogFormat {
+	pub fn parse_str(value: &str) -> Result<AccessLogFormat> {
+		Ok(match value.to_lowercase().trim() {
+			"common" => AccessLogFormat::Common,
+			"combined" => AccessLogFormat::Combined,
+			"json" => AccessLogFormat::Json,
+			_ => {
+				bail!("unknown access log format \"{value}\", use \"common\", \"combined\" or \"json\"")
+			}
+		})
+	}
+}
+
+/// Everything that is known about a finished request.
+pub struct AccessLogEntry<'a> {
+	pub time: SystemTime,
+	pub remote: Option<SocketAddr>,
+	pub method: &'a str,
+	pub path: &'a str,
+	pub version: &'a str,
+	pub status: u16,
+	pub bytes: u64,
+	pub encoding: Option<&'a str>,
+	pub duration: Duration,
+	pub source: &'a str,
+	pub referer: Option<&'a str>,
+	pub user_agent: Option<&'a str>,
+}
+
+impl AccessLogEntry<'_> {
+	fn format(&self, format: AccessLogFormat) -> String {
+		match format {
+			AccessLogFormat::Common => self.format_common(),
+			AccessLogFormat::Combined => format!(
+				"{} \"{}\" \"{}\"",
+				self.format_common(),
+				escape(self.referer.unwrap_or("-")),
+				escape(self.user_agent.unwrap_or("-"))
+			),
+			AccessLogFormat::Json => self.format_json(),
+		}
+	}
+
+	fn format_common(&self) -> String {
+		let remote = self
+			.remote
+			.map_or(String::from("-"), |addr| addr.ip().to_string());
+		let bytes = match self.bytes {
+			0 => String::from("-"),
+			bytes => bytes.to_string(),
+		};
+		format!(
+			"{remote} - - [{}] \"{} {} {}\" {} {bytes}",
+			format_clf_time(self.time),
+			self.method,
+			escape(self.path),
+			self.version,
+			self.status
+		)
+	}
+
+	fn format_json(&self) -> String {
+		let optional = |value: Option<&str>| value.map_or(JsonValue::Null, JsonValue::from);
+		JsonValue::from(vec![
+			("time", JsonValue::from(format_iso_time(self.time))),
+			(
+				"remote",
+				optional(self.remote.map(|addr| addr.ip().to_string()).as_deref()),
+			),
+			("method", JsonValue::from(self.method)),
+			("path", JsonValue::from(self.path)),
+			("version", JsonValue::from(self.version)),
+			("status", JsonValue::from(self.status as u32)),
+			("bytes", JsonValue::from(self.bytes)),
+			("encoding", optional(self.encoding)),
+			(
+				"duration_ms",
+				JsonValue::from((self.duration.as_secs_f64() * 1e6).round() / 1e3),
+			),
+			("source", JsonValue::from(self.source)),
+			("referer", optional(self.referer)),
+			("user_agent", optional(self.user_agent)),
+		])
+		.stringify()
+	}
+}
+
+#[derive(Clone)]
+pub struct AccessLog {
+	format: AccessLogFormat,
+	path: Option<PathBuf>,
+	sender: mpsc::Sender<Message>,
+}
+
+enum Message {
+	Line(String),
+	Reopen(File),
+	Flush(oneshot::Sender<()>),
+}
+
+impl AccessLog {
+	/// Writes the access log to the file at `path` or to stderr.
+	pub fn new(format: AccessLogFormat, path: Option<&Path>) -> Result<AccessLog> {
+		let file = path.map(open_file).transpose()?;
+		let (sender, receiver) = mpsc::channel();
+		thread::Builder::new()
+			.name(String::from("access log"))
+			.spawn(move || write_lines(receiver, file))
+			.context("failed to start access log thread")?;
+		Ok(AccessLog {
+			format,
+			path: path.map(Path::to_path_buf),
+			sender,
+		})
+	}
+
+	/// Opens the log file again, so a rotated file is not written to anymore.
+	pub fn reopen(&self) -> Result<()> {
+		if let Some(path) = &self.path {
+			let file = open_file(path)?;
+			self
+				.sender
+				.send(Message::Reopen(file))
+				.context("access log thread has stopped")?;
+			log::info!("reopened access log {path:?}");
+		}
+		Ok(())
+	}
+
+	pub fn write(&self, entry: &AccessLogEntry) {
+		let mut line = entry.format(self.format);
+		line.push('\n');
+		if self.sender.send(Message::Line(line)).is_err() {
+			log::error!("failed to write access log: thread has stopped");
+		}
+	}
+
+	/// Waits until all lines written so far are flushed.
+	pub async fn flush(&self) {
+		let (sender, receiver) = oneshot::channel();
+		if self.sender.send(Message::Flush(sender)).is_ok() {
+			receiver.await.ok();
+		}
+	}
+}
+
+// runs until every `AccessLog` is dropped, `None` writes to stderr
+fn write_lines(receiver: mpsc::Receiver<Message>, file: Option<File>) {
+	let new_writer = |file: Option<File>| -> BufWriter<Box<dyn Write>> {
+		match file {
+			Some(file) => BufWriter::new(Box::new(file)),
+			None => BufWriter::new(Box::new(std::io::stderr())),
+		}
+	};
+	let mut writer = new_writer(file);
+
+	// flush whenever no more lines are waiting, so they are written in batches under load
+	while let Ok(message) = receiver.recv() {
+		let mut result = Ok(());
+		for message in std::iter::once(message).chain(receiver.try_iter()) {
+			match message {
+				Message::Line(line) => result = result.and(writer.write_all(line.as_bytes())),
+				Message::Reopen(file) => {
+					result = result.and(writer.flush());
+					writer = new_writer(Some(file));
+				}
+				Message::Flush(done) => {
+					result = result.and(writer.flush());
+					done.send(()).ok();
+				}
+			}
+		}
+		if let Err(error) = result.and(writer.flush()) {
+			log::error!("failed to write access log: {error}");
+		}
+	}
+	writer.flush().ok();
+}
+
+fn open_file(path: &Path) -> Result<File> {
+	OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.with_context(|| format!("failed to open access log {path:?}"))
+}
+
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// e.g. "10/Oct/2000:13:55:36 +0000"
+fn format_clf_time(time: SystemTime) -> String {
+	const MONTHS: [&str; 12] = [
+		"Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+	];
+	let (year, month, day, hour, minute, second) = get_utc(time);
+	format!(
+		"{day:02}/{}/{year}:{hour:02}:{minute:02}:{second:02} +0000",
+		MONTHS[month as usize - 1]
+	)
+}
+
+// e.g. "2000-10-10T13:55:36Z"
+fn format_iso_time(time: SystemTime) -> String {
+	let (year, month, day, hour, minute, second) = get_utc(time);
+	format!("{year}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z")
+}
+
+// converts to (year, month, day, hour, minute, second) in UTC, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn get_utc(time: SystemTime) -> (i64, u32, u32, u32, u32, u32) {
+	let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+	let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400) as u32);
+
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+	let year = yoe + era * 400 + i64::from(month <= 2);
+
+	(
+		year,
+		month,
+		day,
+		seconds / 3600,
+		seconds / 60 % 60,
+		seconds % 60,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry() -> AccessLogEntry<'static> {
+		AccessLogEntry {
+			time: UNIX_EPOCH + Duration::from_secs(971186136),
+			remote: Some("127.0.0.1:4711".parse().unwrap()),
+			method: "GET",
+			path: "/tiles/osm/3/4/2.pbf",
+			version: "HTTP/1.1",
+			status: 200,
+			bytes: 2326,
+			encoding: Some("br"),
+			duration: Duration::from_micros(1530),
+			source: "osm",
+			referer: Some("http://www.example.com/start.html"),
+			user_agent: None,
+		}
+	}
+
+	#[test]
+	fn formats() {
+		let entry = entry();
+		assert_eq!(
+			entry.format(AccessLogFormat::Common),
+			"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /tiles/osm/3/4/2.pbf HTTP/1.1\" 200 2326"
+		);
+		assert_eq!(
+			entry.format(AccessLogFormat::Combined),
+			"127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"GET /tiles/osm/3/4/2.pbf HTTP/1.1\" 200 2326 \"http://www.example.com/start.html\" \"-\""
+		);
+		assert_eq!(
+			entry.format(AccessLogFormat::Json),
+			"{\"bytes\":2326,\"duration_ms\":1.53,\"encoding\":\"br\",\"method\":\"GET\",\"path\":\"/tiles/osm/3/4/2.pbf\",\"referer\":\"http://www.example.com/start.html\",\"remote\":\"127.0.0.1\",\"source\":\"osm\",\"status\":200,\"time\":\"2000-10-10T13:55:36Z\",\"user_agent\":null,\"version\":\"HTTP/1.1\"}"
+		);
+
+		let entry = AccessLogEntry {
+			remote: None,
+			path: "/say\"hi\"",
+			bytes: 0,
+			..entry
+		};
+		assert_eq!(
+			entry.format(AccessLogFormat::Common),
+			"- - - [10/Oct/2000:13:55:36 +0000] \"GET /say\\\"hi\\\" HTTP/1.1\" 200 -"
+		);
+	}
+
+	#[test]
+	fn utc() {
+		let utc = |seconds: u64| get_utc(UNIX_EPOCH + Duration::from_secs(seconds));
+		assert_eq!(utc(0), (1970, 1, 1, 0, 0, 0));
+		assert_eq!(utc(951782400), (2000, 2, 29, 0, 0, 0));
+		assert_eq!(utc(1722470399), (2024, 7, 31, 23, 59, 59));
+		assert_eq!(utc(4107542400), (2100, 3, 1, 0, 0, 0));
+	}
+
+	#[test]
+	fn parse_format() {
+		assert_eq!(
+			AccessLogFormat::parse_str("Combined").unwrap(),
+			AccessLogFormat::Combined
+		);
+		assert!(AccessLogFormat::parse_str("apache").is_err());
+	}
+
+	#[tokio::test]
+	async fn reopen() -> Result<()> {
+		let temp_dir = assert_fs::TempDir::new()?;
+		let path = temp_dir.path().join("access.log");
+		let log = AccessLog::new(AccessLogFormat::Common, Some(&path))?;
+
+		log.write(&entry());
+		std::fs::rename(&path, temp_dir.path().join("access.log.1"))?;
+		log.write(&entry());
+		log.reopen()?;
+		log.write(&entry());
+		log.flush().await;
+
+		let count_lines = |name: &str| {
+			std::fs::read_to_string(temp_dir.path().join(name))
+				.unwrap()
+				.lines()
+				.count()
+		};
+		assert_eq!(count_lines("access.log.1"), 2);
+		assert_eq!(count_lines("access.log"), 1);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,204 @@
+//! in-memory cache of tile responses, so popular tiles are neither read nor recompressed again
+//!
+//! Entries are keyed by the generation of the source, tile coordinate, tile format and the content encoding that was sent,
+//! or `None` for images, which are always sent in their stored encoding. They are evicted by least recent use once the memory budget is reached.
+
+use super::{
+	sources::{get_mime, SourceResponse, TileSource},
+	utils::is_incompressible,
+};
+use crate::{
+	types::{LimitedCache, TileCompression, TileCoord3, TileFormat},
+	utils::{get_optimal_compression, recompress, JsonValue, TargetCompression},
+};
+use std::{
+	mem::size_of,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc, Mutex,
+	},
+};
+
+type CacheKey = (u64, TileCoord3, TileFormat, Option<TileCompression>);
+
+#[derive(Clone)]
+pub struct ResponseCache {
+	cache: Arc<Mutex<LimitedCache<CacheKey, SourceResponse>>>,
+	hits: Arc<AtomicU64>,
+	misses: Arc<AtomicU64>,
+}
+
+/// Counters of the cache, e.g. for the API and the metrics.
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	pub entries: usize,
+	pub size: usize,
+	pub maximum_size: usize,
+}
+
+impl ResponseCache {
+	/// Creates a cache that uses up to `maximum_size` bytes.
+	pub fn new(maximum_size: usize) -> ResponseCache {
+		ResponseCache {
+			cache: Arc::new(Mutex::new(LimitedCache::with_size_function(
+				maximum_size,
+				get_entry_size,
+			))),
+			hits: Default::default(),
+			misses: Default::default(),
+		}
+	}
+
+	/// Returns the tile in `format` and in the encoding that is optimal for `target`, from the cache if possible.
+	pub async fn get_tile(
+		&self,
+		source: &TileSource,
+		coord: &TileCoord3,
+		format: TileFormat,
+		target: &TargetCompression,
+	) -> Option<SourceResponse> {
+		let compression = if is_incompressible(get_mime(format)) {
+			None
+		} else {
+			Some(get_optimal_compression(&source.compression, target).ok()?)
+		};
+		let key = (source.generation, *coord, format, compression);
+
+		if let Some(response) = self.lock().get(&key) {
+			self.hits.fetch_add(1, Ordering::Relaxed);
+			return Some(response);
+		}
+		self.misses.fetch_add(1, Ordering::Relaxed);
+
+		// the lock is not held while reading, so other requests are not blocked
+		let mut response = source.get_tile_as(coord, format).await?;
+		if let Some(compression) = compression {
+			response.etag = Some(response.get_etag_for(&compression));
+			response.blob = recompress(response.blob, &response.compression, &compression).ok()?;
+			response.compression = compression;
+		}
+
+		Some(self.lock().add(key, response))
+	}
+
+	/// Removes all responses, e.g. to free the memory of replaced sources.
+	pub fn clear(&self) {
+		self.lock().clear();
+	}
+
+	pub fn get_stats(&self) -> CacheStats {
+		let cache = self.lock();
+		CacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			entries: cache.len(),
+			size: cache.size(),
+			maximum_size: cache.maximum_size(),
+		}
+	}
+
+	fn lock(&self) -> std::sync::MutexGuard<'_, LimitedCache<CacheKey, SourceResponse>> {
+		self
+			.cache
+			.lock()
+			.expect("cache lock should not be poisoned")
+	}
+}
+
+impl CacheStats {
+	pub fn as_json(&self) -> JsonValue {
+		JsonValue::from(vec![
+			("hits", self.hits),
+			("misses", self.misses),
+			("entries", self.entries as u64),
+			("size", self.size as u64),
+			("maximum_size", self.maximum_size as u64),
+		])
+	}
+}
+
+// the blob dominates the size, the rest is a rough estimate
+fn get_entry_size(_key: &CacheKey, response: &SourceResponse) -> usize {
+	size_of::<CacheKey>()
+		+ size_of::<SourceResponse>()
+		+ response.blob.len() as usize
+		+ response.mime.len()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		container::{MockTilesReader, MockTilesReaderProfile},
+		tools::server::Url,
+		types::{
+			TileCompression::*,
+			TileFormat::{PBF, PNG},
+			TilesReaderTrait,
+		},
+		utils::decompress_brotli,
+	};
+	use enumset::enum_set;
+
+	#[tokio::test]
+	async fn get_tile() {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf).unwrap();
+		let source = TileSource::from(reader.boxed(), Url::new("tiles/cheese/")).unwrap();
+		let cache = ResponseCache::new(1_000_000);
+		let coord = TileCoord3::new(0, 0, 0).unwrap();
+
+		let brotli = TargetCompression::from_set(enum_set!(Gzip | Brotli));
+		let gzip = TargetCompression::from_set(enum_set!(Gzip));
+
+		let response = cache.get_tile(&source, &coord, PBF, &brotli).await.unwrap();
+		assert_eq!(response.compression, Brotli);
+		assert!(decompress_brotli(&response.blob).is_ok());
+
+		cache.get_tile(&source, &coord, PBF, &brotli).await.unwrap();
+		let response = cache.get_tile(&source, &coord, PBF, &gzip).await.unwrap();
+		assert_eq!(response.compression, Gzip);
+
+		let stats = cache.get_stats();
+		assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+		assert!(stats.size > 0);
+
+		// the same ETag as without the cache
+		let uncached = source.get_tile_as(&coord, PBF).await.unwrap();
+		assert_eq!(response.etag, Some(uncached.get_etag_for(&Gzip)));
+	}
+
+	#[tokio::test]
+	async fn images_keep_their_encoding() {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png).unwrap();
+		let source = TileSource::from(reader.boxed(), Url::new("tiles/sat/")).unwrap();
+		let cache = ResponseCache::new(1_000_000);
+		let coord = TileCoord3::new(0, 0, 0).unwrap();
+
+		let brotli = TargetCompression::from_set(enum_set!(Gzip | Brotli));
+		let none = TargetCompression::from_set(enum_set!(Uncompressed));
+
+		let response = cache.get_tile(&source, &coord, PNG, &brotli).await.unwrap();
+		assert_eq!(response.compression, Uncompressed);
+		assert_eq!(response.mime, "image/png");
+
+		cache.get_tile(&source, &coord, PNG, &none).await.unwrap();
+		let stats = cache.get_stats();
+		assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+	}
+
+	#[test]
+	fn stats_as_json() {
+		let stats = CacheStats {
+			hits: 3,
+			misses: 1,
+			entries: 1,
+			size: 1234,
+			maximum_size: 100000,
+		};
+		assert_eq!(
+			stats.as_json().stringify(),
+			"{\"entries\":1,\"hits\":3,\"maximum_size\":100000,\"misses\":1,\"size\":1234}"
+		);
+	}
+}
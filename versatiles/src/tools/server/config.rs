@@ -0,0 +1,574 @@
+//! declarative configuration of the server, loaded from a JSON file with `serve --config`
+//!
+//! Example:
+//! ```json
+//! {
+//!   "listen": ["0.0.0.0:8080", "[::]:8080"],
+//!   "allowed_origins": ["https://example.org"],
+//!   "max_age": 86400,
+//!   "headers": { "X-Served-By": "versatiles" },
+//!   "fast": false,
+//!   "disable_api": false,
+//!   "metrics": true,
+//!   "cache_size": 512,
+//!   "access_log": "combined",
+//!   "access_log_file": "/var/log/versatiles/access.log",
+//!   "admin_token": "secret",
+//!   "base_url": "https://tiles.example.org",
+//!   "tile_sources": [
+//!     { "id": "osm", "path": "osm-20240801.versatiles", "max_age": 31536000 },
+//!     { "id": "legacy", "path": "legacy.mbtiles", "scheme": "tms" },
+//!     { "id": "berlin", "vpl": "from_container filename=\"berlin.mbtiles\"", "flip_y": true, "overzoom": 18 }
+//!   ],
+//!   "static_sources": [
+//!     { "path": "frontend.tar.br" },
+//!     { "path": "styles", "prefix": "/assets/styles" }
+//!   ]
+//! }
+//! ```
+//!
+//! Relative paths are resolved from the directory of the config file.
+//! Every tile and static source can override "allowed_origins", "max_age" and add "headers".
+//! With an "admin_token" the sources can be reloaded with `POST /api/admin/reload`.
+//! With "overzoom" a tile source serves tiles beyond its maximum zoom level, up to the given level.
+//! "base_url" is the start of the absolute URLs in "tiles.json" and the WMTS capabilities, by default it is built from the `Host` header.
+//! Instead, `"trust_proxy": true` takes it from the `X-Forwarded-Host` and `X-Forwarded-Proto` headers;
+//! only use this behind a reverse proxy that sets these headers.
+//! The "scheme" of a tile source sets the url of its tiles: "xyz" (default), "tms", "zyx" or "quadkey".
+
+use super::{
+	access_log::AccessLogFormat,
+	options::{parse_base_url, parse_header_pair, BaseUrl, SourceOptions},
+	sources::UrlScheme,
+};
+use crate::{
+	types::TileCompression,
+	utils::{parse_json, JsonValue},
+};
+use anyhow::{bail, ensure, Context, Result};
+use axum::http::{HeaderName, HeaderValue};
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ServerConfig {
+	/// addresses to listen on, e.g. "0.0.0.0:8080"
+	pub listen: Vec<(String, u16)>,
+	/// origins that are allowed to access the server, `*` allows every origin
+	pub allowed_origins: Option<Vec<String>>,
+	/// `max-age` of the `Cache-Control` header in seconds
+	pub max_age: Option<u64>,
+	/// additional headers of every response
+	pub headers: Vec<(HeaderName, HeaderValue)>,
+	/// use minimal recompression to reduce server response time
+	pub fast: bool,
+	pub disable_api: bool,
+	/// serve the local container files themselves at "/files/$id.$extension"
+	pub expose_containers: bool,
+	/// serve request metrics for Prometheus at "/metrics"
+	pub metrics: bool,
+	/// memory budget of the tile cache in megabytes
+	pub cache_size: Option<u64>,
+	/// format of the access log, it is written to stderr if no file is set
+	pub access_log: Option<AccessLogFormat>,
+	pub access_log_file: Option<PathBuf>,
+	/// bearer token of the admin API, which is disabled without it
+	pub admin_token: Option<String>,
+	/// start of the absolute URLs in "tiles.json" and the WMTS capabilities
+	pub base_url: BaseUrl,
+	pub tile_sources: Vec<TileSourceConfig>,
+	pub static_sources: Vec<StaticSourceConfig>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TileSourceConfig {
+	/// id used in the url: "/tiles/$id/"
+	pub id: String,
+	pub input: TileSourceInput,
+	pub flip_y: bool,
+	pub swap_xy: bool,
+	pub override_compression: Option<TileCompression>,
+	/// serve tiles up to this zoom level, deeper tiles are cut out of the tiles at the maximum zoom level
+	pub overzoom: Option<u8>,
+	/// how tile coordinates are encoded in the url
+	pub scheme: UrlScheme,
+	pub options: SourceOptions,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TileSourceInput {
+	/// a container file, a directory or a url
+	Path(String),
+	/// a VersaTiles pipeline, relative file names are resolved from `dir`
+	Vpl { vpl: String, dir: PathBuf },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StaticSourceConfig {
+	/// a folder or a tar file
+	pub path: PathBuf,
+	/// url prefix, e.g. "/assets/styles"
+	pub prefix: String,
+	pub options: SourceOptions,
+}
+
+impl TileSourceConfig {
+	pub fn new(id: &str, path: &str) -> TileSourceConfig {
+		TileSourceConfig {
+			id: id.to_owned(),
+			input: TileSourceInput::Path(path.to_owned()),
+			flip_y: false,
+			swap_xy: false,
+			override_compression: None,
+			overzoom: None,
+			scheme: UrlScheme::default(),
+			options: SourceOptions::default(),
+		}
+	}
+}
+
+impl ServerConfig {
+	pub fn from_path(path: &Path) -> Result<ServerConfig> {
+		let json = std::fs::read_to_string(path)
+			.with_context(|| format!("failed to read config file {path:?}"))?;
+		let dir = path.parent().unwrap_or(Path::new(""));
+		ServerConfig::from_json(&json, dir)
+			.with_context(|| format!("failed to parse config file {path:?}"))
+	}
+
+	/// Parses a JSON config. Relative paths are resolved from `dir`.
+	pub fn from_json(json: &str, dir: &Path) -> Result<ServerConfig> {
+		let json = parse_json(json)?;
+		let object = Object::from(&json, "config")?;
+		object.check_keys(&[
+			"listen",
+			"allowed_origins",
+			"max_age",
+			"headers",
+			"fast",
+			"disable_api",
+			"expose_containers",
+			"metrics",
+			"cache_size",
+			"access_log",
+			"access_log_file",
+			"admin_token",
+			"base_url",
+			"trust_proxy",
+			"tile_sources",
+			"static_sources",
+		])?;
+
+		let mut config = ServerConfig {
+			listen: object
+				.get_strings("listen")?
+				.unwrap_or_default()
+				.iter()
+				.map(|address| parse_address(address))
+				.collect::<Result<Vec<_>>>()?,
+			allowed_origins: object.get_strings("allowed_origins")?,
+			max_age: object.get_u64("max_age")?,
+			headers: object.get_headers("headers")?,
+			fast: object.get_bool("fast")?,
+			disable_api: object.get_bool("disable_api")?,
+			expose_containers: object.get_bool("expose_containers")?,
+			metrics: object.get_bool("metrics")?,
+			cache_size: object.get_u64("cache_size")?,
+			access_log: object
+				.get_string("access_log")?
+				.map(|format| AccessLogFormat::parse_str(&format))
+				.transpose()?,
+			access_log_file: object
+				.get_string("access_log_file")?
+				.map(|path| dir.join(path)),
+			admin_token: object.get_string("admin_token")?,
+			base_url: match (
+				object.get_string("base_url")?,
+				object.get_bool("trust_proxy")?,
+			) {
+				(None, false) => BaseUrl::FromHost,
+				(Some(url), false) => parse_base_url(&url)?,
+				(None, true) => BaseUrl::FromProxy,
+				(Some(_), true) => bail!("\"base_url\" and \"trust_proxy\" can not be used together"),
+			},
+			..Default::default()
+		};
+
+		for value in object.get_array("tile_sources")? {
+			let source = Object::from(value, "tile source")?;
+			source.check_keys(&[
+				"id",
+				"path",
+				"vpl",
+				"flip_y",
+				"swap_xy",
+				"override_compression",
+				"overzoom",
+				"scheme",
+				"allowed_origins",
+				"max_age",
+				"headers",
+			])?;
+
+			let input = match (source.get_string("path")?, source.get_string("vpl")?) {
+				(Some(path), None) => TileSourceInput::Path(resolve_path(dir, &path)),
+				(None, Some(vpl)) => TileSourceInput::Vpl {
+					vpl,
+					dir: dir.to_path_buf(),
+				},
+				_ => bail!("a tile source must have either a \"path\" or a \"vpl\""),
+			};
+
+			let id = match source.get_string("id")? {
+				Some(id) => id,
+				None => match &input {
+					TileSourceInput::Path(path) => get_id_from_path(path),
+					TileSourceInput::Vpl { .. } => bail!("a tile source with a \"vpl\" needs an \"id\""),
+				},
+			};
+
+			config.tile_sources.push(TileSourceConfig {
+				id,
+				input,
+				flip_y: source.get_bool("flip_y")?,
+				swap_xy: source.get_bool("swap_xy")?,
+				override_compression: source
+					.get_string("override_compression")?
+					.map(|c| TileCompression::parse_str(&c))
+					.transpose()?,
+				overzoom: source
+					.get_u64("overzoom")?
+					.map(|zoom| {
+						u8::try_from(zoom)
+							.ok()
+							.filter(|zoom| *zoom <= 30)
+							.context("\"overzoom\" in tile source must be a zoom level <= 30")
+					})
+					.transpose()?,
+				scheme: source
+					.get_string("scheme")?
+					.map(|scheme| UrlScheme::parse_str(&scheme))
+					.transpose()?
+					.unwrap_or_default(),
+				options: source.get_source_options()?,
+			});
+		}
+
+		for value in object.get_array("static_sources")? {
+			let source = Object::from(value, "static source")?;
+			source.check_keys(&["path", "prefix", "allowed_origins", "max_age", "headers"])?;
+
+			let path = source
+				.get_string("path")?
+				.context("a static source needs a \"path\"")?;
+			config.static_sources.push(StaticSourceConfig {
+				path: dir.join(path),
+				prefix: source.get_string("prefix")?.unwrap_or_default(),
+				options: source.get_source_options()?,
+			});
+		}
+
+		Ok(config)
+	}
+}
+
+/// Generates the id of a tile source from the file name, e.g. ".../ukraine.versatiles" -> "ukraine"
+pub fn get_id_from_path(path: &str) -> String {
+	path
+		.rsplit(['/', '\\'])
+		.next()
+		.unwrap()
+		.split('.')
+		.next()
+		.unwrap()
+		.to_owned()
+}
+
+fn resolve_path(dir: &Path, path: &str) -> String {
+	if path.starts_with("http://") || path.starts_with("https://") {
+		path.to_owned()
+	} else {
+		dir.join(path).to_string_lossy().into_owned()
+	}
+}
+
+fn parse_address(address: &str) -> Result<(String, u16)> {
+	let (ip, port) = address
+		.rsplit_once(':')
+		.with_context(|| format!("listen address \"{address}\" must have the form \"ip:port\""))?;
+	let port = port
+		.parse::<u16>()
+		.with_context(|| format!("invalid port in listen address \"{address}\""))?;
+	ensure!(!ip.is_empty(), "missing ip in listen address \"{address}\"");
+	Ok((ip.to_owned(), port))
+}
+
+struct Object<'a> {
+	map: &'a BTreeMap<String, JsonValue>,
+	name: &'a str,
+}
+
+impl<'a> Object<'a> {
+	fn from(value: &'a JsonValue, name: &'a str) -> Result<Object<'a>> {
+		match value {
+			JsonValue::Object(map) => Ok(Object { map, name }),
+			_ => bail!("{name} must be an object, not {}", value.type_as_str()),
+		}
+	}
+
+	// unknown keys are rejected, so typos are not ignored silently
+	fn check_keys(&self, keys: &[&str]) -> Result<()> {
+		for key in self.map.keys() {
+			ensure!(
+				keys.contains(&key.as_str()),
+				"unknown key \"{key}\" in {}, allowed keys are: {}",
+				self.name,
+				keys.join(", ")
+			);
+		}
+		Ok(())
+	}
+
+	fn get(&self, key: &str) -> Option<&'a JsonValue> {
+		self.map.get(key).filter(|v| **v != JsonValue::Null)
+	}
+
+	fn get_string(&self, key: &str) -> Result<Option<String>> {
+		self
+			.get(key)
+			.map(|v| v.as_string())
+			.transpose()
+			.with_context(|| format!("\"{key}\" in {}", self.name))
+	}
+
+	fn get_bool(&self, key: &str) -> Result<bool> {
+		match self.get(key) {
+			None => Ok(false),
+			Some(JsonValue::Boolean(b)) => Ok(*b),
+			Some(v) => bail!(
+				"\"{key}\" in {} must be a boolean, not {}",
+				self.name,
+				v.type_as_str()
+			),
+		}
+	}
+
+	fn get_u64(&self, key: &str) -> Result<Option<u64>> {
+		self
+			.get(key)
+			.map(|v| v.as_u64())
+			.transpose()
+			.with_context(|| format!("\"{key}\" in {}", self.name))
+	}
+
+	fn get_array(&self, key: &str) -> Result<&'a [JsonValue]> {
+		match self.get(key) {
+			None => Ok(&[]),
+			Some(JsonValue::Array(array)) => Ok(array),
+			Some(v) => bail!(
+				"\"{key}\" in {} must be an array, not {}",
+				self.name,
+				v.type_as_str()
+			),
+		}
+	}
+
+	fn get_headers(&self, key: &str) -> Result<Vec<(HeaderName, HeaderValue)>> {
+		match self.get(key) {
+			None => Ok(Vec::new()),
+			Some(value) => {
+				let headers = Object::from(value, key)?;
+				headers
+					.map
+					.iter()
+					.map(|(name, value)| parse_header_pair(name, value.as_str()?))
+					.collect::<Result<Vec<_>>>()
+					.with_context(|| format!("\"{key}\" in {}", self.name))
+			}
+		}
+	}
+
+	fn get_source_options(&self) -> Result<SourceOptions> {
+		Ok(SourceOptions {
+			allowed_origins: self.get_strings("allowed_origins")?,
+			max_age: self.get_u64("max_age")?,
+			extra_headers: self.get_headers("headers")?,
+		})
+	}
+
+	fn get_strings(&self, key: &str) -> Result<Option<Vec<String>>> {
+		if self.get(key).is_none() {
+			return Ok(None);
+		}
+		self
+			.get_array(key)?
+			.iter()
+			.map(|v| v.as_string())
+			.collect::<Result<Vec<_>>>()
+			.with_context(|| format!("\"{key}\" in {}", self.name))
+			.map(Some)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn full_config() {
+		let json = r#"{
+			"listen": ["127.0.0.1:8080", "[::1]:8081"],
+			"allowed_origins": ["https://example.org"],
+			"max_age": 86400,
+			"headers": { "X-Served-By": "versatiles" },
+			"fast": true,
+			"metrics": true,
+			"cache_size": 512,
+			"access_log": "json",
+			"access_log_file": "logs/access.log",
+			"admin_token": "secret",
+			"base_url": "https://tiles.example.org/",
+			"tile_sources": [
+				{ "path": "data/osm.versatiles", "max_age": 31536000, "headers": { "X-Release": "2024" } },
+				{ "id": "remote", "path": "https://example.org/planet.versatiles", "scheme": "quadkey" },
+				{ "id": "debug", "vpl": "from_debug format=png", "flip_y": true, "override_compression": "gzip", "overzoom": 16 }
+			],
+			"static_sources": [
+				{ "path": "frontend.tar" },
+				{ "path": "/srv/styles", "prefix": "/assets/styles", "allowed_origins": ["*"] }
+			]
+		}"#;
+		let dir = Path::new("/etc/versatiles");
+		let config = ServerConfig::from_json(json, dir).unwrap();
+
+		assert_eq!(
+			config,
+			ServerConfig {
+				listen: vec![
+					(String::from("127.0.0.1"), 8080),
+					(String::from("[::1]"), 8081)
+				],
+				allowed_origins: Some(vec![String::from("https://example.org")]),
+				max_age: Some(86400),
+				headers: vec![parse_header_pair("X-Served-By", "versatiles").unwrap()],
+				fast: true,
+				disable_api: false,
+				expose_containers: false,
+				metrics: true,
+				cache_size: Some(512),
+				access_log: Some(AccessLogFormat::Json),
+				access_log_file: Some(PathBuf::from("/etc/versatiles/logs/access.log")),
+				admin_token: Some(String::from("secret")),
+				base_url: BaseUrl::Fixed(String::from("https://tiles.example.org")),
+				tile_sources: vec![
+					TileSourceConfig {
+						options: SourceOptions {
+							max_age: Some(31536000),
+							extra_headers: vec![parse_header_pair("X-Release", "2024").unwrap()],
+							..Default::default()
+						},
+						..TileSourceConfig::new("osm", "/etc/versatiles/data/osm.versatiles")
+					},
+					TileSourceConfig {
+						scheme: UrlScheme::Quadkey,
+						..TileSourceConfig::new("remote", "https://example.org/planet.versatiles")
+					},
+					TileSourceConfig {
+						id: String::from("debug"),
+						input: TileSourceInput::Vpl {
+							vpl: String::from("from_debug format=png"),
+							dir: dir.to_path_buf()
+						},
+						flip_y: true,
+						swap_xy: false,
+						override_compression: Some(TileCompression::Gzip),
+						overzoom: Some(16),
+						scheme: UrlScheme::Xyz,
+						options: SourceOptions::default(),
+					}
+				],
+				static_sources: vec![
+					StaticSourceConfig {
+						path: PathBuf::from("/etc/versatiles/frontend.tar"),
+						prefix: String::from(""),
+						options: SourceOptions::default(),
+					},
+					StaticSourceConfig {
+						path: PathBuf::from("/srv/styles"),
+						prefix: String::from("/assets/styles"),
+						options: SourceOptions {
+							allowed_origins: Some(vec![String::from("*")]),
+							..Default::default()
+						},
+					}
+				],
+			}
+		);
+	}
+
+	#[test]
+	fn empty_config() {
+		let config = ServerConfig::from_json("{}", Path::new("")).unwrap();
+		assert_eq!(config, ServerConfig::default());
+	}
+
+	#[test]
+	fn errors() {
+		let check = |json: &str, message: &str| {
+			let error = ServerConfig::from_json(json, Path::new("")).unwrap_err();
+			assert_eq!(format!("{error:#}"), message);
+		};
+
+		check("[]", "config must be an object, not array");
+		check(
+			r#"{"port":8080}"#,
+			"unknown key \"port\" in config, allowed keys are: listen, allowed_origins, max_age, headers, fast, disable_api, expose_containers, metrics, cache_size, access_log, access_log_file, admin_token, base_url, trust_proxy, tile_sources, static_sources",
+		);
+		check(
+			r#"{"listen":["8080"]}"#,
+			"listen address \"8080\" must have the form \"ip:port\"",
+		);
+		check(
+			r#"{"fast":"yes"}"#,
+			"\"fast\" in config must be a boolean, not string",
+		);
+		check(
+			r#"{"tile_sources":[{"id":"a"}]}"#,
+			"a tile source must have either a \"path\" or a \"vpl\"",
+		);
+		check(
+			r#"{"tile_sources":[{"vpl":"from_debug"}]}"#,
+			"a tile source with a \"vpl\" needs an \"id\"",
+		);
+		check(
+			r#"{"static_sources":[{"prefix":"/"}]}"#,
+			"a static source needs a \"path\"",
+		);
+		check(
+			r#"{"headers":{"X Served By":"me"}}"#,
+			"\"headers\" in config: invalid header name \"X Served By\": invalid HTTP header name",
+		);
+		check(
+			r#"{"tile_sources":[{"path":"a.pmtiles","overzoom":40}]}"#,
+			"\"overzoom\" in tile source must be a zoom level <= 30",
+		);
+		check(
+			r#"{"tile_sources":[{"path":"a.pmtiles","scheme":"wms"}]}"#,
+			"unknown url scheme \"wms\", use \"xyz\", \"tms\", \"zyx\" or \"quadkey\"",
+		);
+		check(
+			r#"{"base_url":"https://tiles.example.org","trust_proxy":true}"#,
+			"\"base_url\" and \"trust_proxy\" can not be used together",
+		);
+		check(
+			r#"{"base_url":"tiles.example.org"}"#,
+			"base url \"tiles.example.org\" must start with \"http://\" or \"https://\"",
+		);
+		check(
+			r#"{"max_age":"long"}"#,
+			"\"max_age\" in config: value has type 'string' and not 'number'",
+		);
+	}
+}
@@ -0,0 +1,266 @@
+//! request metrics of the server in the Prometheus text format, served at "/metrics"
+//!
+//! Every request is counted per source, status code and content encoding.
+//! Tile sources are labeled with their id, static sources with "static" and the API with "api".
+//! If the response cache is enabled, its hits, misses and size are included.
+
+use super::cache::CacheStats;
+use std::{
+	collections::BTreeMap,
+	fmt::Write,
+	sync::{Arc, Mutex},
+};
+
+/// upper bounds of the latency histogram buckets in seconds
+const BUCKETS: [f64; 12] = [
+	0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+	inner: Arc<Mutex<MetricsData>>,
+}
+
+#[derive(Default)]
+struct MetricsData {
+	// (source, status, compression) -> count
+	requests: BTreeMap<(String, u16, String), u64>,
+	bytes: BTreeMap<String, u64>,
+	latencies: BTreeMap<String, Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+	buckets: [u64; BUCKETS.len()],
+	sum: f64,
+	count: u64,
+}
+
+impl Histogram {
+	fn add(&mut self, value: f64) {
+		for (bucket, limit) in self.buckets.iter_mut().zip(BUCKETS) {
+			if value <= limit {
+				*bucket += 1;
+			}
+		}
+		self.sum += value;
+		self.count += 1;
+	}
+}
+
+impl Metrics {
+	pub fn new() -> Metrics {
+		Metrics::default()
+	}
+
+	/// Records a finished request.
+	pub fn add_request(
+		&self,
+		source: &str,
+		status: u16,
+		compression: &str,
+		bytes: u64,
+		seconds: f64,
+	) {
+		let mut data = self
+			.inner
+			.lock()
+			.expect("metrics lock should not be poisoned");
+		*data
+			.requests
+			.entry((source.to_owned(), status, compression.to_owned()))
+			.or_default() += 1;
+		*data.bytes.entry(source.to_owned()).or_default() += bytes;
+		data
+			.latencies
+			.entry(source.to_owned())
+			.or_default()
+			.add(seconds);
+	}
+
+	/// Renders all metrics in the Prometheus text exposition format.
+	pub fn render(&self, cache: Option<&CacheStats>) -> String {
+		let data = self
+			.inner
+			.lock()
+			.expect("metrics lock should not be poisoned");
+		let mut text = String::new();
+
+		write_header(
+			&mut text,
+			"versatiles_requests_total",
+			"counter",
+			"Number of HTTP requests.",
+		);
+		for ((source, status, compression), count) in data.requests.iter() {
+			writeln!(
+				text,
+				"versatiles_requests_total{{source=\"{}\",status=\"{status}\",compression=\"{}\"}} {count}",
+				escape(source),
+				escape(compression)
+			)
+			.unwrap();
+		}
+
+		write_header(
+			&mut text,
+			"versatiles_response_bytes_total",
+			"counter",
+			"Number of body bytes sent.",
+		);
+		for (source, bytes) in data.bytes.iter() {
+			writeln!(
+				text,
+				"versatiles_response_bytes_total{{source=\"{}\"}} {bytes}",
+				escape(source)
+			)
+			.unwrap();
+		}
+
+		write_header(
+			&mut text,
+			"versatiles_request_duration_seconds",
+			"histogram",
+			"Time to answer HTTP requests.",
+		);
+		for (source, histogram) in data.latencies.iter() {
+			let source = escape(source);
+			for (limit, count) in BUCKETS.iter().zip(histogram.buckets) {
+				writeln!(
+					text,
+					"versatiles_request_duration_seconds_bucket{{source=\"{source}\",le=\"{limit}\"}} {count}"
+				)
+				.unwrap();
+			}
+			writeln!(
+				text,
+				"versatiles_request_duration_seconds_bucket{{source=\"{source}\",le=\"+Inf\"}} {}",
+				histogram.count
+			)
+			.unwrap();
+			writeln!(
+				text,
+				"versatiles_request_duration_seconds_sum{{source=\"{source}\"}} {}",
+				histogram.sum
+			)
+			.unwrap();
+			writeln!(
+				text,
+				"versatiles_request_duration_seconds_count{{source=\"{source}\"}} {}",
+				histogram.count
+			)
+			.unwrap();
+		}
+
+		if let Some(cache) = cache {
+			write_header(
+				&mut text,
+				"versatiles_cache_requests_total",
+				"counter",
+				"Number of tile requests answered from the cache (hit) or not (miss).",
+			);
+			writeln!(
+				text,
+				"versatiles_cache_requests_total{{result=\"hit\"}} {}",
+				cache.hits
+			)
+			.unwrap();
+			writeln!(
+				text,
+				"versatiles_cache_requests_total{{result=\"miss\"}} {}",
+				cache.misses
+			)
+			.unwrap();
+
+			write_header(
+				&mut text,
+				"versatiles_cache_entries",
+				"gauge",
+				"Number of responses in the cache.",
+			);
+			writeln!(text, "versatiles_cache_entries {}", cache.entries).unwrap();
+
+			write_header(
+				&mut text,
+				"versatiles_cache_size_bytes",
+				"gauge",
+				"Memory used by the cache.",
+			);
+			writeln!(text, "versatiles_cache_size_bytes {}", cache.size).unwrap();
+		}
+
+		text
+	}
+}
+
+fn write_header(text: &mut String, name: &str, kind: &str, help: &str) {
+	writeln!(text, "# HELP {name} {help}").unwrap();
+	writeln!(text, "# TYPE {name} {kind}").unwrap();
+}
+
+fn escape(value: &str) -> String {
+	value
+		.replace('\\', "\\\\")
+		.replace('"', "\\\"")
+		.replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn render() {
+		let metrics = Metrics::new();
+		metrics.add_request("osm", 200, "br", 1000, 0.003);
+		metrics.add_request("osm", 200, "br", 500, 0.2);
+		metrics.add_request("osm", 404, "none", 9, 0.0001);
+		metrics.add_request("say \"hi\"", 200, "none", 0, 7.0);
+
+		let text = metrics.render(None);
+		let lines: Vec<&str> = text.lines().collect();
+
+		assert_eq!(
+			lines[0..6],
+			[
+				"# HELP versatiles_requests_total Number of HTTP requests.",
+				"# TYPE versatiles_requests_total counter",
+				"versatiles_requests_total{source=\"osm\",status=\"200\",compression=\"br\"} 2",
+				"versatiles_requests_total{source=\"osm\",status=\"404\",compression=\"none\"} 1",
+				"versatiles_requests_total{source=\"say \\\"hi\\\"\",status=\"200\",compression=\"none\"} 1",
+				"# HELP versatiles_response_bytes_total Number of body bytes sent.",
+			]
+		);
+		assert!(lines.contains(&"versatiles_response_bytes_total{source=\"osm\"} 1509"));
+		assert!(lines.contains(&"# TYPE versatiles_request_duration_seconds histogram"));
+		assert!(lines
+			.contains(&"versatiles_request_duration_seconds_bucket{source=\"osm\",le=\"0.001\"} 1"));
+		assert!(lines
+			.contains(&"versatiles_request_duration_seconds_bucket{source=\"osm\",le=\"0.005\"} 2"));
+		assert!(lines
+			.contains(&"versatiles_request_duration_seconds_bucket{source=\"osm\",le=\"0.25\"} 3"));
+		assert!(lines
+			.contains(&"versatiles_request_duration_seconds_bucket{source=\"osm\",le=\"+Inf\"} 3"));
+		assert!(lines.contains(&"versatiles_request_duration_seconds_count{source=\"osm\"} 3"));
+		assert!(lines.contains(
+			&"versatiles_request_duration_seconds_bucket{source=\"say \\\"hi\\\"\",le=\"5\"} 0"
+		));
+	}
+
+	#[test]
+	fn render_cache() {
+		let cache = CacheStats {
+			hits: 3,
+			misses: 1,
+			entries: 1,
+			size: 1234,
+			maximum_size: 100000,
+		};
+		let text = Metrics::new().render(Some(&cache));
+		let lines: Vec<&str> = text.lines().collect();
+		assert!(lines.contains(&"versatiles_cache_requests_total{result=\"hit\"} 3"));
+		assert!(lines.contains(&"versatiles_cache_requests_total{result=\"miss\"} 1"));
+		assert!(lines.contains(&"versatiles_cache_entries 1"));
+		assert!(lines.contains(&"versatiles_cache_size_bytes 1234"));
+	}
+}
@@ -1,8 +1,20 @@
 //! server implementation
 
+mod access_log;
+mod cache;
+mod config;
+mod metrics;
+mod options;
+mod reload;
 mod sources;
 mod tile_server;
 mod utils;
+mod wmts;
 
+pub use access_log::{AccessLog, AccessLogFormat};
+pub use config::*;
+pub use options::{parse_base_url, parse_header, BaseUrl};
+pub use reload::*;
+pub use sources::UrlScheme;
 pub use tile_server::*;
 pub use utils::Url;
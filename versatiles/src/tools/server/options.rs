@@ -0,0 +1,402 @@
+//! options that control how the server builds its responses
+
+use anyhow::{ensure, Context, Result};
+use axum::http::{
+	header::{
+		ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+		ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, HOST,
+		ORIGIN, VARY,
+	},
+	HeaderMap, HeaderName, HeaderValue,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponseOptions {
+	/// use the best compression, even if it is slower
+	pub best_compression: bool,
+	/// origins that are allowed to access the server, `*` allows every origin
+	pub allowed_origins: Vec<String>,
+	/// `max-age` of the `Cache-Control` header in seconds
+	pub max_age: u64,
+	/// additional headers of every response, they replace headers with the same name
+	pub extra_headers: Vec<(HeaderName, HeaderValue)>,
+	/// scheme and host of the URLs in `tiles.json` and the WMTS capabilities
+	pub base_url: BaseUrl,
+}
+
+/// Where the scheme and host of absolute URLs in responses come from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum BaseUrl {
+	/// "http://" and the `Host` header of the request, which caches include in their keys
+	#[default]
+	FromHost,
+	/// a fixed base URL like "https://tiles.example.org"
+	Fixed(String),
+	/// the `X-Forwarded-Host` and `X-Forwarded-Proto` headers of the request, falling back to `Host`.
+	/// Clients can set them to anything, so only use this behind a proxy that overwrites them.
+	FromProxy,
+}
+
+/// Options of a single source, overriding the options of the server.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceOptions {
+	pub allowed_origins: Option<Vec<String>>,
+	pub max_age: Option<u64>,
+	/// added to the extra headers of the server
+	pub extra_headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl Default for ResponseOptions {
+	fn default() -> Self {
+		ResponseOptions {
+			best_compression: true,
+			allowed_origins: vec![String::from("*")],
+			max_age: 2419200,
+			extra_headers: Vec::new(),
+			base_url: BaseUrl::FromHost,
+		}
+	}
+}
+
+impl ResponseOptions {
+	/// Returns the options for a source, with its overrides applied.
+	pub fn with_source_options(&self, source: &SourceOptions) -> ResponseOptions {
+		let mut options = self.clone();
+		if let Some(origins) = &source.allowed_origins {
+			options.allowed_origins = origins.clone();
+		}
+		if let Some(max_age) = source.max_age {
+			options.max_age = max_age;
+		}
+		for (name, value) in source.extra_headers.iter() {
+			options.extra_headers.retain(|(n, _)| n != name);
+			options.extra_headers.push((name.clone(), value.clone()));
+		}
+		options
+	}
+
+	pub fn get_cache_control(&self) -> String {
+		format!("public, max-age={}, no-transform", self.max_age)
+	}
+
+	/// Returns true if the `Access-Control-Allow-Origin` header depends on the `Origin` of the request.
+	pub fn varies_by_origin(&self) -> bool {
+		!self.allowed_origins.iter().any(|o| o == "*")
+	}
+
+	/// Returns the value of the `Access-Control-Allow-Origin` header for a request,
+	/// or `None` if its origin is not allowed.
+	pub fn get_allowed_origin(&self, request_headers: &HeaderMap) -> Option<String> {
+		if !self.varies_by_origin() {
+			return Some(String::from("*"));
+		}
+
+		let origin = request_headers.get(ORIGIN)?.to_str().ok()?;
+		self
+			.allowed_origins
+			.iter()
+			.find(|o| o.eq_ignore_ascii_case(origin))
+			.map(|_| origin.to_owned())
+	}
+
+	/// Adds the CORS headers of a response.
+	pub fn add_cors_headers(&self, request_headers: &HeaderMap, headers: &mut HeaderMap) {
+		if self.varies_by_origin() {
+			headers.append(VARY, HeaderValue::from_static("origin"));
+		}
+
+		if let Some(origin) = self.get_allowed_origin(request_headers) {
+			if let Ok(origin) = HeaderValue::from_str(&origin) {
+				headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+				headers.insert(
+					ACCESS_CONTROL_EXPOSE_HEADERS,
+					HeaderValue::from_static("content-length, content-range, etag, last-modified"),
+				);
+			}
+		}
+	}
+
+	/// Adds the headers of a response to a CORS preflight request (`OPTIONS`).
+	pub fn add_preflight_headers(&self, request_headers: &HeaderMap, headers: &mut HeaderMap) {
+		self.add_cors_headers(request_headers, headers);
+		if !headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN) {
+			return;
+		}
+
+		headers.insert(
+			ACCESS_CONTROL_ALLOW_METHODS,
+			HeaderValue::from_static("GET, HEAD, OPTIONS"),
+		);
+		if let Some(requested) = request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS) {
+			headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, requested.clone());
+		}
+		headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(86400));
+	}
+
+	/// Returns "scheme://host" for the URLs in a response, or "" if the host is unknown.
+	pub fn get_base_url(&self, request_headers: &HeaderMap) -> String {
+		let header = |key: &str| {
+			request_headers
+				.get(key)
+				.and_then(|value| value.to_str().ok())
+				.map(|value| value.split(',').next().unwrap_or_default().trim())
+				.filter(|value| !value.is_empty())
+		};
+
+		let (host, scheme) = match &self.base_url {
+			BaseUrl::Fixed(url) => return url.trim_end_matches('/').to_owned(),
+			BaseUrl::FromHost => (header(HOST.as_str()), None),
+			BaseUrl::FromProxy => (
+				header("x-forwarded-host").or(header(HOST.as_str())),
+				header("x-forwarded-proto"),
+			),
+		};
+		match host {
+			Some(host) => format!("{}://{host}", scheme.unwrap_or("http")),
+			None => String::new(),
+		}
+	}
+
+	/// Adds the `Vary` header of a response that contains the base URL.
+	pub fn add_base_url_headers(&self, headers: &mut HeaderMap) {
+		let vary = match self.base_url {
+			BaseUrl::Fixed(_) => return,
+			BaseUrl::FromHost => "host",
+			BaseUrl::FromProxy => "host, x-forwarded-host, x-forwarded-proto",
+		};
+		headers.append(VARY, HeaderValue::from_static(vary));
+	}
+
+	pub fn add_extra_headers(&self, headers: &mut HeaderMap) {
+		for (name, value) in self.extra_headers.iter() {
+			headers.insert(name, value.clone());
+		}
+	}
+}
+
+/// Parses a fixed base URL like "https://tiles.example.org".
+pub fn parse_base_url(url: &str) -> Result<BaseUrl> {
+	let url = url.trim().trim_end_matches('/');
+	let host = url
+		.strip_prefix("https://")
+		.or(url.strip_prefix("http://"))
+		.with_context(|| format!("base url \"{url}\" must start with \"http://\" or \"https://\""))?;
+	ensure!(
+		!host.is_empty() && HeaderValue::from_str(url).is_ok(),
+		"invalid base url \"{url}\""
+	);
+	Ok(BaseUrl::Fixed(url.to_owned()))
+}
+
+/// Parses a header like "X-Served-By: versatiles".
+pub fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue)> {
+	let (name, value) = header
+		.split_once(':')
+		.with_context(|| format!("header \"{header}\" must have the form \"name: value\""))?;
+	parse_header_pair(name, value)
+}
+
+pub fn parse_header_pair(name: &str, value: &str) -> Result<(HeaderName, HeaderValue)> {
+	Ok((
+		HeaderName::from_bytes(name.trim().as_bytes())
+			.with_context(|| format!("invalid header name \"{name}\""))?,
+		HeaderValue::from_str(value.trim())
+			.with_context(|| format!("invalid value of header \"{name}\""))?,
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn request(origin: Option<&str>) -> HeaderMap {
+		let mut headers = HeaderMap::new();
+		if let Some(origin) = origin {
+			headers.insert(ORIGIN, origin.parse().unwrap());
+		}
+		headers
+	}
+
+	fn restricted() -> ResponseOptions {
+		ResponseOptions {
+			allowed_origins: vec![
+				String::from("https://example.org"),
+				String::from("https://maps.example.org"),
+			],
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn allowed_origin() {
+		let options = ResponseOptions::default();
+		assert!(!options.varies_by_origin());
+		assert_eq!(
+			options.get_allowed_origin(&request(None)).as_deref(),
+			Some("*")
+		);
+		assert_eq!(
+			options
+				.get_allowed_origin(&request(Some("https://example.org")))
+				.as_deref(),
+			Some("*")
+		);
+
+		let options = restricted();
+		assert!(options.varies_by_origin());
+		assert_eq!(options.get_allowed_origin(&request(None)), None);
+		assert_eq!(
+			options
+				.get_allowed_origin(&request(Some("https://maps.example.org")))
+				.as_deref(),
+			Some("https://maps.example.org")
+		);
+		assert_eq!(
+			options.get_allowed_origin(&request(Some("https://evil.example.com"))),
+			None
+		);
+	}
+
+	#[test]
+	fn cache_control() {
+		let options = ResponseOptions {
+			max_age: 60,
+			..Default::default()
+		};
+		assert_eq!(
+			options.get_cache_control(),
+			"public, max-age=60, no-transform"
+		);
+	}
+
+	#[test]
+	fn source_options() {
+		let global = ResponseOptions {
+			extra_headers: vec![
+				parse_header("X-Served-By: versatiles").unwrap(),
+				parse_header("X-Release: 1").unwrap(),
+			],
+			..Default::default()
+		};
+		let source = SourceOptions {
+			allowed_origins: Some(vec![String::from("https://example.org")]),
+			max_age: Some(31536000),
+			extra_headers: vec![parse_header("x-release: 2").unwrap()],
+		};
+
+		let options = global.with_source_options(&source);
+		assert_eq!(options.allowed_origins, ["https://example.org"]);
+		assert_eq!(options.max_age, 31536000);
+		assert_eq!(
+			options.extra_headers,
+			[
+				parse_header("X-Served-By: versatiles").unwrap(),
+				parse_header("X-Release: 2").unwrap()
+			]
+		);
+
+		assert_eq!(
+			global.with_source_options(&SourceOptions::default()),
+			global
+		);
+	}
+
+	#[test]
+	fn preflight() {
+		let mut request = request(Some("https://example.org"));
+		request.insert(ACCESS_CONTROL_REQUEST_HEADERS, "range".parse().unwrap());
+
+		let mut headers = HeaderMap::new();
+		restricted().add_preflight_headers(&request, &mut headers);
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://example.org");
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, HEAD, OPTIONS");
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "range");
+		assert_eq!(headers[VARY], "origin");
+
+		let mut request = request.clone();
+		request.insert(ORIGIN, "https://evil.example.com".parse().unwrap());
+		let mut headers = HeaderMap::new();
+		restricted().add_preflight_headers(&request, &mut headers);
+		assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+		assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_METHODS));
+	}
+
+	#[test]
+	fn base_url() {
+		let mut request = HeaderMap::new();
+		request.insert(HOST, "127.0.0.1:8080".parse().unwrap());
+		request.insert("x-forwarded-host", "tiles.example.org".parse().unwrap());
+		request.insert("x-forwarded-proto", "https, http".parse().unwrap());
+
+		let test = |base_url: BaseUrl, request: &HeaderMap, expected: &str| {
+			let options = ResponseOptions {
+				base_url,
+				..Default::default()
+			};
+			assert_eq!(options.get_base_url(request), expected);
+		};
+
+		// forwarded headers are ignored unless the server is behind a trusted proxy
+		test(BaseUrl::FromHost, &request, "http://127.0.0.1:8080");
+		test(
+			BaseUrl::Fixed(String::from("https://maps.example.org/")),
+			&request,
+			"https://maps.example.org",
+		);
+		test(BaseUrl::FromProxy, &request, "https://tiles.example.org");
+		test(BaseUrl::FromHost, &HeaderMap::new(), "");
+		test(BaseUrl::FromProxy, &HeaderMap::new(), "");
+		request.remove("x-forwarded-host");
+		request.remove("x-forwarded-proto");
+		test(BaseUrl::FromProxy, &request, "http://127.0.0.1:8080");
+
+		let vary = |base_url: BaseUrl| {
+			let mut headers = HeaderMap::new();
+			ResponseOptions {
+				base_url,
+				..Default::default()
+			}
+			.add_base_url_headers(&mut headers);
+			headers.get(VARY).map(|v| v.to_str().unwrap().to_owned())
+		};
+		assert_eq!(vary(BaseUrl::FromHost).as_deref(), Some("host"));
+		assert_eq!(
+			vary(BaseUrl::Fixed(String::from("https://example.org"))),
+			None
+		);
+		assert_eq!(
+			vary(BaseUrl::FromProxy).as_deref(),
+			Some("host, x-forwarded-host, x-forwarded-proto")
+		);
+	}
+
+	#[test]
+	fn base_urls() {
+		assert_eq!(
+			parse_base_url("https://tiles.example.org/").unwrap(),
+			BaseUrl::Fixed(String::from("https://tiles.example.org"))
+		);
+		assert_eq!(
+			parse_base_url("http://127.0.0.1:8080/maps").unwrap(),
+			BaseUrl::Fixed(String::from("http://127.0.0.1:8080/maps"))
+		);
+		assert!(parse_base_url("tiles.example.org").is_err());
+		assert!(parse_base_url("https://").is_err());
+	}
+
+	#[test]
+	fn headers() {
+		assert!(parse_header("X-Served-By").is_err());
+		assert!(parse_header("X Served: by").is_err());
+		assert!(parse_header("X-Served-By: a\nb").is_err());
+
+		let mut headers = HeaderMap::new();
+		headers.insert("x-served-by", "old".parse().unwrap());
+		let options = ResponseOptions {
+			extra_headers: vec![parse_header("X-Served-By:  versatiles ").unwrap()],
+			..Default::default()
+		};
+		options.add_extra_headers(&mut headers);
+		assert_eq!(headers["x-served-by"], "versatiles");
+	}
+}
@@ -0,0 +1,122 @@
+//! triggers for reloading the sources of a running server
+//!
+//! A reload can be requested with `SIGHUP`, by changing the config file or with
+//! `POST /api/admin/reload` and an admin token. Every trigger sends a `ReloadRequest`
+//! to the serve loop, which opens the sources again and swaps them in the running server.
+
+use anyhow::Result;
+use std::{
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
+use tokio::{
+	sync::{mpsc::Sender, oneshot},
+	task::JoinHandle,
+};
+
+pub struct ReloadRequest {
+	/// e.g. "SIGHUP", only used for logging
+	pub trigger: String,
+	/// receives the result of the reload, if someone waits for it
+	pub done: Option<oneshot::Sender<Result<()>>>,
+}
+
+impl ReloadRequest {
+	pub fn new(trigger: &str) -> ReloadRequest {
+		ReloadRequest {
+			trigger: trigger.to_owned(),
+			done: None,
+		}
+	}
+
+	pub fn finish(self, result: Result<()>) {
+		if let Err(error) = &result {
+			log::error!("failed to reload sources: {error:#}");
+		}
+		if let Some(done) = self.done {
+			done.send(result).ok();
+		}
+	}
+}
+
+/// Sends a reload request whenever the process receives `SIGHUP`.
+#[cfg(unix)]
+pub fn reload_on_signal(sender: Sender<ReloadRequest>) -> Result<JoinHandle<()>> {
+	use tokio::signal::unix::{signal, SignalKind};
+	let mut signals = signal(SignalKind::hangup())?;
+	Ok(tokio::spawn(async move {
+		while signals.recv().await.is_some() {
+			if sender.send(ReloadRequest::new("SIGHUP")).await.is_err() {
+				break;
+			}
+		}
+	}))
+}
+
+/// Sends a reload request whenever the modification time of the file changes.
+pub fn reload_on_change(
+	path: &Path,
+	interval: Duration,
+	sender: Sender<ReloadRequest>,
+) -> JoinHandle<()> {
+	let path: PathBuf = path.to_path_buf();
+	let get_modified = move || -> Option<SystemTime> { path.metadata().ok()?.modified().ok() };
+
+	tokio::spawn(async move {
+		let mut last_modified = get_modified();
+		loop {
+			tokio::time::sleep(interval).await;
+			let modified = get_modified();
+			// a file that is being replaced can be missing for a moment
+			if modified.is_some() && modified != last_modified {
+				last_modified = modified;
+				if sender
+					.send(ReloadRequest::new("config file change"))
+					.await
+					.is_err()
+				{
+					break;
+				}
+			}
+		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::sync::mpsc;
+
+	#[tokio::test]
+	async fn file_change() -> Result<()> {
+		let temp_dir = assert_fs::TempDir::new()?;
+		let path = temp_dir.path().join("config.json");
+		std::fs::write(&path, "{}")?;
+
+		let (sender, mut receiver) = mpsc::channel(4);
+		let task = reload_on_change(&path, Duration::from_millis(10), sender);
+
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		assert!(receiver.try_recv().is_err());
+
+		let file = std::fs::File::options().write(true).open(&path)?;
+		file.set_modified(SystemTime::now() + Duration::from_secs(10))?;
+
+		let request = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await?;
+		assert_eq!(request.unwrap().trigger, "config file change");
+
+		task.abort();
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn finish() {
+		let (done, result) = oneshot::channel();
+		let request = ReloadRequest {
+			trigger: String::from("test"),
+			done: Some(done),
+		};
+		request.finish(Err(anyhow::anyhow!("broken")));
+		assert_eq!(result.await.unwrap().unwrap_err().to_string(), "broken");
+	}
+}
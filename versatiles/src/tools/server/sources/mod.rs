@@ -1,14 +1,19 @@
-//! implementation of different sources (tile containers, folders, tar files)
+//! implementation of different sources (tile containers, folders, tar files, single files)
 
 mod response;
-pub use response::SourceResponse;
+pub use response::{ContentRange, FilePart, SourceResponse};
 
 mod static_source;
 pub use static_source::StaticSource;
 
+mod static_source_file;
+
 mod static_source_folder;
 
 mod static_source_tar;
 
 mod tile_source;
-pub use tile_source::TileSource;
+pub use tile_source::{get_mime, TileSource};
+
+mod url_scheme;
+pub use url_scheme::UrlScheme;
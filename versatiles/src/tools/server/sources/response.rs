@@ -1,9 +1,35 @@
-use crate::types::{Blob, TileCompression};
+use super::super::utils::{get_etag, RangeRequest};
+use crate::types::{Blob, ByteRange, TileCompression};
+use std::{path::PathBuf, time::SystemTime};
 
+#[derive(Clone)]
 pub struct SourceResponse {
 	pub blob: Blob,
 	pub compression: TileCompression,
 	pub mime: String,
+	pub last_modified: Option<SystemTime>,
+	/// ETag of the stored version, used as long as the server does not recompress the blob
+	pub etag: Option<String>,
+	/// set if the blob is only a part of the resource, as the answer to a range request
+	pub content_range: Option<ContentRange>,
+	/// set if the content is streamed from a file instead of being held in the blob
+	pub file: Option<FilePart>,
+}
+
+/// A part of a file on disk, which is sent as it is stored, without loading it into memory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilePart {
+	pub path: PathBuf,
+	pub range: ByteRange,
+}
+
+/// The part of a resource that is sent in response to a range request.
+#[derive(Clone)]
+pub struct ContentRange {
+	/// `None` if the requested range is not satisfiable
+	pub range: Option<ByteRange>,
+	/// size of the whole resource
+	pub size: u64,
 }
 
 impl SourceResponse {
@@ -16,6 +42,115 @@ impl SourceResponse {
 			blob,
 			compression: compression.to_owned(),
 			mime: mime.to_owned(),
+			last_modified: None,
+			etag: None,
+			content_range: None,
+			file: None,
 		})
 	}
+
+	pub fn with_last_modified(mut self, last_modified: Option<SystemTime>) -> SourceResponse {
+		self.last_modified = last_modified;
+		self
+	}
+
+	pub fn with_etag(mut self, etag: String) -> SourceResponse {
+		self.etag = Some(etag);
+		self
+	}
+
+	/// Returns the ETag of the resource when it is sent with `compression`.
+	///
+	/// It is derived from the stored version, so a conditional request can be answered without recompressing the blob.
+	/// Every content encoding gets its own strong ETag.
+	pub fn get_etag_for(&self, compression: &TileCompression) -> String {
+		let etag = self.etag.clone().unwrap_or_else(|| get_etag(&self.blob));
+		if *compression == self.compression {
+			return etag;
+		}
+		let suffix = match compression {
+			TileCompression::Uncompressed => "identity",
+			TileCompression::Gzip => "gzip",
+			TileCompression::Brotli => "br",
+		};
+		match etag.strip_suffix('"') {
+			Some(tag) => format!("{tag}-{suffix}\""),
+			None => format!("{etag}-{suffix}"),
+		}
+	}
+
+	/// Cuts the requested range out of the blob. The ETag is calculated before, so it identifies the whole resource.
+	pub fn into_range(mut self, range: &RangeRequest) -> SourceResponse {
+		if self.etag.is_none() {
+			self.etag = Some(get_etag(&self.blob));
+		}
+
+		let size = self.blob.len();
+		let range = range.resolve(size);
+		self.blob = match &range {
+			Some(range) => self
+				.blob
+				.read_range(range)
+				.expect("range should be resolved"),
+			None => Blob::new_empty(),
+		};
+		self.content_range = Some(ContentRange { range, size });
+		self
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn get_etag_for() {
+		let response = SourceResponse::new_some(
+			Blob::from("tile"),
+			&TileCompression::Gzip,
+			"application/x-protobuf",
+		)
+		.unwrap();
+		let etag = get_etag(&Blob::from("tile"));
+
+		assert_eq!(response.get_etag_for(&TileCompression::Gzip), etag);
+		assert_eq!(
+			response.get_etag_for(&TileCompression::Brotli),
+			format!("{}-br\"", &etag[..etag.len() - 1])
+		);
+
+		let response = response.with_etag(String::from("\"stored\""));
+		assert_eq!(
+			response.get_etag_for(&TileCompression::Uncompressed),
+			"\"stored-identity\""
+		);
+	}
+
+	#[test]
+	fn into_range() {
+		let response = || {
+			SourceResponse::new_some(
+				Blob::from("0123456789"),
+				&TileCompression::Uncompressed,
+				"text/plain",
+			)
+			.unwrap()
+		};
+		let etag = get_etag(&Blob::from("0123456789"));
+
+		let result = response().into_range(&RangeRequest::Bounded(2, 4));
+		assert_eq!(result.blob.as_str(), "234");
+		assert_eq!(result.etag, Some(etag.clone()));
+		let content_range = result.content_range.unwrap();
+		assert_eq!(content_range.range, Some(ByteRange::new(2, 3)));
+		assert_eq!(content_range.size, 10);
+
+		let result = response().into_range(&RangeRequest::Suffix(3));
+		assert_eq!(result.blob.as_str(), "789");
+
+		let result = response().into_range(&RangeRequest::From(10));
+		assert!(result.blob.is_empty());
+		assert_eq!(result.etag, Some(etag));
+		assert_eq!(result.content_range.unwrap().range, None);
+	}
 }
@@ -1,7 +1,14 @@
 use super::{
-	super::utils::Url, static_source_folder::Folder, static_source_tar::TarFile, SourceResponse,
+	super::{
+		options::SourceOptions,
+		utils::{RangeRequest, Url},
+	},
+	static_source_file::StaticFile,
+	static_source_folder::Folder,
+	static_source_tar::TarFile,
+	SourceResponse,
 };
-use crate::utils::TargetCompression;
+use crate::{types::TileCompression, utils::TargetCompression};
 use anyhow::{ensure, Result};
 use async_trait::async_trait;
 use std::{fmt::Debug, path::Path, sync::Arc};
@@ -13,12 +20,20 @@ pub trait StaticSourceTrait: Send + Sync + Debug {
 	#[cfg(test)]
 	fn get_name(&self) -> &str;
 	fn get_data(&self, url: &Url, accept: &TargetCompression) -> Option<SourceResponse>;
+
+	// Returns a part of a file. By default the stored version is loaded completely and sliced.
+	// Ranges of compressed files are not served, so the client gets the whole file instead.
+	fn get_range(&self, url: &Url, range: &RangeRequest) -> Option<SourceResponse> {
+		let response = self.get_data(url, &TargetCompression::from_none())?;
+		(response.compression == TileCompression::Uncompressed).then(|| response.into_range(range))
+	}
 }
 
 #[derive(Clone)]
 pub struct StaticSource {
 	source: Arc<Box<dyn StaticSourceTrait>>,
 	prefix: Url,
+	pub options: SourceOptions,
 }
 
 impl StaticSource {
@@ -32,29 +47,54 @@ impl StaticSource {
 				Box::new(TarFile::from(path)?)
 			}),
 			prefix,
+			options: SourceOptions::default(),
+		})
+	}
+	// Serves a single file at the given url, e.g. a container file that clients read with range requests
+	pub fn new_file(path: &Path, url: Url) -> Result<StaticSource> {
+		let mut parts = url.as_vec();
+		let name = parts.pop();
+		ensure!(
+			name.is_some() && !url.is_dir(),
+			"url {url} must end in a file name"
+		);
+
+		Ok(StaticSource {
+			source: Arc::new(Box::new(StaticFile::from(path, &name.unwrap())?)),
+			prefix: Url::new(&parts.join("/")).as_dir(),
+			options: SourceOptions::default(),
 		})
 	}
+
 	#[cfg(test)]
 	pub fn get_type(&self) -> &str {
 		self.source.get_type()
 	}
+	pub fn matches_prefix(&self, url: &Url) -> bool {
+		url.starts_with(&self.prefix)
+	}
 	pub fn get_data(&self, url: &Url, accept: &TargetCompression) -> Option<SourceResponse> {
-		if !url.starts_with(&self.prefix) {
+		if !self.matches_prefix(url) {
 			return None;
 		}
 		self
 			.source
 			.get_data(&url.strip_prefix(&self.prefix).unwrap(), accept)
 	}
+	pub fn get_range(&self, url: &Url, range: &RangeRequest) -> Option<SourceResponse> {
+		if !self.matches_prefix(url) {
+			return None;
+		}
+		self
+			.source
+			.get_range(&url.strip_prefix(&self.prefix).unwrap(), range)
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::{
-		types::{Blob, TileCompression},
-		utils::compress,
-	};
+	use crate::{types::Blob, utils::compress};
 	use async_trait::async_trait;
 	use std::{fs::File, io::Write, path::PathBuf};
 
@@ -150,6 +190,7 @@ mod tests {
 		let static_source = StaticSource {
 			source: Arc::new(Box::new(MockStaticSource)),
 			prefix: Url::new(""),
+			options: SourceOptions::default(),
 		};
 		let result = static_source.get_data(&Url::new("exists"), &TargetCompression::from_none());
 		assert!(result.is_some());
@@ -160,6 +201,7 @@ mod tests {
 		let static_source = StaticSource {
 			source: Arc::new(Box::new(MockStaticSource)),
 			prefix: Url::new(""),
+			options: SourceOptions::default(),
 		};
 		let result =
 			static_source.get_data(&Url::new("does_not_exist"), &TargetCompression::from_none());
@@ -171,6 +213,7 @@ mod tests {
 		let static_source = StaticSource {
 			source: Arc::new(Box::new(MockStaticSource)),
 			prefix: Url::new("path/to"),
+			options: SourceOptions::default(),
 		};
 		// Should match and retrieve data
 		let result =
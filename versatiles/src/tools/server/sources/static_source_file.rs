@@ -0,0 +1,119 @@
+use super::{
+	static_source::StaticSourceTrait,
+	static_source_folder::{read_file_range, stream_file},
+	SourceResponse,
+};
+use crate::{
+	tools::server::{utils::RangeRequest, Url},
+	utils::TargetCompression,
+};
+use anyhow::{ensure, Result};
+use async_trait::async_trait;
+use std::{
+	env::current_dir,
+	fmt::Debug,
+	path::{Path, PathBuf},
+};
+
+// A single file, e.g. a tile container that is served as it is, so that clients can read it with range requests.
+// It is streamed from disk and never recompressed.
+pub struct StaticFile {
+	path: PathBuf,
+	name: String,
+}
+
+impl StaticFile {
+	pub fn from(path: &Path, name: &str) -> Result<StaticFile> {
+		let path = current_dir()?.join(path).canonicalize()?;
+		ensure!(path.is_file(), "path {path:?} must be a file");
+
+		Ok(StaticFile {
+			path,
+			name: name.to_owned(),
+		})
+	}
+
+	fn matches(&self, url: &Url) -> bool {
+		url.str[1..] == self.name
+	}
+}
+
+#[async_trait]
+impl StaticSourceTrait for StaticFile {
+	#[cfg(test)]
+	fn get_type(&self) -> &str {
+		"file"
+	}
+
+	#[cfg(test)]
+	fn get_name(&self) -> &str {
+		&self.name
+	}
+
+	fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Option<SourceResponse> {
+		if !self.matches(url) {
+			return None;
+		}
+		stream_file(&self.path)
+	}
+
+	fn get_range(&self, url: &Url, range: &RangeRequest) -> Option<SourceResponse> {
+		if !self.matches(url) {
+			return None;
+		}
+		read_file_range(&self.path, range)
+	}
+}
+
+impl Debug for StaticFile {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("StaticFile")
+			.field("path", &self.path)
+			.field("name", &self.name)
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{tools::server::sources::FilePart, types::ByteRange};
+
+	#[test]
+	fn test() {
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		let path = temp_dir.path().join("tiles.pmtiles");
+		std::fs::write(&path, b"PMTiles content").unwrap();
+
+		assert!(StaticFile::from(temp_dir.path(), "tiles.pmtiles").is_err());
+
+		let file = StaticFile::from(&path, "berlin.pmtiles").unwrap();
+		assert_eq!(file.get_name(), "berlin.pmtiles");
+		assert_eq!(file.get_type(), "file");
+
+		let accept = TargetCompression::from_none();
+		assert!(file.get_data(&Url::new("tiles.pmtiles"), &accept).is_none());
+		assert!(file
+			.get_data(&Url::new("berlin.pmtiles/x"), &accept)
+			.is_none());
+
+		let response = file.get_data(&Url::new("berlin.pmtiles"), &accept).unwrap();
+		assert!(response.blob.is_empty());
+		assert_eq!(
+			response.file,
+			Some(FilePart {
+				path: path.canonicalize().unwrap(),
+				range: ByteRange::new(0, 15)
+			})
+		);
+		assert_eq!(response.mime, "application/octet-stream");
+
+		let response = file
+			.get_range(&Url::new("berlin.pmtiles"), &RangeRequest::Suffix(7))
+			.unwrap();
+		assert_eq!(response.file.unwrap().range, ByteRange::new(8, 7));
+		assert!(file
+			.get_range(&Url::new("tiles.pmtiles"), &RangeRequest::Suffix(7))
+			.is_none());
+	}
+}
@@ -1,5 +1,5 @@
 use crate::{
-	types::{Blob, TileCompression},
+	types::{Blob, ByteRange, TileCompression},
 	utils::TargetCompression,
 };
 use anyhow::{ensure, Result};
@@ -7,14 +7,22 @@ use async_trait::async_trait;
 use std::{
 	env::current_dir,
 	fmt::Debug,
-	fs::File,
+	fs::{File, Metadata},
 	io::{BufReader, Read},
 	path::{Path, PathBuf},
+	time::SystemTime,
 };
 
-use crate::tools::server::{utils::guess_mime, Url};
+use crate::tools::server::{
+	utils::{guess_mime, RangeRequest},
+	Url,
+};
 
-use super::{static_source::StaticSourceTrait, SourceResponse};
+use super::{
+	response::{ContentRange, FilePart},
+	static_source::StaticSourceTrait,
+	SourceResponse,
+};
 
 // Folder struct definition
 #[derive(Clone)]
@@ -61,6 +69,17 @@ impl StaticSourceTrait for Folder {
 	// Gets the data at the given path and responds with a compressed or uncompressed version
 	// based on the accept header
 	fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Option<SourceResponse> {
+		read_file(&self.get_local_path(url)?)
+	}
+
+	// Reads only the requested range of the file
+	fn get_range(&self, url: &Url, range: &RangeRequest) -> Option<SourceResponse> {
+		read_file_range(&self.get_local_path(url)?, range)
+	}
+}
+
+impl Folder {
+	fn get_local_path(&self, url: &Url) -> Option<PathBuf> {
 		let mut local_path = url.as_path(&self.folder);
 
 		// If the path is a directory, append 'index.html'
@@ -73,15 +92,84 @@ impl StaticSourceTrait for Folder {
 			return None;
 		}
 
-		let f = File::open(&local_path).unwrap();
-		let mut buffer = Vec::new();
-		BufReader::new(f).read_to_end(&mut buffer).unwrap();
-		let blob = Blob::from(buffer);
+		Some(local_path)
+	}
+}
+
+/// Reads a whole file.
+pub(super) fn read_file(path: &Path) -> Option<SourceResponse> {
+	let f = File::open(path).ok()?;
+	let metadata = f.metadata().ok()?;
+	let mut buffer = Vec::new();
+	BufReader::new(f).read_to_end(&mut buffer).ok()?;
+	let blob = Blob::from(buffer);
+
+	SourceResponse::new_some(blob, &TileCompression::Uncompressed, &guess_mime(path))
+		.map(|response| with_file_info(response, &metadata))
+}
+
+/// The longest part of a file that is sent in response to one range request, longer ranges are shortened.
+/// Clients like PMTiles only request small parts, others can continue with further ranges.
+const MAX_RANGE_LENGTH: u64 = 16 * 1024 * 1024;
 
-		let mime = guess_mime(&local_path);
+/// Streams the whole file, without loading it into memory.
+pub(super) fn stream_file(path: &Path) -> Option<SourceResponse> {
+	let metadata = path.metadata().ok()?;
+	let file = FilePart {
+		path: path.to_path_buf(),
+		range: ByteRange::new(0, metadata.len()),
+	};
 
-		SourceResponse::new_some(blob, &TileCompression::Uncompressed, &mime)
+	SourceResponse::new_some(
+		Blob::new_empty(),
+		&TileCompression::Uncompressed,
+		&guess_mime(path),
+	)
+	.map(|mut response| {
+		response.file = Some(file);
+		with_file_info(response, &metadata)
+	})
+}
+
+/// Streams only the requested range of a file, so large files can be served efficiently.
+pub(super) fn read_file_range(path: &Path, range: &RangeRequest) -> Option<SourceResponse> {
+	let metadata = path.metadata().ok()?;
+	if !metadata.is_file() {
+		return None;
 	}
+	let size = metadata.len();
+
+	let range = range
+		.resolve(size)
+		.map(|range| ByteRange::new(range.offset, range.length.min(MAX_RANGE_LENGTH)));
+	let file = range.map(|range| FilePart {
+		path: path.to_path_buf(),
+		range,
+	});
+
+	SourceResponse::new_some(
+		Blob::new_empty(),
+		&TileCompression::Uncompressed,
+		&guess_mime(path),
+	)
+	.map(|mut response| {
+		response.content_range = Some(ContentRange { range, size });
+		response.file = file;
+		with_file_info(response, &metadata)
+	})
+}
+
+// The ETag is derived from size and modification time, so that whole files and ranges share the same ETag
+// without hashing the complete file.
+fn with_file_info(response: SourceResponse, metadata: &Metadata) -> SourceResponse {
+	let last_modified = metadata.modified().ok();
+	let nanos = last_modified
+		.and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+		.map_or(0, |duration| duration.as_nanos());
+
+	response
+		.with_last_modified(last_modified)
+		.with_etag(format!("\"{:x}-{:x}\"", metadata.len(), nanos))
 }
 
 impl Debug for Folder {
@@ -145,5 +233,52 @@ mod tests {
 
 		let result = response.blob.as_str();
 		assert_eq!(result, "Hello, world!");
+		assert!(response.last_modified.is_some());
+		let etag = response.etag.unwrap();
+
+		// Ranges are streamed from the file and share the ETag of the whole file
+		let response = folder
+			.get_range(&Url::new("testdir"), &RangeRequest::Bounded(7, 11))
+			.unwrap();
+		assert!(response.blob.is_empty());
+		assert_eq!(
+			response.file,
+			Some(FilePart {
+				path: dir_path.join("index.html").canonicalize().unwrap(),
+				range: ByteRange::new(7, 5)
+			})
+		);
+		assert_eq!(response.etag.unwrap(), etag);
+		let content_range = response.content_range.unwrap();
+		assert_eq!(content_range.range, Some(ByteRange::new(7, 5)));
+		assert_eq!(content_range.size, 13);
+
+		let response = folder
+			.get_range(&Url::new("testdir"), &RangeRequest::From(13))
+			.unwrap();
+		assert!(response.file.is_none());
+		assert!(response.content_range.unwrap().range.is_none());
+	}
+
+	#[test]
+	fn long_ranges_are_shortened() {
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		let path = temp_dir.path().join("planet.versatiles");
+		File::create(&path)
+			.unwrap()
+			.set_len(MAX_RANGE_LENGTH * 2)
+			.unwrap();
+
+		let response = read_file_range(&path, &RangeRequest::From(10)).unwrap();
+		let content_range = response.content_range.unwrap();
+		assert_eq!(
+			content_range.range,
+			Some(ByteRange::new(10, MAX_RANGE_LENGTH))
+		);
+		assert_eq!(content_range.size, MAX_RANGE_LENGTH * 2);
+		assert_eq!(
+			response.file.unwrap().range,
+			ByteRange::new(10, MAX_RANGE_LENGTH)
+		);
 	}
 }
@@ -8,7 +8,14 @@ use anyhow::{bail, ensure, Result};
 use async_trait::async_trait;
 use log::trace;
 use std::{
-	collections::HashMap, env::current_dir, ffi::OsStr, fmt::Debug, fs::File, io::Read, path::Path,
+	collections::HashMap,
+	env::current_dir,
+	ffi::OsStr,
+	fmt::Debug,
+	fs::File,
+	io::Read,
+	path::Path,
+	time::{Duration, SystemTime},
 };
 use tar::{Archive, EntryType};
 
@@ -18,6 +25,7 @@ struct FileEntry {
 	un: Option<Blob>,
 	gz: Option<Blob>,
 	br: Option<Blob>,
+	last_modified: Option<SystemTime>,
 }
 
 impl FileEntry {
@@ -27,6 +35,7 @@ impl FileEntry {
 			un: None,
 			gz: None,
 			br: None,
+			last_modified: None,
 		}
 	}
 }
@@ -89,6 +98,12 @@ impl TarFile {
 				entry_path.set_extension("");
 			}
 
+			let last_modified = file
+				.header()
+				.mtime()
+				.ok()
+				.map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime));
+
 			let mut buffer = Vec::new();
 			file.read_to_end(&mut buffer)?;
 			let blob = Blob::from(buffer);
@@ -111,6 +126,7 @@ impl TarFile {
 
 				let entry = lookup.entry(name);
 				let versions = entry.or_insert_with(|| FileEntry::new(mime.to_string()));
+				versions.last_modified = versions.last_modified.max(last_modified);
 				match compression {
 					Uncompressed => versions.un = Some(blob),
 					Gzip => versions.gz = Some(blob),
@@ -147,29 +163,33 @@ impl StaticSourceTrait for TarFile {
 		use TileCompression::*;
 
 		let file_entry = self.lookup.get(&url.str[1..])?.to_owned();
+		let respond = |blob: &Blob, compression: &TileCompression| {
+			SourceResponse::new_some(blob.to_owned(), compression, &file_entry.mime)
+				.map(|response| response.with_last_modified(file_entry.last_modified))
+		};
 
 		if accept.contains(Brotli) {
 			if let Some(blob) = &file_entry.br {
-				return SourceResponse::new_some(blob.to_owned(), &Brotli, &file_entry.mime);
+				return respond(blob, &Brotli);
 			}
 		}
 
 		if accept.contains(Gzip) {
 			if let Some(blob) = &file_entry.gz {
-				return SourceResponse::new_some(blob.to_owned(), &Gzip, &file_entry.mime);
+				return respond(blob, &Gzip);
 			}
 		}
 
 		if let Some(blob) = &file_entry.un {
-			return SourceResponse::new_some(blob.to_owned(), &Uncompressed, &file_entry.mime);
+			return respond(blob, &Uncompressed);
 		}
 
 		if let Some(blob) = &file_entry.br {
-			return SourceResponse::new_some(blob.to_owned(), &Brotli, &file_entry.mime);
+			return respond(blob, &Brotli);
 		}
 
 		if let Some(blob) = &file_entry.gz {
-			return SourceResponse::new_some(blob.to_owned(), &Gzip, &file_entry.mime);
+			return respond(blob, &Gzip);
 		}
 
 		None
@@ -189,7 +209,9 @@ mod tests {
 		container::{
 			convert_tiles_container, MockTilesReader, MockTilesReaderProfile, TilesConverterParameters,
 		},
+		tools::server::utils::RangeRequest,
 		types::TilesReaderTrait,
+		utils::compress_brotli,
 	};
 	use assert_fs::NamedTempFile;
 
@@ -275,4 +297,32 @@ mod tests {
 			}
 		}
 	}
+
+	#[test]
+	fn get_range() -> Result<()> {
+		let mut builder = tar::Builder::new(Vec::new());
+		let mut append = |name: &str, blob: Blob| {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(blob.len());
+			header.set_mode(0o644);
+			header.set_cksum();
+			builder.append_data(&mut header, name, blob.as_slice())
+		};
+		append("x.json.br", compress_brotli(&Blob::from("{\"x\":1}"))?)?;
+		append("y.json", Blob::from("{\"y\":2}"))?;
+		let file = NamedTempFile::new("temp.tar")?;
+		std::fs::write(&file, builder.into_inner()?)?;
+
+		let tar_file = TarFile::from(&file)?;
+		let range = RangeRequest::Bounded(0, 3);
+
+		// only stored compressed, so the whole file is served instead of a part of the brotli stream
+		assert!(tar_file.get_range(&Url::new("x.json"), &range).is_none());
+
+		let response = tar_file.get_range(&Url::new("y.json"), &range).unwrap();
+		assert_eq!(response.blob.as_str(), "{\"y\"");
+		assert_eq!(response.compression, TileCompression::Uncompressed);
+
+		Ok(())
+	}
 }
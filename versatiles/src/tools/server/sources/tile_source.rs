@@ -1,117 +1,120 @@
-use super::{super::utils::Url, SourceResponse};
+use super::{
+	super::{options::SourceOptions, utils::Url},
+	SourceResponse, UrlScheme,
+};
 use crate::{
-	types::{TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
-	utils::TargetCompression,
+	geometry::{vector_tile::VectorTile, GeoCollection},
+	image::helper::transcode,
+	types::{
+		Blob, TileCompression, TileCoord3, TileFormat, TilesReaderParameters, TilesReaderTrait,
+	},
+	utils::{decompress, parse_json, JsonValue, TargetCompression},
+};
+use anyhow::{bail, Result};
+use std::{
+	collections::BTreeMap,
+	fmt::Debug,
+	path::Path,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::SystemTime,
 };
-use anyhow::Result;
-use std::{fmt::Debug, sync::Arc};
-use tokio::sync::Mutex;
+
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
 
 // TileSource struct definition
 #[derive(Clone)]
 pub struct TileSource {
 	pub prefix: Url,
+	// unique for every opened source, so a reloaded source never gets the cached tiles of the old one
+	pub generation: u64,
 	pub json_info: String,
-	reader: Arc<Mutex<Box<dyn TilesReaderTrait>>>,
+	// readers take `&self`, so concurrent requests are served in parallel without locking
+	reader: Arc<Box<dyn TilesReaderTrait>>,
 	pub tile_mime: String,
+	pub tile_format: TileFormat,
 	pub compression: TileCompression,
+	// modification time of the container file, if the reader is backed by a local file
+	last_modified: Option<SystemTime>,
+	pub options: SourceOptions,
+	// how tile coordinates are written in the url
+	pub scheme: UrlScheme,
 }
 
 impl TileSource {
 	// Constructor function for creating a TileSource instance
 	pub fn from(reader: Box<dyn TilesReaderTrait>, prefix: Url) -> Result<TileSource> {
-		use TileFormat::*;
-
 		let parameters = reader.get_parameters();
 		let compression = parameters.tile_compression;
-
-		// Determine the MIME type based on the tile format
-		let tile_mime = match parameters.tile_format {
-			// Various tile formats with their corresponding MIME types
-			BIN => "application/octet-stream",
-			PNG => "image/png",
-			JPG => "image/jpeg",
-			WEBP => "image/webp",
-			AVIF => "image/avif",
-			SVG => "image/svg+xml",
-			PBF => "application/x-protobuf",
-			GEOJSON => "application/geo+json",
-			TOPOJSON => "application/topo+json",
-			JSON => "application/json",
-		}
-		.to_string();
+		let tile_format = parameters.tile_format;
+		let tile_mime = get_mime(tile_format).to_string();
 
 		let bbox_pyramid = &parameters.bbox_pyramid;
-		let tile_format = format!("{:?}", parameters.tile_format).to_lowercase();
-		let tile_compression = format!("{:?}", parameters.tile_compression).to_lowercase();
+		let format_name = format!("{:?}", parameters.tile_format).to_lowercase();
+		let compression_name = format!("{:?}", parameters.tile_compression).to_lowercase();
 		let json_info = format!(
 			"{{\"type\":\"{}\",\"format\":\"{}\",\"compression\":\"{}\",\"zoom_min\":{},\"zoom_max\":{},\"bbox\":[{}]}}",
 			reader.get_container_name(),
-			tile_format,
-			tile_compression,
+			format_name,
+			compression_name,
 			bbox_pyramid.get_zoom_min().unwrap(),
 			bbox_pyramid.get_zoom_max().unwrap(),
 			bbox_pyramid.get_geo_bbox().map(|f| f.to_string()).join(","),
 		);
 
+		let last_modified = Path::new(reader.get_name())
+			.metadata()
+			.and_then(|m| m.modified())
+			.ok();
+
 		Ok(TileSource {
 			prefix,
+			generation: NEXT_GENERATION.fetch_add(1, Ordering::Relaxed),
 			json_info,
-			reader: Arc::new(Mutex::new(reader)),
+			reader: Arc::new(reader),
 			tile_mime,
+			tile_format,
 			compression,
+			last_modified,
+			options: SourceOptions::default(),
+			scheme: UrlScheme::default(),
 		})
 	}
 
-	pub async fn get_id(&self) -> String {
-		let reader = self.reader.lock().await;
-		return reader.get_name().to_owned();
+	pub fn get_id(&self) -> String {
+		self.reader.get_name().to_owned()
 	}
 
-	// Retrieve the tile data as an HTTP response
-	pub async fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Option<SourceResponse> {
-		let parts: Vec<String> = url.as_vec();
-
-		if parts.len() >= 3 {
-			// Parse the tile coordinates
-			let z = parts[0].parse::<u8>();
-			let x = parts[1].parse::<u32>();
-			let y: String = parts[2].chars().take_while(|c| c.is_numeric()).collect();
-			let y = y.parse::<u32>();
-
-			// Check for parsing errors
-			if x.is_err() || y.is_err() || z.is_err() {
-				return None;
-			}
-
-			// Create a TileCoord3 instance
-			let coord = TileCoord3::new(x.unwrap(), y.unwrap(), z.unwrap()).unwrap();
-
-			log::debug!("get tile {} - {:?}", self.prefix, coord);
-
-			// Get tile data
-			let reader = self.reader.lock().await;
-			let tile = reader.get_tile_data(&coord).await;
-			drop(reader);
-
-			// If tile data is not found, return a not found response
-			if tile.is_err() {
-				return None;
-			}
+	pub fn get_parameters(&self) -> &TilesReaderParameters {
+		self.reader.get_parameters()
+	}
 
-			let tile = tile.unwrap();
+	// All formats a tile can be requested in, starting with the format of the container
+	pub fn get_formats(&self) -> Vec<TileFormat> {
+		let mut formats = vec![self.tile_format];
+		match self.tile_format {
+			TileFormat::PBF => formats.push(TileFormat::GEOJSON),
+			format if is_raster(format) => formats.extend(
+				[TileFormat::PNG, TileFormat::JPG, TileFormat::WEBP]
+					.into_iter()
+					.filter(|f| *f != format),
+			),
+			_ => {}
+		}
+		formats
+	}
 
-			// If tile data is not found, return a not found response
-			return if let Some(tile) = tile {
-				SourceResponse::new_some(tile, &self.compression, &self.tile_mime)
-			} else {
-				None
-			};
-		} else if (parts[0] == "meta.json") || (parts[0] == "tiles.json") {
+	// Retrieve the tile data as an HTTP response
+	pub async fn get_data(&self, url: &Url, _accept: &TargetCompression) -> Option<SourceResponse> {
+		if let Some(coord) = self.get_coord(url) {
+			return self
+				.get_tile_as(&coord, self.get_response_format(url, None))
+				.await;
+		} else if url.str == "/meta.json" {
 			// Get metadata
-			let reader = self.reader.lock().await;
-			let meta_option = reader.get_meta().unwrap();
-			drop(reader);
+			let meta_option = self.reader.get_meta().unwrap();
 
 			// If metadata is empty, return a not found response
 			meta_option.as_ref()?;
@@ -120,12 +123,239 @@ impl TileSource {
 				meta_option.unwrap(),
 				&TileCompression::Uncompressed,
 				"application/json",
-			);
+			)
+			.map(|response| response.with_last_modified(self.last_modified));
 		}
 
 		// If the request is unknown, return a not found response
 		None
 	}
+
+	// Parse the tile coordinates of an url like "z/x/y.ext", depending on the url scheme
+	pub fn get_coord(&self, url: &Url) -> Option<TileCoord3> {
+		self.scheme.get_coord(url)
+	}
+
+	// Choose the format of a tile response: by the extension of the url, e.g. "z/x/y.webp",
+	// or by the `Accept` header if the url has no extension.
+	// Raster tiles are transcoded, vector tiles can be rendered as GeoJSON.
+	pub fn get_response_format(&self, url: &Url, accept: Option<&str>) -> TileFormat {
+		let filename = url.as_vec().pop().unwrap_or_default();
+		let extension = filename.split_once('.').map(|(_, extension)| extension);
+
+		if self.tile_format == TileFormat::PBF {
+			return match extension {
+				Some("geojson") => TileFormat::GEOJSON,
+				_ => self.tile_format,
+			};
+		}
+
+		if !is_raster(self.tile_format) {
+			return self.tile_format;
+		}
+
+		match extension {
+			Some(extension) => TileFormat::parse_str(extension)
+				.ok()
+				.filter(|format| is_raster(*format))
+				.unwrap_or(self.tile_format),
+			None => accept.map_or(self.tile_format, |accept| {
+				negotiate_format(accept, self.tile_format)
+			}),
+		}
+	}
+
+	// If the response format depends on the `Accept` header, caches must know that
+	pub fn negotiates_format(&self, url: &Url) -> bool {
+		is_raster(self.tile_format)
+			&& url
+				.as_vec()
+				.last()
+				.is_some_and(|filename| !filename.contains('.'))
+	}
+
+	// Get a tile in `format`, raster tiles in other formats are transcoded and vector tiles rendered as GeoJSON
+	pub async fn get_tile_as(
+		&self,
+		coord: &TileCoord3,
+		format: TileFormat,
+	) -> Option<SourceResponse> {
+		let mut response = self.get_tile(coord).await?;
+		if format == self.tile_format {
+			return Some(response);
+		}
+
+		// encoding an image takes a while, so it must not block the other requests
+		let (from, compression, coord) = (self.tile_format, response.compression, *coord);
+		let blob = response.blob;
+		let result = tokio::task::spawn_blocking(move || {
+			let blob = decompress(blob, &compression)?;
+			match (from, format) {
+				(TileFormat::PBF, TileFormat::GEOJSON) => vector_tile_to_geojson(&blob, &coord),
+				(from, to) if is_raster(from) => transcode(&blob, from, to),
+				_ => bail!("can not convert {from} to {format}"),
+			}
+		})
+		.await;
+
+		match result {
+			Ok(Ok(blob)) => {
+				response.blob = blob;
+				response.compression = TileCompression::Uncompressed;
+				response.mime = get_mime(format).to_owned();
+				Some(response)
+			}
+			Ok(Err(error)) => {
+				log::warn!(
+					"{}: failed to transcode tile {coord:?} to {format}: {error:#}",
+					self.prefix
+				);
+				None
+			}
+			Err(error) => {
+				log::error!("{}: transcoding failed: {error}", self.prefix);
+				None
+			}
+		}
+	}
+
+	// Get a tile in the compression of the container
+	pub async fn get_tile(&self, coord: &TileCoord3) -> Option<SourceResponse> {
+		log::debug!("get tile {} - {:?}", self.prefix, coord);
+
+		// If tile data is not found, return a not found response
+		let tile = self.reader.get_tile_data(coord).await.ok()??;
+
+		SourceResponse::new_some(tile, &self.compression, &self.tile_mime)
+			.map(|response| response.with_last_modified(self.last_modified))
+	}
+
+	// Build a TileJSON 3.0 document, tile URLs are absolute if `base_url` (e.g. "http://host:port") is given
+	pub fn get_tile_json(&self, base_url: &str) -> Option<SourceResponse> {
+		let parameters = self.reader.get_parameters();
+		let bbox_pyramid = &parameters.bbox_pyramid;
+		let zoom_min = bbox_pyramid.get_zoom_min()?;
+		let zoom_max = bbox_pyramid.get_zoom_max()?;
+		let bounds = bbox_pyramid.get_geo_bbox();
+		let format = format!("{:?}", parameters.tile_format).to_lowercase();
+
+		// start with the container meta, so fields like "vector_layers" or "attribution" are kept
+		let mut tile_json = match self.reader.get_meta() {
+			Ok(Some(meta)) => match parse_json(meta.as_str()) {
+				Ok(JsonValue::Object(object)) => object,
+				_ => {
+					log::warn!("{}: meta data is not a JSON object", self.prefix);
+					BTreeMap::new()
+				}
+			},
+			_ => BTreeMap::new(),
+		};
+
+		let mut set = |key: &str, value: JsonValue| tile_json.insert(key.to_owned(), value);
+		set("tilejson", JsonValue::from("3.0.0"));
+		set(
+			"tiles",
+			JsonValue::from(vec![format!(
+				"{base_url}{}{}.{format}",
+				self.prefix.as_dir(),
+				self.scheme.get_template()
+			)]),
+		);
+		set(
+			"scheme",
+			JsonValue::from(self.scheme.get_tile_json_scheme()),
+		);
+		set("minzoom", JsonValue::from(zoom_min));
+		set("maxzoom", JsonValue::from(zoom_max));
+		set("bounds", JsonValue::from(bounds.to_vec()));
+		set(
+			"center",
+			JsonValue::from(vec![
+				(bounds[0] + bounds[2]) / 2.0,
+				(bounds[1] + bounds[3]) / 2.0,
+				((zoom_min + zoom_max) / 2) as f64,
+			]),
+		);
+		set("format", JsonValue::from(format));
+
+		// TileJSON 3.0 requires "vector_layers" for vector tiles
+		if parameters.tile_format == TileFormat::PBF {
+			tile_json
+				.entry(String::from("vector_layers"))
+				.or_insert(JsonValue::Array(vec![]));
+		}
+
+		SourceResponse::new_some(
+			Blob::from(JsonValue::Object(tile_json).stringify()),
+			&TileCompression::Uncompressed,
+			"application/json",
+		)
+		.map(|response| response.with_last_modified(self.last_modified))
+	}
+}
+
+fn vector_tile_to_geojson(blob: &Blob, coord: &TileCoord3) -> Result<Blob> {
+	let collection = GeoCollection {
+		features: VectorTile::from_blob(blob)?.to_geo_features(coord)?,
+	};
+	Ok(Blob::from(collection.to_json().stringify()))
+}
+
+pub fn get_mime(format: TileFormat) -> &'static str {
+	use TileFormat::*;
+	match format {
+		BIN => "application/octet-stream",
+		PNG => "image/png",
+		JPG => "image/jpeg",
+		WEBP => "image/webp",
+		AVIF => "image/avif",
+		SVG => "image/svg+xml",
+		PBF => "application/x-protobuf",
+		GEOJSON => "application/geo+json",
+		TOPOJSON => "application/topo+json",
+		JSON => "application/json",
+	}
+}
+
+// formats that can be transcoded into each other
+fn is_raster(format: TileFormat) -> bool {
+	matches!(format, TileFormat::PNG | TileFormat::JPG | TileFormat::WEBP)
+}
+
+// Prefers WebP, then the format of the source, then JPEG and PNG.
+// WebP is only sent if it is listed explicitly, because older browsers accept "image/*" without supporting it.
+fn negotiate_format(accept: &str, source_format: TileFormat) -> TileFormat {
+	let accepted: Vec<&str> = accept
+		.split(',')
+		.filter_map(|item| {
+			let mut parts = item.split(';').map(str::trim);
+			let mime = parts.next()?;
+			let is_refused = parts.any(|param| {
+				param
+					.strip_prefix("q=")
+					.and_then(|q| q.parse::<f32>().ok())
+					.is_some_and(|q| q <= 0.0)
+			});
+			(!is_refused).then_some(mime)
+		})
+		.collect();
+
+	let is_accepted = |format: TileFormat| {
+		let mime = get_mime(format);
+		accepted.iter().any(|accepted| {
+			*accepted == mime || (format != TileFormat::WEBP && matches!(*accepted, "image/*" | "*/*"))
+		})
+	};
+
+	[
+		TileFormat::WEBP,
+		source_format,
+		TileFormat::JPG,
+		TileFormat::PNG,
+	]
+	.into_iter()
+	.find(|format| is_accepted(*format))
+	.unwrap_or(source_format)
 }
 
 // Debug implementation for TileSource
@@ -142,8 +372,13 @@ impl Debug for TileSource {
 #[cfg(test)]
 mod tests {
 	use super::*;
-	use crate::container::{MockTilesReader, MockTilesReaderProfile};
+	use crate::{
+		container::{MockTilesReader, MockTilesReaderProfile},
+		types::{Blob, TileBBoxPyramid, TilesReaderParameters},
+	};
 	use anyhow::Result;
+	use async_trait::async_trait;
+	use tokio::sync::Barrier;
 
 	// Test the constructor function for TileSource
 	#[test]
@@ -162,7 +397,7 @@ mod tests {
 	fn debug() -> Result<()> {
 		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
 		let container = TileSource::from(reader.boxed(), Url::new("prefix")).unwrap();
-		assert_eq!(format!("{container:?}"), "TileSource { reader: Mutex { data: MockTilesReader { parameters: TilesReaderParameters { bbox_pyramid: [0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64), 4: [0,0,15,15] (256)], tile_compression: Uncompressed, tile_format: PNG } } }, tile_mime: \"image/png\", compression: Uncompressed }");
+		assert_eq!(format!("{container:?}"), "TileSource { reader: MockTilesReader { parameters: TilesReaderParameters { bbox_pyramid: [0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64), 4: [0,0,15,15] (256)], tile_compression: Uncompressed, tile_format: PNG } }, tile_mime: \"image/png\", compression: Uncompressed }");
 		Ok(())
 	}
 
@@ -222,4 +457,237 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn transcoding() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let source = TileSource::from(reader.boxed(), Url::new("prefix"))?;
+		let get = |url: &str| {
+			let (source, url) = (source.clone(), Url::new(url));
+			async move { source.get_data(&url, &TargetCompression::from_none()).await }
+		};
+
+		let response = get("0/0/0.webp").await.unwrap();
+		assert_eq!(response.mime, "image/webp");
+		assert_eq!(&response.blob.as_slice()[8..12], b"WEBP");
+
+		let response = get("0/0/0.jpg").await.unwrap();
+		assert_eq!(response.mime, "image/jpeg");
+		assert_eq!(&response.blob.as_slice()[0..2], b"\xff\xd8");
+
+		// other formats are ignored, as before
+		assert_eq!(get("0/0/0.pbf").await.unwrap().mime, "image/png");
+
+		// vector tiles are never transcoded
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let source = TileSource::from(reader.boxed(), Url::new("prefix"))?;
+		let response = source
+			.get_data(&Url::new("0/0/0.png"), &TargetCompression::from_none())
+			.await
+			.unwrap();
+		assert_eq!(response.mime, "application/x-protobuf");
+
+		// but can be rendered as GeoJSON
+		let response = source
+			.get_data(&Url::new("0/0/0.geojson"), &TargetCompression::from_none())
+			.await
+			.unwrap();
+		assert_eq!(response.mime, "application/geo+json");
+		assert_eq!(response.compression, TileCompression::Uncompressed);
+		let JsonValue::Object(json) = parse_json(response.blob.as_str())? else {
+			panic!("GeoJSON must be an object")
+		};
+		assert_eq!(json["type"], JsonValue::from("FeatureCollection"));
+		let JsonValue::Array(features) = &json["features"] else {
+			panic!("features must be an array")
+		};
+		assert!(!features.is_empty());
+		let JsonValue::Object(feature) = &features[0] else {
+			panic!("a feature must be an object")
+		};
+		let JsonValue::Object(properties) = &feature["properties"] else {
+			panic!("properties must be an object")
+		};
+		assert_eq!(properties["layer"], JsonValue::from("ocean"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn response_format() -> Result<()> {
+		use TileFormat::*;
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let source = TileSource::from(reader.boxed(), Url::new("prefix"))?;
+		let test =
+			|url: &str, accept: Option<&str>| source.get_response_format(&Url::new(url), accept);
+
+		assert_eq!(test("1/2/3.png", Some("image/webp")), PNG);
+		assert_eq!(test("1/2/3.webp", None), WEBP);
+		assert_eq!(test("1/2/3.jpeg", None), JPG);
+		assert_eq!(test("1/2/3.mvt", None), PNG);
+		assert_eq!(test("1/2/3", None), PNG);
+		assert_eq!(
+			test("1/2/3", Some("image/avif,image/webp,image/apng,*/*;q=0.8")),
+			WEBP
+		);
+		assert_eq!(
+			test("1/2/3", Some("image/png,image/*;q=0.8,*/*;q=0.5")),
+			PNG
+		);
+		assert_eq!(test("1/2/3", Some("image/jpeg")), JPG);
+		assert_eq!(test("1/2/3", Some("image/webp;q=0, image/jpeg")), JPG);
+		assert_eq!(test("1/2/3", Some("text/html")), PNG);
+
+		assert!(source.negotiates_format(&Url::new("1/2/3")));
+		assert!(!source.negotiates_format(&Url::new("1/2/3.png")));
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let source = TileSource::from(reader.boxed(), Url::new("prefix"))?;
+		assert_eq!(
+			source.get_response_format(&Url::new("1/2/3.png"), Some("image/png")),
+			PBF
+		);
+		assert!(!source.negotiates_format(&Url::new("1/2/3")));
+
+		Ok(())
+	}
+
+	#[test]
+	fn tile_json() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let source = TileSource::from(reader.boxed(), Url::new("tiles/osm/"))?;
+
+		let response = source.get_tile_json("http://localhost:8080").unwrap();
+		assert_eq!(response.mime, "application/json");
+		assert_eq!(
+			response.blob.as_str(),
+			"{\"bounds\":[-180,-85.05112877980659,180,85.05112877980659],\"center\":[0,0,2],\"format\":\"pbf\",\"maxzoom\":4,\"minzoom\":0,\"scheme\":\"xyz\",\"tilejson\":\"3.0.0\",\"tiles\":[\"http://localhost:8080/tiles/osm/{z}/{x}/{y}.pbf\"],\"vector_layers\":[]}"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn url_schemes() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let mut source = TileSource::from(reader.boxed(), Url::new("tiles/osm/"))?;
+
+		source.scheme = UrlScheme::Tms;
+		assert_eq!(
+			source.get_coord(&Url::new("2/1/0.pbf")),
+			Some(TileCoord3::new(1, 3, 2)?)
+		);
+		let tile_json = source.get_tile_json("").unwrap();
+		assert!(tile_json.blob.as_str().contains("\"scheme\":\"tms\""));
+
+		source.scheme = UrlScheme::Quadkey;
+		assert_eq!(
+			source.get_coord(&Url::new("q/13.pbf")),
+			Some(TileCoord3::new(3, 1, 2)?)
+		);
+		let tile_json = source.get_tile_json("").unwrap();
+		assert!(tile_json
+			.blob
+			.as_str()
+			.contains("\"tiles\":[\"/tiles/osm/q/{quadkey}.pbf\"]"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn tile_json_keeps_meta() -> Result<()> {
+		let reader = BarrierReader {
+			parameters: TilesReaderParameters::new(
+				TileFormat::PNG,
+				TileCompression::Uncompressed,
+				TileBBoxPyramid::from_geo_bbox(3, 6, &[13.0, 52.0, 14.0, 53.0]),
+			),
+			barrier: Barrier::new(1),
+			meta: Some(Blob::from(
+				"{\"attribution\":\"© OpenStreetMap\",\"minzoom\":0,\"tilejson\":\"2.0.0\"}",
+			)),
+		};
+		let source = TileSource::from(Box::new(reader), Url::new("raster"))?;
+
+		let tile_json = parse_json(source.get_tile_json("").unwrap().blob.as_str())?;
+		let JsonValue::Object(tile_json) = tile_json else {
+			panic!("TileJSON must be an object")
+		};
+		assert_eq!(tile_json["attribution"], JsonValue::from("© OpenStreetMap"));
+		assert_eq!(tile_json["tilejson"], JsonValue::from("3.0.0"));
+		assert_eq!(tile_json["minzoom"], JsonValue::from(3));
+		assert_eq!(tile_json["maxzoom"], JsonValue::from(5));
+		assert_eq!(tile_json["format"], JsonValue::from("png"));
+		assert_eq!(
+			tile_json["tiles"],
+			JsonValue::from(vec!["/raster/{z}/{x}/{y}.png"])
+		);
+		assert!(!tile_json.contains_key("vector_layers"));
+
+		Ok(())
+	}
+
+	// A reader that answers only when all expected requests are running at the same time
+	#[derive(Debug)]
+	struct BarrierReader {
+		parameters: TilesReaderParameters,
+		barrier: Barrier,
+		meta: Option<Blob>,
+	}
+
+	#[async_trait]
+	impl TilesReaderTrait for BarrierReader {
+		fn get_name(&self) -> &str {
+			"barrier"
+		}
+		fn get_container_name(&self) -> &str {
+			"barrier"
+		}
+		fn get_parameters(&self) -> &TilesReaderParameters {
+			&self.parameters
+		}
+		fn override_compression(&mut self, _tile_compression: TileCompression) {}
+		fn get_meta(&self) -> Result<Option<Blob>> {
+			Ok(self.meta.clone())
+		}
+		async fn get_tile_data(&self, _coord: &TileCoord3) -> Result<Option<Blob>> {
+			self.barrier.wait().await;
+			Ok(Some(Blob::from("tile")))
+		}
+	}
+
+	// Requests to the same source must not block each other
+	#[tokio::test(flavor = "multi_thread")]
+	async fn concurrent_requests() -> Result<()> {
+		const COUNT: usize = 8;
+
+		let reader = BarrierReader {
+			parameters: TilesReaderParameters::new(
+				TileFormat::PBF,
+				TileCompression::Uncompressed,
+				TileBBoxPyramid::new_full(2),
+			),
+			barrier: Barrier::new(COUNT),
+			meta: None,
+		};
+		let source = TileSource::from(Box::new(reader), Url::new("prefix"))?;
+
+		let handles: Vec<_> = (0..COUNT)
+			.map(|_| {
+				let source = source.clone();
+				tokio::spawn(async move {
+					source
+						.get_data(&Url::new("1/0/1.pbf"), &TargetCompression::from_none())
+						.await
+				})
+			})
+			.collect();
+
+		for handle in handles {
+			assert_eq!(handle.await?.unwrap().blob.as_str(), "tile");
+		}
+
+		Ok(())
+	}
 }
@@ -0,0 +1,138 @@
+//! url schemes of tile sources, so legacy clients can request tiles without a rewriting proxy
+//!
+//! | scheme    | url             | example        |
+//! |-----------|-----------------|----------------|
+//! | `xyz`     | `{z}/{x}/{y}`   | `3/4/2.png`    |
+//! | `tms`     | `{z}/{x}/{y}`   | `3/4/5.png`    |
+//! | `zyx`     | `{z}/{y}/{x}`   | `3/2/4.png`    |
+//! | `quadkey` | `q/{quadkey}`   | `q/120.png`    |
+//!
+//! TMS counts rows from the bottom. Quadkeys are used by Bing Maps, every digit selects a quarter of the tile above.
+
+use super::super::utils::Url;
+use crate::{types::TileCoord3, utils::TransformCoord};
+use anyhow::{bail, Result};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum UrlScheme {
+	/// "{z}/{x}/{y}", used by most web maps
+	#[default]
+	Xyz,
+	/// "{z}/{x}/{y}" with rows counted from the bottom
+	Tms,
+	/// "{z}/{y}/{x}"
+	Zyx,
+	/// "q/{quadkey}", as used by Bing Maps
+	Quadkey,
+}
+
+impl UrlScheme {
+	pub fn parse_str(value: &str) -> Result<UrlScheme> {
+		Ok(match value.to_lowercase().trim() {
+			"xyz" => UrlScheme::Xyz,
+			"tms" => UrlScheme::Tms,
+			"zyx" => UrlScheme::Zyx,
+			"quadkey" => UrlScheme::Quadkey,
+			_ => bail!("unknown url scheme \"{value}\", use \"xyz\", \"tms\", \"zyx\" or \"quadkey\""),
+		})
+	}
+
+	// Parse the tile coordinates of an url relative to the source, e.g. "3/4/2.png"
+	pub fn get_coord(&self, url: &Url) -> Option<TileCoord3> {
+		let parts: Vec<String> = url.as_vec();
+		// the last part can have an extension, e.g. "2.png"
+		let get_number =
+			|part: &String| -> String { part.chars().take_while(|c| c.is_numeric()).collect() };
+
+		if *self == UrlScheme::Quadkey {
+			return match parts.as_slice() {
+				[q, key] if q == "q" => parse_quadkey(&get_number(key)),
+				_ => None,
+			};
+		}
+
+		if parts.len() < 3 {
+			return None;
+		}
+		let z = parts[0].parse::<u8>().ok()?;
+		let a = parts[1].parse::<u32>().ok()?;
+		let b = get_number(&parts[2]).parse::<u32>().ok()?;
+
+		let mut coord = match self {
+			UrlScheme::Zyx => TileCoord3::new(b, a, z),
+			_ => TileCoord3::new(a, b, z),
+		}
+		.ok()?;
+		if !coord.is_valid() {
+			return None;
+		}
+		if *self == UrlScheme::Tms {
+			coord.flip_y();
+		}
+		Some(coord)
+	}
+
+	// The url of the tiles relative to the source, as used in TileJSON
+	pub fn get_template(&self) -> &'static str {
+		match self {
+			UrlScheme::Xyz | UrlScheme::Tms => "{z}/{x}/{y}",
+			UrlScheme::Zyx => "{z}/{y}/{x}",
+			UrlScheme::Quadkey => "q/{quadkey}",
+		}
+	}
+
+	// The "scheme" of TileJSON, which only knows "xyz" and "tms"
+	pub fn get_tile_json_scheme(&self) -> &'static str {
+		match self {
+			UrlScheme::Tms => "tms",
+			_ => "xyz",
+		}
+	}
+}
+
+fn parse_quadkey(key: &str) -> Option<TileCoord3> {
+	if key.is_empty() || key.len() > 30 {
+		return None;
+	}
+	let (mut x, mut y) = (0u32, 0u32);
+	for digit in key.chars() {
+		let digit = digit.to_digit(4)?;
+		x = (x << 1) | (digit & 1);
+		y = (y << 1) | (digit >> 1);
+	}
+	TileCoord3::new(x, y, key.len() as u8).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn get_coord() {
+		let coord =
+			|scheme: UrlScheme, url: &str| scheme.get_coord(&Url::new(url)).map(|c| (c.z, c.x, c.y));
+
+		assert_eq!(coord(UrlScheme::Xyz, "3/4/2.png"), Some((3, 4, 2)));
+		assert_eq!(coord(UrlScheme::Xyz, "3/4/2"), Some((3, 4, 2)));
+		assert_eq!(coord(UrlScheme::Tms, "3/4/5.png"), Some((3, 4, 2)));
+		assert_eq!(coord(UrlScheme::Zyx, "3/2/4.png"), Some((3, 4, 2)));
+		assert_eq!(coord(UrlScheme::Quadkey, "q/120.png"), Some((3, 4, 2)));
+		assert_eq!(coord(UrlScheme::Quadkey, "q/0231"), Some((4, 3, 6)));
+
+		assert_eq!(coord(UrlScheme::Xyz, "3/4"), None);
+		assert_eq!(coord(UrlScheme::Xyz, "3/8/2.png"), None);
+		assert_eq!(coord(UrlScheme::Tms, "3/4/8.png"), None);
+		assert_eq!(coord(UrlScheme::Quadkey, "3/4/2.png"), None);
+		assert_eq!(coord(UrlScheme::Quadkey, "q/0241.png"), None);
+		assert_eq!(coord(UrlScheme::Quadkey, "q/.png"), None);
+	}
+
+	#[test]
+	fn parse_str() {
+		assert_eq!(UrlScheme::parse_str("TMS").unwrap(), UrlScheme::Tms);
+		assert_eq!(
+			UrlScheme::parse_str("wms").unwrap_err().to_string(),
+			"unknown url scheme \"wms\", use \"xyz\", \"tms\", \"zyx\" or \"quadkey\""
+		);
+	}
+}
@@ -1,35 +1,65 @@
 use super::{
-	sources::{SourceResponse, StaticSource, TileSource},
-	utils::Url,
+	access_log::{AccessLog, AccessLogEntry},
+	cache::ResponseCache,
+	metrics::Metrics,
+	options::{BaseUrl, ResponseOptions, SourceOptions},
+	reload::ReloadRequest,
+	sources::{ContentRange, FilePart, SourceResponse, StaticSource, TileSource, UrlScheme},
+	utils::{is_incompressible, is_not_modified, is_range_valid, RangeRequest, Url},
+	wmts::{self, WmtsRequest},
 };
 use crate::{
-	types::{Blob, TileCompression, TilesReaderTrait},
-	utils::{optimize_compression, TargetCompression},
+	types::{Blob, ByteRange, TileCompression, TilesReaderTrait},
+	utils::{get_optimal_compression, recompress, JsonValue, TargetCompression},
 };
 use anyhow::{bail, Result};
 use axum::{
-	body::Body,
-	extract::State,
+	body::{Body, HttpBody},
+	extract::{ConnectInfo, Request, State},
 	http::{
-		header::{ACCEPT_ENCODING, CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE},
-		HeaderMap, Uri,
+		header::{
+			ACCEPT, ACCEPT_ENCODING, ACCEPT_RANGES, AUTHORIZATION, CACHE_CONTROL, CONTENT_ENCODING,
+			CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, LAST_MODIFIED, REFERER, USER_AGENT,
+			WWW_AUTHENTICATE,
+		},
+		HeaderMap, HeaderName, HeaderValue, Uri,
 	},
+	middleware::{self, Next},
 	response::Response,
-	routing::get,
+	routing::{get, post, MethodRouter},
 	Router,
 };
-use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, VARY};
-use std::path::Path;
-use tokio::sync::oneshot::Sender;
+use hyper::header::VARY;
+use std::{
+	fs::File,
+	io::{Read, Seek, SeekFrom},
+	net::SocketAddr,
+	path::Path,
+	sync::{Arc, RwLock},
+	time::{Instant, SystemTime},
+};
+use tokio::{
+	sync::{mpsc, oneshot, watch::Sender},
+	task::JoinHandle,
+};
+use tower::ServiceExt;
 
 pub struct TileServer {
 	ip: String,
 	port: u16,
+	additional_addresses: Vec<(String, u16)>,
 	tile_sources: Vec<TileSource>,
 	static_sources: Vec<StaticSource>,
-	exit_signal: Option<Sender<()>>,
-	use_best_compression: bool,
+	exit_signal: Option<Sender<bool>>,
+	options: ResponseOptions,
 	use_api: bool,
+	metrics: Option<Metrics>,
+	access_log: Option<AccessLog>,
+	reopen_task: Option<JoinHandle<()>>,
+	cache: Option<ResponseCache>,
+	admin: Option<(String, mpsc::Sender<ReloadRequest>)>,
+	// the listeners pass every request to the current router, so it can be replaced while running
+	router: Arc<RwLock<Router>>,
 }
 
 impl TileServer {
@@ -37,18 +67,91 @@ impl TileServer {
 		TileServer {
 			ip: ip.to_owned(),
 			port,
+			additional_addresses: Vec::new(),
 			tile_sources: Vec::new(),
 			static_sources: Vec::new(),
 			exit_signal: None,
-			use_best_compression,
+			options: ResponseOptions {
+				best_compression: use_best_compression,
+				..Default::default()
+			},
 			use_api,
+			metrics: None,
+			access_log: None,
+			reopen_task: None,
+			cache: None,
+			admin: None,
+			router: Default::default(),
 		}
 	}
 
+	/// Counts requests, bytes and latencies, and serves them at "/metrics".
+	pub fn enable_metrics(&mut self) {
+		self.metrics = Some(Metrics::new());
+	}
+
+	/// Keeps up to `maximum_size` bytes of tiles in memory, in the encodings they were sent.
+	pub fn set_cache_size(&mut self, maximum_size: usize) {
+		self.cache = Some(ResponseCache::new(maximum_size));
+	}
+
+	/// Writes one line per request to the access log.
+	pub fn set_access_log(&mut self, access_log: AccessLog) {
+		self.access_log = Some(access_log);
+	}
+
+	/// Enables `POST /api/admin/reload`, which requires the header `Authorization: Bearer <token>`
+	/// and sends a reload request to `sender`.
+	pub fn set_admin_token(&mut self, token: &str, sender: mpsc::Sender<ReloadRequest>) {
+		self.admin = Some((token.to_owned(), sender));
+	}
+
+	/// Listens on an additional address, e.g. for IPv4 and IPv6.
+	pub fn add_address(&mut self, ip: &str, port: u16) {
+		self.additional_addresses.push((ip.to_owned(), port));
+	}
+
+	/// Restricts access to the given origins. `*` allows every origin.
+	pub fn set_allowed_origins(&mut self, origins: Vec<String>) {
+		self.options.allowed_origins = origins;
+	}
+
+	/// Sets the `max-age` of the `Cache-Control` header in seconds.
+	pub fn set_max_age(&mut self, max_age: u64) {
+		self.options.max_age = max_age;
+	}
+
+	/// Sets where the scheme and host of the URLs in `tiles.json` and the WMTS capabilities come from.
+	pub fn set_base_url(&mut self, base_url: BaseUrl) {
+		self.options.base_url = base_url;
+	}
+
+	/// Adds a header to every response.
+	pub fn add_header(&mut self, name: HeaderName, value: HeaderValue) {
+		self.options.extra_headers.push((name, value));
+	}
+
+	#[cfg(test)]
 	pub fn add_tile_source(
 		&mut self,
 		url_prefix: Url,
 		reader: Box<dyn TilesReaderTrait>,
+	) -> Result<()> {
+		self.add_tile_source_with_options(
+			url_prefix,
+			reader,
+			SourceOptions::default(),
+			UrlScheme::default(),
+		)
+	}
+
+	/// Adds a tile source with its own CORS, cache and header settings and url scheme.
+	pub fn add_tile_source_with_options(
+		&mut self,
+		url_prefix: Url,
+		reader: Box<dyn TilesReaderTrait>,
+		options: SourceOptions,
+		scheme: UrlScheme,
 	) -> Result<()> {
 		let url_prefix = url_prefix.as_dir();
 
@@ -63,20 +166,39 @@ impl TileServer {
 			};
 		}
 
-		self
-			.tile_sources
-			.push(TileSource::from(reader, url_prefix)?);
+		let mut tile_source = TileSource::from(reader, url_prefix)?;
+		tile_source.options = options;
+		tile_source.scheme = scheme;
+		self.tile_sources.push(tile_source);
 
 		Ok(())
 	}
 
+	#[cfg(test)]
 	pub fn add_static_source(&mut self, path: &Path, url_prefix: Url) -> Result<()> {
+		self.add_static_source_with_options(path, url_prefix, SourceOptions::default())
+	}
+
+	/// Adds a static source with its own CORS, cache and header settings.
+	pub fn add_static_source_with_options(
+		&mut self,
+		path: &Path,
+		url_prefix: Url,
+		options: SourceOptions,
+	) -> Result<()> {
 		let url_prefix = url_prefix.as_dir();
 
 		log::info!("add static: {path:?}");
-		self
-			.static_sources
-			.push(StaticSource::new(path, url_prefix)?);
+		let mut static_source = StaticSource::new(path, url_prefix)?;
+		static_source.options = options;
+		self.static_sources.push(static_source);
+		Ok(())
+	}
+
+	/// Serves a single file, e.g. a container file, that clients can read with range requests.
+	pub fn add_static_file(&mut self, path: &Path, url: Url) -> Result<()> {
+		log::info!("add static file: {path:?} at {url}");
+		self.static_sources.push(StaticSource::new_file(path, url)?);
 		Ok(())
 	}
 
@@ -87,32 +209,62 @@ impl TileServer {
 
 		log::info!("starting server");
 
-		// Initialize App
-		let mut router = Router::new().route("/status", get(|| async { "ready!" }));
-
-		router = self.add_tile_sources_to_app(router);
-		if self.use_api {
-			router = self.add_api_to_app(router).await?;
-		}
-		router = self.add_static_sources_to_app(router);
-
-		let addr = format!("{}:{}", self.ip, self.port);
-		eprintln!("server starts listening on {}", addr);
-
-		let listener = tokio::net::TcpListener::bind(addr).await?;
-		let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+		*self
+			.router
+			.write()
+			.expect("router lock should not be poisoned") = self.build_router().await?;
+
+		// every request is answered by the router that was current when it arrived
+		let shared = self.router.clone();
+		let router = Router::new().fallback(|request: Request| async move {
+			let router = shared
+				.read()
+				.expect("router lock should not be poisoned")
+				.clone();
+			router.oneshot(request).await
+		});
 
-		tokio::spawn(async {
-			axum::serve(listener, router.into_make_service())
-				.with_graceful_shutdown(async {
-					rx.await.ok();
+		let (tx, rx) = tokio::sync::watch::channel(false);
+
+		let addresses = std::iter::once((self.ip.clone(), self.port))
+			.chain(self.additional_addresses.iter().cloned());
+		for (ip, port) in addresses {
+			let addr = format!("{ip}:{port}");
+			eprintln!("server starts listening on {}", addr);
+
+			let listener = tokio::net::TcpListener::bind(addr).await?;
+			let router = router.clone();
+			let mut rx = rx.clone();
+
+			tokio::spawn(async move {
+				axum::serve(
+					listener,
+					router.into_make_service_with_connect_info::<SocketAddr>(),
+				)
+				.with_graceful_shutdown(async move {
+					rx.wait_for(|stop| *stop).await.ok();
 				})
 				.await
 				.expect("should start server")
-		});
+			});
+		}
 
 		self.exit_signal = Some(tx);
 
+		// logrotate moves the file and sends SIGUSR1, like for nginx
+		#[cfg(unix)]
+		if let Some(access_log) = self.access_log.clone() {
+			use tokio::signal::unix::{signal, SignalKind};
+			let mut signals = signal(SignalKind::user_defined1())?;
+			self.reopen_task = Some(tokio::spawn(async move {
+				while signals.recv().await.is_some() {
+					if let Err(error) = access_log.reopen() {
+						log::error!("{error:#}");
+					}
+				}
+			}));
+		}
+
 		Ok(())
 	}
 
@@ -123,48 +275,163 @@ impl TileServer {
 
 		log::info!("stopping server");
 
+		if let Some(task) = self.reopen_task.take() {
+			task.abort();
+		}
+		if let Some(access_log) = &self.access_log {
+			access_log.flush().await;
+		}
+
 		self
 			.exit_signal
 			.take()
 			.expect("should have exit signal")
-			.send(())
+			.send(true)
 			.expect("should habe send exit signal");
 	}
 
+	/// Replaces the tile and static sources and the response options with those of `other`.
+	///
+	/// If the server is running, new requests are answered with the new sources,
+	/// while requests that already started are finished with the old ones.
+	pub async fn replace_sources(&mut self, other: TileServer) -> Result<()> {
+		self.tile_sources = other.tile_sources;
+		self.static_sources = other.static_sources;
+		self.options = other.options;
+
+		if self.exit_signal.is_some() {
+			let router = self.build_router().await?;
+			*self
+				.router
+				.write()
+				.expect("router lock should not be poisoned") = router;
+			log::info!("replaced sources");
+		}
+
+		// new sources have a new generation, so tiles that old requests still add are never served
+		if let Some(cache) = &self.cache {
+			cache.clear();
+		}
+		Ok(())
+	}
+
+	async fn build_router(&self) -> Result<Router> {
+		let mut router = Router::new().route("/status", get(|| async { "ready!" }));
+
+		router = self.add_tile_sources_to_app(router);
+		if self.use_api {
+			router = self.add_api_to_app(router).await?;
+		}
+		router = self.add_admin_to_app(router);
+		router = self.add_static_sources_to_app(router);
+
+		if let Some(metrics) = self.metrics.clone() {
+			let cache = self.cache.clone();
+			router = router.route(
+				"/metrics",
+				get(|| async move {
+					let stats = cache.as_ref().map(|cache| cache.get_stats());
+					Response::builder()
+						.header(CONTENT_TYPE, "text/plain; version=0.0.4")
+						.body(Body::from(metrics.render(stats.as_ref())))
+						.expect("should have build a body")
+				}),
+			);
+		}
+
+		Ok(router)
+	}
+
 	fn add_tile_sources_to_app(&self, mut app: Router) -> Router {
 		for tile_source in self.tile_sources.iter() {
 			let route = tile_source.prefix.join_as_string("*path");
 
+			let options = self.options.with_source_options(&tile_source.options);
 			let tile_app = Router::new()
-				.route(&route, get(serve_tile))
-				.with_state((tile_source.clone(), self.use_best_compression));
+				.route(&route, get(serve_tile).merge(preflight(&options)))
+				.with_state((tile_source.clone(), options, self.cache.clone()));
 
-			app = app.merge(tile_app);
+			app = app.merge(self.track_requests(tile_app, &get_source_id(tile_source)));
 
 			async fn serve_tile(
 				uri: Uri,
 				headers: HeaderMap,
-				State((tile_source, best_compression)): State<(TileSource, bool)>,
+				State((tile_source, options, cache)): State<(
+					TileSource,
+					ResponseOptions,
+					Option<ResponseCache>,
+				)>,
 			) -> Response<Body> {
 				let path = Url::new(uri.path());
 
-				let mut target_compressions = get_encoding(headers);
-				target_compressions.set_best_compression(best_compression);
-
-				let response = tile_source
-					.get_data(
-						&path
-							.strip_prefix(&tile_source.prefix)
-							.expect("should start with prefix"),
-						&target_compressions,
+				let mut target_compressions = get_encoding(&headers);
+				target_compressions.set_best_compression(options.best_compression);
+
+				let url = path
+					.strip_prefix(&tile_source.prefix)
+					.expect("should start with prefix");
+
+				let (coord, format) = if url.str == "/wmts" {
+					let id = get_source_id(&tile_source);
+					let query = uri.query().unwrap_or_default();
+					match wmts::parse_request(query, &tile_source, &id) {
+						Ok(WmtsRequest::GetTile { coord, format }) => (Some(coord), format),
+						Ok(WmtsRequest::GetCapabilities) => {
+							let base_url = options.get_base_url(&headers);
+							let xml = wmts::get_capabilities(&tile_source, &id, &base_url);
+							let response = SourceResponse::new_some(
+								Blob::from(xml),
+								&TileCompression::Uncompressed,
+								"application/xml",
+							)
+							.expect("should have created a response");
+							let mut response = ok_data(response, target_compressions, &headers, &options);
+							options.add_base_url_headers(response.headers_mut());
+							return response;
+						}
+						Err(exception) => {
+							return Response::builder()
+								.status(exception.status)
+								.header(CONTENT_TYPE, "application/xml")
+								.body(Body::from(exception.as_xml()))
+								.expect("should have build a body");
+						}
+					}
+				} else {
+					let accept = headers.get(ACCEPT).and_then(|value| value.to_str().ok());
+					(
+						tile_source.get_coord(&url),
+						tile_source.get_response_format(&url, accept),
 					)
-					.await;
+				};
+
+				let response = match (&cache, coord) {
+					_ if url.str == "/tiles.json" => {
+						tile_source.get_tile_json(&options.get_base_url(&headers))
+					}
+					(Some(cache), Some(coord)) => {
+						cache
+							.get_tile(&tile_source, &coord, format, &target_compressions)
+							.await
+					}
+					(None, Some(coord)) => tile_source.get_tile_as(&coord, format).await,
+					_ => tile_source.get_data(&url, &target_compressions).await,
+				};
 
 				if let Some(response) = response {
-					log::warn!("{}: {path} found", tile_source.prefix);
-					ok_data(response, target_compressions)
+					log::debug!("{}: {path} found", tile_source.prefix);
+					let mut response = ok_data(response, target_compressions, &headers, &options);
+					if url.str == "/tiles.json" {
+						options.add_base_url_headers(response.headers_mut());
+					}
+					if tile_source.negotiates_format(&url) {
+						response
+							.headers_mut()
+							.append(VARY, HeaderValue::from_static("accept"));
+					}
+					response
 				} else {
-					log::warn!("{}: {path} not found", tile_source.prefix);
+					log::debug!("{}: {path} not found", tile_source.prefix);
 					ok_not_found()
 				}
 			}
@@ -174,16 +441,27 @@ impl TileServer {
 	}
 
 	fn add_static_sources_to_app(&self, app: Router) -> Router {
+		let sources: Vec<(StaticSource, ResponseOptions)> = self
+			.static_sources
+			.iter()
+			.map(|source| {
+				let options = self.options.with_source_options(&source.options);
+				(source.clone(), options)
+			})
+			.collect();
+
 		let static_app = Router::new()
-			.fallback(get(serve_static))
-			.with_state((self.static_sources.clone(), self.use_best_compression));
+			.fallback(get(serve_static).options(serve_preflight))
+			.with_state((sources, self.options.clone()));
+
+		return app.merge(self.track_requests(static_app, "static"));
 
-		return app.merge(static_app);
+		type StaticState = (Vec<(StaticSource, ResponseOptions)>, ResponseOptions);
 
 		async fn serve_static(
 			uri: Uri,
 			headers: HeaderMap,
-			State((sources, best_compression)): State<(Vec<StaticSource>, bool)>,
+			State((sources, _)): State<StaticState>,
 		) -> Response<Body> {
 			let mut url = Url::new(uri.path());
 
@@ -191,64 +469,273 @@ impl TileServer {
 				url.push("index.html");
 			}
 
-			let mut compressions = get_encoding(headers);
-			compressions.set_best_compression(best_compression);
+			let range = RangeRequest::from_headers(&headers);
 
-			for source in sources.iter() {
-				if let Some(result) = source.get_data(&url, &compressions) {
-					return ok_data(result, compressions);
+			for (source, options) in sources.iter() {
+				let mut compressions = get_encoding(&headers);
+				compressions.set_best_compression(options.best_compression);
+
+				let mut result = range.and_then(|range| source.get_range(&url, &range));
+
+				// If-Range does not match, so send the whole file
+				if let Some(r) = &result {
+					if !is_range_valid(&headers, r.etag.as_deref(), r.last_modified) {
+						result = None;
+					}
+				}
+
+				if let Some(result) = result.or_else(|| source.get_data(&url, &compressions)) {
+					let mut response = ok_data(result, compressions, &headers, options);
+					response
+						.headers_mut()
+						.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+					return response;
 				}
 			}
 
 			ok_not_found()
 		}
+
+		// answers with the CORS settings of the first source that could serve the url
+		async fn serve_preflight(
+			uri: Uri,
+			headers: HeaderMap,
+			State((sources, options)): State<StaticState>,
+		) -> Response<Body> {
+			let url = Url::new(uri.path());
+			let options = sources
+				.iter()
+				.find(|(source, _)| source.matches_prefix(&url))
+				.map_or(&options, |(_, options)| options);
+			ok_preflight(&headers, options)
+		}
 	}
 
 	async fn add_api_to_app(&self, app: Router) -> Result<Router> {
 		let mut api_app = Router::new();
+		let options = self.options.clone();
 		api_app = api_app.route(
 			"/api/status",
-			get(|| async { ok_json("{\"status\":\"ready\"}") }),
+			get(
+				|headers: HeaderMap| async move { ok_json("{\"status\":\"ready\"}", &headers, &options) },
+			)
+			.merge(preflight(&self.options)),
 		);
 
 		let mut objects: Vec<String> = Vec::new();
 		for tile_source in self.tile_sources.iter() {
-			let id = tile_source
-				.prefix
-				.as_vec()
-				.last()
-				.expect("should end in id")
-				.to_owned();
+			let id = get_source_id(tile_source);
 			let object = format!(
 				"{{\"url\":\"{}\",\"id\":\"{}\",\"container\":{}}}",
 				tile_source.prefix, id, tile_source.json_info
 			);
 			objects.push(object.clone());
+			let options = self.options.clone();
 			api_app = api_app.route(
 				&format!("/api/source/{id}"),
-				get(|| async move { ok_json(&object) }),
+				get(|headers: HeaderMap| async move { ok_json(&object, &headers, &options) })
+					.merge(preflight(&self.options)),
 			);
 		}
 		let tile_sources_json: String = "[".to_owned() + &objects.join(",") + "]";
 
+		if let Some(cache) = self.cache.clone() {
+			let options = self.options.clone();
+			api_app = api_app.route(
+				"/api/cache",
+				get(|headers: HeaderMap| async move {
+					let json = cache.get_stats().as_json().stringify();
+					ok_json(&json, &headers, &options)
+				})
+				.merge(preflight(&self.options)),
+			);
+		}
+
+		let options = self.options.clone();
 		api_app = api_app.route(
 			"/api/sources",
-			get(|| async move { ok_json(&tile_sources_json) }),
+			get(|headers: HeaderMap| async move { ok_json(&tile_sources_json, &headers, &options) })
+				.merge(preflight(&self.options)),
+		);
+
+		Ok(app.merge(self.track_requests(api_app, "api")))
+	}
+
+	fn add_admin_to_app(&self, app: Router) -> Router {
+		let Some((token, sender)) = self.admin.clone() else {
+			return app;
+		};
+
+		let admin_app = Router::new().route(
+			"/api/admin/reload",
+			post(|headers: HeaderMap| async move {
+				if !is_authorized(&headers, &token) {
+					return Response::builder()
+						.status(401)
+						.header(WWW_AUTHENTICATE, "Bearer")
+						.body(Body::from("Unauthorized"))
+						.expect("should have build a body");
+				}
+
+				let (done, result) = oneshot::channel();
+				let request = ReloadRequest {
+					trigger: String::from("admin API"),
+					done: Some(done),
+				};
+				let result = match sender.send(request).await {
+					Ok(()) => result
+						.await
+						.unwrap_or_else(|_| bail!("reload was cancelled")),
+					Err(_) => Err(anyhow::anyhow!("server does not accept reload requests")),
+				};
+
+				let (status, message) = match result {
+					Ok(()) => (200, String::from("{\"status\":\"reloaded\"}")),
+					Err(error) => (
+						500,
+						JsonValue::from(vec![("error", format!("{error:#}"))]).stringify(),
+					),
+				};
+				Response::builder()
+					.status(status)
+					.header(CONTENT_TYPE, "application/json")
+					.body(Body::from(message))
+					.expect("should have build a body")
+			}),
 		);
 
-		Ok(app.merge(api_app))
+		app.merge(self.track_requests(admin_app, "api"))
+	}
+
+	// records the requests of a router in the metrics and the access log, if they are enabled
+	fn track_requests(&self, router: Router, source: &str) -> Router {
+		if self.metrics.is_none() && self.access_log.is_none() {
+			return router;
+		}
+
+		let tracker = RequestTracker {
+			source: source.to_owned(),
+			metrics: self.metrics.clone(),
+			access_log: self.access_log.clone(),
+		};
+		return router.layer(middleware::from_fn_with_state(tracker, track));
+
+		async fn track(
+			State(tracker): State<RequestTracker>,
+			request: Request,
+			next: Next,
+		) -> Response<Body> {
+			let time = SystemTime::now();
+			let start = Instant::now();
+
+			let remote = request
+				.extensions()
+				.get::<ConnectInfo<SocketAddr>>()
+				.map(|info| info.0);
+			let method = request.method().clone();
+			let path = request.uri().to_string();
+			let version = format!("{:?}", request.version());
+			let header = |headers: &HeaderMap, key| {
+				headers
+					.get(key)
+					.and_then(|value| value.to_str().ok())
+					.map(str::to_owned)
+			};
+			let referer = header(request.headers(), REFERER);
+			let user_agent = header(request.headers(), USER_AGENT);
+
+			let response = next.run(request).await;
+
+			let duration = start.elapsed();
+			let status = response.status().as_u16();
+			let encoding = response
+				.headers()
+				.get(CONTENT_ENCODING)
+				.and_then(|value| value.to_str().ok());
+			// streamed bodies have no exact size, but a Content-Length
+			let bytes = response.body().size_hint().exact().unwrap_or_else(|| {
+				response
+					.headers()
+					.get(CONTENT_LENGTH)
+					.and_then(|value| value.to_str().ok()?.parse().ok())
+					.unwrap_or(0)
+			});
+
+			if let Some(metrics) = &tracker.metrics {
+				metrics.add_request(
+					&tracker.source,
+					status,
+					encoding.unwrap_or("none"),
+					bytes,
+					duration.as_secs_f64(),
+				);
+			}
+
+			if let Some(access_log) = &tracker.access_log {
+				access_log.write(&AccessLogEntry {
+					time,
+					remote,
+					method: method.as_str(),
+					path: &path,
+					version: &version,
+					status,
+					bytes,
+					encoding,
+					duration,
+					source: &tracker.source,
+					referer: referer.as_deref(),
+					user_agent: user_agent.as_deref(),
+				});
+			}
+
+			response
+		}
 	}
 
 	pub async fn get_url_mapping(&self) -> Vec<(String, String)> {
 		let mut result = Vec::new();
 		for tile_source in self.tile_sources.iter() {
-			let id = tile_source.get_id().await;
+			let id = tile_source.get_id();
 			result.push((tile_source.prefix.as_string(), id.to_owned()))
 		}
 		result
 	}
 }
 
+// checks the bearer token in constant time, so it cannot be guessed byte by byte
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+	let Some(given) = headers
+		.get(AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+	else {
+		return false;
+	};
+	given.len() == token.len()
+		&& given
+			.bytes()
+			.zip(token.bytes())
+			.fold(0, |diff, (a, b)| diff | (a ^ b))
+			== 0
+}
+
+// the last part of the url prefix, e.g. "osm" for "/tiles/osm/"
+fn get_source_id(tile_source: &TileSource) -> String {
+	tile_source
+		.prefix
+		.as_vec()
+		.last()
+		.expect("should end in id")
+		.to_owned()
+}
+
+#[derive(Clone)]
+struct RequestTracker {
+	source: String,
+	metrics: Option<Metrics>,
+	access_log: Option<AccessLog>,
+}
+
 fn ok_not_found() -> Response<Body> {
 	Response::builder()
 		.status(404)
@@ -256,26 +743,87 @@ fn ok_not_found() -> Response<Body> {
 		.expect("should have build a body")
 }
 
-fn ok_data(result: SourceResponse, target_compressions: TargetCompression) -> Response<Body> {
-	let is_incompressible = matches!(
-		result.mime.as_str(),
-		"image/png" | "image/jpeg" | "image/webp" | "image/avif"
-	);
+// handles CORS preflight requests
+fn preflight<S: Clone + Send + Sync + 'static>(options: &ResponseOptions) -> MethodRouter<S> {
+	let options = options.clone();
+	axum::routing::options(|headers: HeaderMap| async move { ok_preflight(&headers, &options) })
+}
+
+fn ok_preflight(request_headers: &HeaderMap, options: &ResponseOptions) -> Response<Body> {
+	let mut response = Response::builder().status(204);
+	if let Some(headers) = response.headers_mut() {
+		options.add_preflight_headers(request_headers, headers);
+		options.add_extra_headers(headers);
+	}
+	response
+		.body(Body::empty())
+		.expect("should have build a body")
+}
+
+fn ok_data(
+	result: SourceResponse,
+	target_compressions: TargetCompression,
+	request_headers: &HeaderMap,
+	options: &ResponseOptions,
+) -> Response<Body> {
+	// files and parts of a file are always sent as they are stored
+	let compression =
+		if is_incompressible(&result.mime) || result.content_range.is_some() || result.file.is_some()
+		{
+			result.compression
+		} else {
+			get_optimal_compression(&result.compression, &target_compressions)
+				.expect("should have found a compression")
+		};
+
+	// the ETag does not depend on the recompressed body, so a 304 is sent without recompressing
+	let etag = result.get_etag_for(&compression);
 
 	let mut response = Response::builder()
-		.status(200)
-		.header(CONTENT_TYPE, result.mime)
-		.header(CACHE_CONTROL, "public, max-age=2419200, no-transform")
+		.header(CACHE_CONTROL, options.get_cache_control())
 		.header(VARY, "accept-encoding")
-		.header(ACCESS_CONTROL_ALLOW_ORIGIN, "*");
+		.header(ETAG, &etag);
+
+	if let Some(headers) = response.headers_mut() {
+		options.add_cors_headers(request_headers, headers);
+		options.add_extra_headers(headers);
+	}
 
-	let (blob, compression) = if is_incompressible {
-		(result.blob, result.compression)
-	} else {
-		optimize_compression(result.blob, &result.compression, target_compressions)
-			.expect("should have optimized compression")
+	if let Some(last_modified) = result.last_modified {
+		response = response.header(LAST_MODIFIED, httpdate::fmt_http_date(last_modified));
+	}
+
+	if is_not_modified(request_headers, &etag, result.last_modified) {
+		return response
+			.status(304)
+			.body(Body::empty())
+			.expect("should have build a body");
+	}
+
+	response = match result.content_range {
+		None => response.status(200),
+		Some(ContentRange {
+			range: Some(range),
+			size,
+		}) => response.status(206).header(
+			CONTENT_RANGE,
+			format!(
+				"bytes {}-{}/{size}",
+				range.offset,
+				range.offset + range.length - 1
+			),
+		),
+		Some(ContentRange { range: None, size }) => {
+			return response
+				.status(416)
+				.header(CONTENT_RANGE, format!("bytes */{size}"))
+				.body(Body::empty())
+				.expect("should have build a body");
+		}
 	};
 
+	response = response.header(CONTENT_TYPE, result.mime);
+
 	use TileCompression::*;
 	match compression {
 		Uncompressed => {}
@@ -283,23 +831,84 @@ fn ok_data(result: SourceResponse, target_compressions: TargetCompression) -> Re
 		Brotli => response = response.header(CONTENT_ENCODING, "br"),
 	}
 
-	response
-		.body(Body::from(blob.into_vec()))
-		.expect("should have build a body")
+	let body = match result.file {
+		Some(file) => {
+			response = response.header(CONTENT_LENGTH, file.range.length);
+			get_file_body(file)
+		}
+		None => {
+			let blob = recompress(result.blob, &result.compression, &compression)
+				.expect("should have recompressed the blob");
+			Body::from(blob.into_vec())
+		}
+	};
+
+	response.body(body).expect("should have build a body")
+}
+
+// reads the file in chunks while sending, so large files are never loaded into memory
+fn get_file_body(part: FilePart) -> Body {
+	const CHUNK_SIZE: u64 = 64 * 1024;
+
+	// the file is opened by the first read and moved between the blocking reads
+	let state = (part, None::<File>);
+	Body::from_stream(futures::stream::try_unfold(
+		state,
+		|(part, file)| async move {
+			let remaining = part.range.length;
+			if remaining == 0 {
+				return Ok(None);
+			}
+			let length = remaining.min(CHUNK_SIZE);
+			let path = part.path.clone();
+			let offset = part.range.offset;
+			let (file, chunk) = tokio::task::spawn_blocking(move || -> std::io::Result<_> {
+				let mut file = match file {
+					Some(file) => file,
+					None => {
+						let mut file = File::open(path)?;
+						file.seek(SeekFrom::Start(offset))?;
+						file
+					}
+				};
+				let mut chunk = vec![0; length as usize];
+				file.read_exact(&mut chunk)?;
+				Ok((file, chunk))
+			})
+			.await
+			.map_err(std::io::Error::other)??;
+
+			let part = FilePart {
+				path: part.path,
+				range: ByteRange::new(offset + length, remaining - length),
+			};
+			Ok::<_, std::io::Error>(Some((chunk, (part, Some(file)))))
+		},
+	))
 }
 
-fn ok_json(message: &str) -> Response<Body> {
+fn ok_json(
+	message: &str,
+	request_headers: &HeaderMap,
+	options: &ResponseOptions,
+) -> Response<Body> {
 	ok_data(
 		SourceResponse {
 			blob: Blob::from(message),
 			compression: TileCompression::Uncompressed,
 			mime: String::from("application/json"),
+			last_modified: None,
+			etag: None,
+			content_range: None,
+			file: None,
 		},
 		TargetCompression::from_none(),
+		request_headers,
+		options,
 	)
 }
 
-fn get_encoding(headers: HeaderMap) -> TargetCompression {
+fn get_encoding(headers: &HeaderMap) -> TargetCompression {
 	let mut encoding_set: TargetCompression = TargetCompression::from_none();
 	let encoding_option = headers.get(ACCEPT_ENCODING);
 	if let Some(encoding) = encoding_option {
@@ -320,7 +929,8 @@ mod tests {
 	use super::*;
 	use crate::{
 		container::{MockTilesReader, MockTilesReaderProfile},
-		types::TileCompression::*,
+		tools::server::AccessLogFormat,
+		types::{TileBBoxPyramid, TileCompression::*, TileFormat, TilesReaderParameters},
 	};
 	use axum::http::{header::ACCEPT_ENCODING, HeaderMap};
 	use enumset::{enum_set, EnumSet};
@@ -335,7 +945,7 @@ mod tests {
 				map.insert(ACCEPT_ENCODING, encoding.parse().unwrap());
 			}
 			let comp0 = TargetCompression::from_set(comp0);
-			let comp = get_encoding(map);
+			let comp = get_encoding(&map);
 			assert_eq!(comp, comp0);
 		};
 
@@ -401,7 +1011,10 @@ mod tests {
 		assert_eq!(get("api/source/cheese").await, JSON);
 		assert_eq!(get("tiles/cheese/brum.json").await, "Not Found");
 		assert_eq!(get("tiles/cheese/meta.json").await, "dummy meta data");
-		assert_eq!(get("tiles/cheese/tiles.json").await, "dummy meta data");
+		assert_eq!(
+			get("tiles/cheese/tiles.json").await,
+			"{\"bounds\":[-180,-85.05112877980659,180,85.05112877980659],\"center\":[0,0,2],\"format\":\"pbf\",\"maxzoom\":4,\"minzoom\":0,\"scheme\":\"xyz\",\"tilejson\":\"3.0.0\",\"tiles\":[\"http://127.0.0.1:50001/tiles/cheese/{z}/{x}/{y}.pbf\"],\"vector_layers\":[]}"
+		);
 		assert!(get("tiles/cheese/0/0/0.png")
 			.await
 			.starts_with("\u{1a}4\n\u{5}ocean"));
@@ -410,6 +1023,738 @@ mod tests {
 		server.stop().await;
 	}
 
+	#[tokio::test]
+	async fn conditional_requests() {
+		async fn get(path: &str, header: Option<(&str, &str)>) -> reqwest::Response {
+			let mut request = reqwest::Client::new().get(format!("http://{IP}:50006/{path}"));
+			if let Some((key, value)) = header {
+				request = request.header(key, value);
+			}
+			request
+				.send()
+				.await
+				.expect("should have made a get request")
+		}
+		fn header(response: &reqwest::Response, key: &str) -> String {
+			response.headers()[key].to_str().unwrap().to_owned()
+		}
+
+		let mut server = TileServer::new(IP, 50006, true, false);
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/cheese"), reader)
+			.unwrap();
+
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(temp_dir.path().join("index.html"), b"Hello, world!").unwrap();
+		server
+			.add_static_source(temp_dir.path(), Url::new(""))
+			.unwrap();
+
+		server.start().await.unwrap();
+
+		// tiles
+		let response = get("tiles/cheese/0/0/0.pbf", None).await;
+		assert_eq!(response.status(), 200);
+		let etag = header(&response, "etag");
+		assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+		let response = get("tiles/cheese/0/0/0.pbf", Some(("if-none-match", &etag))).await;
+		assert_eq!(response.status(), 304);
+		assert_eq!(header(&response, "etag"), etag);
+		assert_eq!(response.bytes().await.unwrap().len(), 0);
+
+		let weak = format!("\"nope\", W/{etag}");
+		let response = get("tiles/cheese/0/0/0.pbf", Some(("if-none-match", &weak))).await;
+		assert_eq!(response.status(), 304);
+
+		let response = get(
+			"tiles/cheese/0/0/0.pbf",
+			Some(("if-none-match", "\"nope\"")),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+
+		// static files
+		let response = get("index.html", None).await;
+		assert_eq!(response.status(), 200);
+		let etag = header(&response, "etag");
+		let last_modified = header(&response, "last-modified");
+
+		let response = get("index.html", Some(("if-none-match", &etag))).await;
+		assert_eq!(response.status(), 304);
+
+		let response = get("index.html", Some(("if-modified-since", &last_modified))).await;
+		assert_eq!(response.status(), 304);
+		assert_eq!(header(&response, "last-modified"), last_modified);
+
+		let response = get(
+			"index.html",
+			Some(("if-modified-since", "Thu, 01 Jan 1970 00:00:00 GMT")),
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.text().await.unwrap(), "Hello, world!");
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn range_requests() {
+		async fn get(path: &str, headers: &[(&str, &str)]) -> reqwest::Response {
+			let mut request = reqwest::Client::new().get(format!("http://{IP}:50007/{path}"));
+			for (key, value) in headers {
+				request = request.header(*key, *value);
+			}
+			request
+				.send()
+				.await
+				.expect("should have made a get request")
+		}
+		fn header(response: &reqwest::Response, key: &str) -> String {
+			response.headers()[key].to_str().unwrap().to_owned()
+		}
+
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		let path = temp_dir.path().join("data.txt");
+		std::fs::write(&path, b"0123456789").unwrap();
+
+		let mut server = TileServer::new(IP, 50007, true, false);
+		server
+			.add_static_source(temp_dir.path(), Url::new("static"))
+			.unwrap();
+		server
+			.add_static_file(&path, Url::new("files/data.pmtiles"))
+			.unwrap();
+		// bigger than one chunk of a streamed file
+		let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+		let big_path = temp_dir.path().join("big.versatiles");
+		std::fs::write(&big_path, &content).unwrap();
+		server
+			.add_static_file(&big_path, Url::new("files/big.versatiles"))
+			.unwrap();
+		server.start().await.unwrap();
+
+		let response = get("static/data.txt", &[]).await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(header(&response, "accept-ranges"), "bytes");
+		let etag = header(&response, "etag");
+
+		let response = get("static/data.txt", &[("range", "bytes=2-4")]).await;
+		assert_eq!(response.status(), 206);
+		assert_eq!(header(&response, "content-range"), "bytes 2-4/10");
+		assert_eq!(header(&response, "etag"), etag);
+		assert_eq!(response.text().await.unwrap(), "234");
+
+		let response = get(
+			"static/data.txt",
+			&[("range", "bytes=-3"), ("accept-encoding", "gzip, br")],
+		)
+		.await;
+		assert_eq!(response.status(), 206);
+		assert!(response.headers().get("content-encoding").is_none());
+		assert_eq!(response.text().await.unwrap(), "789");
+
+		let response = get("static/data.txt", &[("range", "bytes=10-")]).await;
+		assert_eq!(response.status(), 416);
+		assert_eq!(header(&response, "content-range"), "bytes */10");
+
+		let response = get(
+			"static/data.txt",
+			&[("range", "bytes=2-4"), ("if-range", "\"outdated\"")],
+		)
+		.await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.text().await.unwrap(), "0123456789");
+
+		let response = get(
+			"static/data.txt",
+			&[("range", "bytes=2-4"), ("if-range", &etag)],
+		)
+		.await;
+		assert_eq!(response.status(), 206);
+
+		let response = get("files/data.pmtiles", &[("range", "bytes=0-0")]).await;
+		assert_eq!(response.status(), 206);
+		assert_eq!(header(&response, "content-range"), "bytes 0-0/10");
+		assert_eq!(response.text().await.unwrap(), "0");
+
+		assert_eq!(get("files/data.txt", &[]).await.status(), 404);
+
+		// container files are streamed as they are, never recompressed
+		let response = get("files/big.versatiles", &[("accept-encoding", "gzip, br")]).await;
+		assert_eq!(response.status(), 200);
+		assert!(response.headers().get("content-encoding").is_none());
+		assert_eq!(header(&response, "content-length"), "200000");
+		assert_eq!(response.bytes().await.unwrap().to_vec(), content);
+
+		let response = get("files/big.versatiles", &[("range", "bytes=65530-131080")]).await;
+		assert_eq!(response.status(), 206);
+		assert_eq!(
+			header(&response, "content-range"),
+			"bytes 65530-131080/200000"
+		);
+		assert_eq!(
+			response.bytes().await.unwrap().to_vec(),
+			content[65530..=131080]
+		);
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn cors_and_headers() {
+		async fn request(method: &str, path: &str, origin: &str) -> reqwest::Response {
+			reqwest::Client::new()
+				.request(method.parse().unwrap(), format!("http://{IP}:50008/{path}"))
+				.header("origin", origin)
+				.header("access-control-request-headers", "range")
+				.send()
+				.await
+				.expect("should have made a request")
+		}
+		fn header(response: &reqwest::Response, key: &str) -> Option<String> {
+			response
+				.headers()
+				.get(key)
+				.map(|v| v.to_str().unwrap().to_owned())
+		}
+		fn vary(response: &reqwest::Response) -> Vec<String> {
+			let values = response.headers().get_all("vary").iter();
+			values.map(|v| v.to_str().unwrap().to_owned()).collect()
+		}
+
+		let mut server = TileServer::new(IP, 50008, true, true);
+		server.set_max_age(60);
+		server.add_header(
+			HeaderName::from_static("x-served-by"),
+			HeaderValue::from_static("versatiles"),
+		);
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+			.unwrap()
+			.boxed();
+		let options = SourceOptions {
+			allowed_origins: Some(vec![String::from("https://example.org")]),
+			max_age: Some(31536000),
+			extra_headers: vec![(
+				HeaderName::from_static("x-release"),
+				HeaderValue::from_static("2024"),
+			)],
+		};
+		server
+			.add_tile_source_with_options(
+				Url::new("tiles/cheese"),
+				reader,
+				options,
+				UrlScheme::default(),
+			)
+			.unwrap();
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)
+			.unwrap()
+			.boxed();
+		let options = SourceOptions {
+			allowed_origins: Some(vec![String::from("https://example.org")]),
+			..Default::default()
+		};
+		server
+			.add_tile_source_with_options(Url::new("tiles/sat"), reader, options, UrlScheme::default())
+			.unwrap();
+
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		std::fs::write(temp_dir.path().join("style.json"), b"{}").unwrap();
+		server
+			.add_static_source(temp_dir.path(), Url::new(""))
+			.unwrap();
+
+		server.start().await.unwrap();
+
+		// tiles with their own settings
+		let response = request("GET", "tiles/cheese/0/0/0.pbf", "https://example.org").await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			header(&response, "access-control-allow-origin").as_deref(),
+			Some("https://example.org")
+		);
+		assert_eq!(
+			header(&response, "cache-control").as_deref(),
+			Some("public, max-age=31536000, no-transform")
+		);
+		assert_eq!(
+			header(&response, "x-served-by").as_deref(),
+			Some("versatiles")
+		);
+		assert_eq!(header(&response, "x-release").as_deref(), Some("2024"));
+		assert_eq!(vary(&response), ["accept-encoding", "origin"]);
+
+		// the format of an extensionless raster url is negotiated, without losing "Vary: origin"
+		let response = request("GET", "tiles/sat/0/0/0", "https://example.org").await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			header(&response, "access-control-allow-origin").as_deref(),
+			Some("https://example.org")
+		);
+		assert_eq!(vary(&response), ["accept-encoding", "origin", "accept"]);
+
+		let response = request("GET", "tiles/cheese/0/0/0.pbf", "https://evil.example.com").await;
+		assert_eq!(header(&response, "access-control-allow-origin"), None);
+
+		let response = request("OPTIONS", "tiles/cheese/0/0/0.pbf", "https://example.org").await;
+		assert_eq!(response.status(), 204);
+		assert_eq!(
+			header(&response, "access-control-allow-origin").as_deref(),
+			Some("https://example.org")
+		);
+		assert_eq!(
+			header(&response, "access-control-allow-methods").as_deref(),
+			Some("GET, HEAD, OPTIONS")
+		);
+		assert_eq!(
+			header(&response, "access-control-allow-headers").as_deref(),
+			Some("range")
+		);
+
+		let response = request(
+			"OPTIONS",
+			"tiles/cheese/0/0/0.pbf",
+			"https://evil.example.com",
+		)
+		.await;
+		assert_eq!(response.status(), 204);
+		assert_eq!(header(&response, "access-control-allow-origin"), None);
+
+		// static files and api with the global settings
+		let response = request("GET", "style.json", "https://evil.example.com").await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(
+			header(&response, "access-control-allow-origin").as_deref(),
+			Some("*")
+		);
+		assert_eq!(
+			header(&response, "cache-control").as_deref(),
+			Some("public, max-age=60, no-transform")
+		);
+		assert_eq!(header(&response, "x-release"), None);
+
+		let response = request("OPTIONS", "style.json", "https://evil.example.com").await;
+		assert_eq!(response.status(), 204);
+		assert_eq!(
+			header(&response, "access-control-allow-origin").as_deref(),
+			Some("*")
+		);
+
+		let response = request("OPTIONS", "api/sources", "https://evil.example.com").await;
+		assert_eq!(response.status(), 204);
+		assert_eq!(
+			header(&response, "access-control-allow-methods").as_deref(),
+			Some("GET, HEAD, OPTIONS")
+		);
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn metrics() {
+		async fn get(path: &str) -> String {
+			reqwest::Client::new()
+				.get(format!("http://{IP}:50009/{path}"))
+				.header("accept-encoding", "br")
+				.send()
+				.await
+				.expect("should have made a get request")
+				.text()
+				.await
+				.expect("should have returned text")
+		}
+
+		let mut server = TileServer::new(IP, 50009, true, true);
+		server.enable_metrics();
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/cheese"), reader)
+			.unwrap();
+
+		server.start().await.unwrap();
+
+		get("tiles/cheese/0/0/0.pbf").await;
+		get("tiles/cheese/0/0/0.pbf").await;
+		get("tiles/cheese/x/0/0.pbf").await;
+		get("api/status").await;
+		get("unknown.html").await;
+
+		let metrics = get("metrics").await;
+		let lines: Vec<&str> = metrics.lines().collect();
+		for line in [
+			"versatiles_requests_total{source=\"cheese\",status=\"200\",compression=\"br\"} 2",
+			"versatiles_requests_total{source=\"cheese\",status=\"404\",compression=\"none\"} 1",
+			"versatiles_requests_total{source=\"api\",status=\"200\",compression=\"none\"} 1",
+			"versatiles_requests_total{source=\"static\",status=\"404\",compression=\"none\"} 1",
+			"versatiles_response_bytes_total{source=\"static\"} 9",
+			"versatiles_request_duration_seconds_count{source=\"cheese\"} 3",
+		] {
+			assert!(lines.contains(&line), "missing {line:?} in:\n{metrics}");
+		}
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn access_log() {
+		let temp_dir = assert_fs::TempDir::new().unwrap();
+		let path = temp_dir.path().join("access.log");
+
+		let mut server = TileServer::new(IP, 50010, true, true);
+		server.set_access_log(AccessLog::new(AccessLogFormat::Json, Some(&path)).unwrap());
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/cheese"), reader)
+			.unwrap();
+
+		server.start().await.unwrap();
+
+		reqwest::Client::new()
+			.get(format!("http://{IP}:50010/tiles/cheese/0/0/0.pbf?key=1"))
+			.header("accept-encoding", "br")
+			.header("user-agent", "test")
+			.send()
+			.await
+			.expect("should have made a get request");
+
+		server.stop().await;
+
+		let log = std::fs::read_to_string(&path).unwrap();
+		let lines: Vec<&str> = log.lines().collect();
+		assert_eq!(lines.len(), 1);
+		for part in [
+			"\"encoding\":\"br\"",
+			"\"method\":\"GET\"",
+			"\"path\":\"/tiles/cheese/0/0/0.pbf?key=1\"",
+			"\"remote\":\"127.0.0.1\"",
+			"\"source\":\"cheese\"",
+			"\"status\":200",
+			"\"user_agent\":\"test\"",
+			"\"version\":\"HTTP/1.1\"",
+		] {
+			assert!(lines[0].contains(part), "missing {part} in {}", lines[0]);
+		}
+	}
+
+	#[tokio::test]
+	async fn cache() {
+		async fn get(path: &str) -> reqwest::Response {
+			reqwest::Client::new()
+				.get(format!("http://{IP}:50011/{path}"))
+				.header("accept-encoding", "gzip, br")
+				.send()
+				.await
+				.expect("should have made a get request")
+		}
+
+		let mut server = TileServer::new(IP, 50011, true, true);
+		server.set_cache_size(1_000_000);
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/cheese"), reader)
+			.unwrap();
+
+		server.start().await.unwrap();
+
+		let response1 = get("tiles/cheese/0/0/0.pbf").await;
+		let response2 = get("tiles/cheese/0/0/0.pbf").await;
+		assert_eq!(response1.headers()["content-encoding"], "br");
+		assert_eq!(response1.headers()["etag"], response2.headers()["etag"]);
+		assert_eq!(
+			response1.bytes().await.unwrap(),
+			response2.bytes().await.unwrap()
+		);
+
+		let stats = get("api/cache").await.text().await.unwrap();
+		assert!(
+			stats.starts_with("{\"entries\":1,\"hits\":1,\"maximum_size\":1000000,\"misses\":1,"),
+			"{stats}"
+		);
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn raster_formats() {
+		async fn get(path: &str, accept: &str) -> reqwest::Response {
+			reqwest::Client::new()
+				.get(format!("http://{IP}:50013/{path}"))
+				.header("accept", accept)
+				.send()
+				.await
+				.expect("should have made a get request")
+		}
+		fn header(response: &reqwest::Response, key: &str) -> String {
+			let values = response.headers().get_all(key).iter();
+			let values: Vec<&str> = values.map(|v| v.to_str().unwrap()).collect();
+			values.join(", ")
+		}
+
+		let mut server = TileServer::new(IP, 50013, true, true);
+		server.set_cache_size(1_000_000);
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/sat"), reader)
+			.unwrap();
+		server.start().await.unwrap();
+
+		let response = get("tiles/sat/0/0/0.webp", "*/*").await;
+		assert_eq!(header(&response, "content-type"), "image/webp");
+		assert_eq!(header(&response, "vary"), "accept-encoding");
+
+		let response = get("tiles/sat/0/0/0.png", "image/webp").await;
+		assert_eq!(header(&response, "content-type"), "image/png");
+
+		// the same url in different formats, also from the cache
+		for _ in 0..2 {
+			let response = get("tiles/sat/0/0/0", "image/webp,*/*").await;
+			assert_eq!(header(&response, "content-type"), "image/webp");
+			assert_eq!(header(&response, "vary"), "accept-encoding, accept");
+
+			let response = get("tiles/sat/0/0/0", "image/jpeg").await;
+			assert_eq!(header(&response, "content-type"), "image/jpeg");
+		}
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn wmts() {
+		async fn get(query: &str) -> reqwest::Response {
+			reqwest::get(format!("http://{IP}:50014/tiles/sat/wmts?{query}"))
+				.await
+				.expect("should have made a get request")
+		}
+
+		let mut server = TileServer::new(IP, 50014, true, true);
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)
+			.unwrap()
+			.boxed();
+		server
+			.add_tile_source(Url::new("tiles/sat"), reader)
+			.unwrap();
+		server.start().await.unwrap();
+
+		let response = get("SERVICE=WMTS&REQUEST=GetCapabilities").await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "application/xml");
+		let vary = response.headers().get_all("vary").iter();
+		assert_eq!(
+			vary.map(|v| v.to_str().unwrap()).collect::<Vec<_>>(),
+			["accept-encoding", "host"]
+		);
+		let xml = response.text().await.unwrap();
+		assert!(xml.contains(&format!(
+			"<ows:Get xlink:href=\"http://{IP}:50014/tiles/sat/wmts?\">"
+		)));
+
+		// without a trusted proxy, forwarded headers can not change the URLs
+		let xml = reqwest::Client::new()
+			.get(format!(
+				"http://{IP}:50014/tiles/sat/wmts?SERVICE=WMTS&REQUEST=GetCapabilities"
+			))
+			.header("x-forwarded-host", "evil.example.com")
+			.header("x-forwarded-proto", "https")
+			.send()
+			.await
+			.unwrap()
+			.text()
+			.await
+			.unwrap();
+		assert!(xml.contains(&format!(
+			"<ows:Get xlink:href=\"http://{IP}:50014/tiles/sat/wmts?\">"
+		)));
+
+		let response = get("SERVICE=WMTS&REQUEST=GetTile&LAYER=sat&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible&TILEMATRIX=2&TILEROW=1&TILECOL=3&FORMAT=image/jpeg").await;
+		assert_eq!(response.status(), 200);
+		assert_eq!(response.headers()["content-type"], "image/jpeg");
+
+		let response = get("SERVICE=WMTS&REQUEST=GetTile&LAYER=sat").await;
+		assert_eq!(response.status(), 400);
+		assert!(response
+			.text()
+			.await
+			.unwrap()
+			.contains("exceptionCode=\"MissingParameterValue\" locator=\"tilematrixset\""));
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn url_schemes() {
+		async fn get(path: &str) -> u16 {
+			reqwest::get(format!("http://{IP}:50015/tiles/{path}"))
+				.await
+				.expect("should have made a get request")
+				.status()
+				.as_u16()
+		}
+
+		let mut server = TileServer::new(IP, 50015, true, true);
+		for (id, scheme) in [("tms", UrlScheme::Tms), ("bing", UrlScheme::Quadkey)] {
+			let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)
+				.unwrap()
+				.boxed();
+			server
+				.add_tile_source_with_options(
+					Url::new(&format!("tiles/{id}")),
+					reader,
+					SourceOptions::default(),
+					scheme,
+				)
+				.unwrap();
+		}
+		server.start().await.unwrap();
+
+		assert_eq!(get("tms/3/4/5.png").await, 200);
+		assert_eq!(get("tms/3/4/8.png").await, 404);
+		assert_eq!(get("bing/q/120.png").await, 200);
+		assert_eq!(get("bing/3/4/2.png").await, 404);
+		assert_eq!(get("bing/meta.json").await, 200);
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn replace_sources_with_cache() {
+		async fn get() -> Vec<u8> {
+			reqwest::get(format!("http://{IP}:50016/tiles/sat/0/0/0.png"))
+				.await
+				.expect("should have made a get request")
+				.bytes()
+				.await
+				.unwrap()
+				.to_vec()
+		}
+		fn new_server(format: TileFormat) -> TileServer {
+			let mut server = TileServer::new(IP, 50016, true, true);
+			let parameters =
+				TilesReaderParameters::new(format, Uncompressed, TileBBoxPyramid::new_full(4));
+			let reader = MockTilesReader::new_mock(parameters).unwrap().boxed();
+			server
+				.add_tile_source(Url::new("tiles/sat"), reader)
+				.unwrap();
+			server
+		}
+
+		let mut server = new_server(TileFormat::PNG);
+		server.set_cache_size(1_000_000);
+		server.start().await.unwrap();
+
+		let png = get().await;
+		assert_eq!(get().await, png);
+
+		// the new source stores JPEG, so its tile is transcoded to different PNG bytes
+		server
+			.replace_sources(new_server(TileFormat::JPG))
+			.await
+			.unwrap();
+		let transcoded = get().await;
+		assert!(transcoded.starts_with(b"\x89PNG"));
+		assert_ne!(transcoded, png);
+
+		server.stop().await;
+	}
+
+	#[tokio::test]
+	async fn replace_sources() {
+		async fn get(path: &str) -> u16 {
+			reqwest::get(format!("http://{IP}:50012/{path}"))
+				.await
+				.expect("should have made a get request")
+				.status()
+				.as_u16()
+		}
+		async fn reload(token: &str) -> reqwest::Response {
+			reqwest::Client::new()
+				.post(format!("http://{IP}:50012/api/admin/reload"))
+				.bearer_auth(token)
+				.send()
+				.await
+				.expect("should have made a post request")
+		}
+		fn new_server(id: &str) -> TileServer {
+			let mut server = TileServer::new(IP, 50012, true, true);
+			let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)
+				.unwrap()
+				.boxed();
+			server
+				.add_tile_source(Url::new(&format!("tiles/{id}")), reader)
+				.unwrap();
+			server
+		}
+
+		let (sender, mut receiver) = mpsc::channel(1);
+		let mut server = new_server("cheese");
+		server.set_cache_size(1_000_000);
+		server.set_admin_token("secret", sender);
+		server.start().await.unwrap();
+
+		assert_eq!(get("tiles/cheese/0/0/0.pbf").await, 200);
+		assert_eq!(get("tiles/burger/0/0/0.pbf").await, 404);
+
+		server.replace_sources(new_server("burger")).await.unwrap();
+		assert_eq!(get("tiles/cheese/0/0/0.pbf").await, 404);
+		assert_eq!(get("tiles/burger/0/0/0.pbf").await, 200);
+		assert_eq!(server.cache.as_ref().unwrap().get_stats().entries, 1);
+
+		// the admin API only sends the request, reloading is up to the receiver
+		let response = reload("wrong").await;
+		assert_eq!(response.status(), 401);
+		assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+		let task = tokio::spawn(async move {
+			let request = receiver.recv().await.unwrap();
+			assert_eq!(request.trigger, "admin API");
+			request.finish(Err(anyhow::anyhow!("file not found")));
+		});
+		let response = reload("secret").await;
+		assert_eq!(response.status(), 500);
+		assert_eq!(
+			response.text().await.unwrap(),
+			"{\"error\":\"file not found\"}"
+		);
+		task.await.unwrap();
+
+		server.stop().await;
+	}
+
+	#[test]
+	fn test_is_authorized() {
+		let test = |value: Option<&str>| {
+			let mut headers = HeaderMap::new();
+			if let Some(value) = value {
+				headers.insert(AUTHORIZATION, value.parse().unwrap());
+			}
+			is_authorized(&headers, "secret")
+		};
+
+		assert!(test(Some("Bearer secret")));
+		assert!(!test(None));
+		assert!(!test(Some("secret")));
+		assert!(!test(Some("Bearer secret2")));
+		assert!(!test(Some("Bearer secreT")));
+		assert!(!test(Some("Basic c2VjcmV0")));
+	}
+
 	#[tokio::test]
 	#[should_panic]
 	async fn same_prefix_twice() {
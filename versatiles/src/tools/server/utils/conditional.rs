@@ -0,0 +1,155 @@
+//! helper functions for conditional requests (`ETag`, `If-None-Match`, `If-Modified-Since`, `If-Range`)
+
+use crate::{types::Blob, utils::get_fnv_hash};
+use axum::http::{
+	header::{IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE},
+	HeaderMap,
+};
+use std::time::SystemTime;
+
+/// Returns a strong ETag (including quotes) based on a hash of the blob.
+pub fn get_etag(blob: &Blob) -> String {
+	format!("\"{:032x}\"", get_fnv_hash(blob.as_slice()))
+}
+
+/// Checks whether the client already has the current version of the resource.
+///
+/// `If-None-Match` takes precedence over `If-Modified-Since`, as defined in RFC 9110.
+pub fn is_not_modified(
+	request_headers: &HeaderMap,
+	etag: &str,
+	last_modified: Option<SystemTime>,
+) -> bool {
+	if let Some(value) = request_headers.get(IF_NONE_MATCH) {
+		return value.to_str().is_ok_and(|v| etag_matches(v, etag));
+	}
+
+	if let (Some(value), Some(last_modified)) =
+		(request_headers.get(IF_MODIFIED_SINCE), last_modified)
+	{
+		if let Some(since) = value
+			.to_str()
+			.ok()
+			.and_then(|v| httpdate::parse_http_date(v).ok())
+		{
+			// HTTP dates have a resolution of seconds
+			return httpdate::HttpDate::from(last_modified) <= httpdate::HttpDate::from(since);
+		}
+	}
+
+	false
+}
+
+/// Checks whether a range request may be answered with a part of the resource.
+/// If the `If-Range` header does not match, the whole resource has to be sent.
+pub fn is_range_valid(
+	request_headers: &HeaderMap,
+	etag: Option<&str>,
+	last_modified: Option<SystemTime>,
+) -> bool {
+	let value = match request_headers.get(IF_RANGE).map(|v| v.to_str()) {
+		None => return true,
+		Some(Ok(value)) => value.trim(),
+		Some(Err(_)) => return false,
+	};
+
+	// If-Range requires the strong comparison
+	if value.starts_with('"') {
+		return etag == Some(value);
+	}
+
+	match (httpdate::parse_http_date(value), last_modified) {
+		(Ok(date), Some(last_modified)) => {
+			httpdate::HttpDate::from(date) == httpdate::HttpDate::from(last_modified)
+		}
+		_ => false,
+	}
+}
+
+/// Compares a list of entity tags from an `If-None-Match` header with an ETag.
+/// Uses the weak comparison, so `W/"abc"` matches `"abc"`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+	if_none_match
+		.split(',')
+		.map(str::trim)
+		.any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	#[test]
+	fn etag() {
+		assert_eq!(
+			get_etag(&Blob::new_empty()),
+			"\"6c62272e07bb014262b821756295c58d\""
+		);
+		assert_eq!(get_etag(&Blob::from("tile")), get_etag(&Blob::from("tile")));
+		assert_ne!(get_etag(&Blob::from("tile")), get_etag(&Blob::from("tilf")));
+	}
+
+	#[test]
+	fn if_none_match() {
+		let etag = "\"0123456789abcdef\"";
+		let check = |value: &str| {
+			let mut headers = HeaderMap::new();
+			headers.insert(IF_NONE_MATCH, value.parse().unwrap());
+			is_not_modified(&headers, etag, None)
+		};
+
+		assert!(check("\"0123456789abcdef\""));
+		assert!(check("W/\"0123456789abcdef\""));
+		assert!(check("\"other\", \"0123456789abcdef\""));
+		assert!(check("*"));
+		assert!(!check("\"other\""));
+		assert!(!check("0123456789abcdef"));
+		assert!(!is_not_modified(&HeaderMap::new(), etag, None));
+	}
+
+	#[test]
+	fn if_range() {
+		let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+		let check = |value: Option<&str>| {
+			let mut headers = HeaderMap::new();
+			if let Some(value) = value {
+				headers.insert(IF_RANGE, value.parse().unwrap());
+			}
+			is_range_valid(&headers, Some("\"etag\""), Some(time))
+		};
+
+		assert!(check(None));
+		assert!(check(Some("\"etag\"")));
+		assert!(check(Some("Tue, 14 Nov 2023 22:13:20 GMT")));
+		assert!(!check(Some("\"other\"")));
+		assert!(!check(Some("W/\"etag\"")));
+		assert!(!check(Some("Tue, 14 Nov 2023 22:13:19 GMT")));
+		assert!(!check(Some("tomorrow")));
+	}
+
+	#[test]
+	fn if_modified_since() {
+		let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_500);
+		let check = |value: &str, last_modified: Option<SystemTime>| {
+			let mut headers = HeaderMap::new();
+			headers.insert(IF_MODIFIED_SINCE, value.parse().unwrap());
+			is_not_modified(&headers, "\"etag\"", last_modified)
+		};
+
+		assert!(check("Tue, 14 Nov 2023 22:13:20 GMT", Some(time)));
+		assert!(check("Wed, 15 Nov 2023 00:00:00 GMT", Some(time)));
+		assert!(!check("Tue, 14 Nov 2023 22:13:19 GMT", Some(time)));
+		assert!(!check("Tue, 14 Nov 2023 22:13:20 GMT", None));
+		assert!(!check("yesterday", Some(time)));
+
+		// If-None-Match takes precedence
+		let mut headers = HeaderMap::new();
+		headers.insert(
+			IF_MODIFIED_SINCE,
+			"Wed, 15 Nov 2023 00:00:00 GMT".parse().unwrap(),
+		);
+		headers.insert(IF_NONE_MATCH, "\"other\"".parse().unwrap());
+		assert!(!is_not_modified(&headers, "\"etag\"", Some(time)));
+	}
+}
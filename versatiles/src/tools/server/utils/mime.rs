@@ -12,6 +12,14 @@ pub fn guess_mime(path: &Path) -> String {
 	}
 }
 
+/// Returns true for formats that are already compressed, they are always sent as they are stored.
+pub fn is_incompressible(mime: &str) -> bool {
+	matches!(
+		mime,
+		"image/png" | "image/jpeg" | "image/webp" | "image/avif"
+	)
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -35,4 +43,12 @@ mod tests {
 		test("fluffy.png", "image/png");
 		test("fluffy.svg", "image/svg+xml");
 	}
+
+	#[test]
+	fn test_is_incompressible() {
+		assert!(is_incompressible("image/png"));
+		assert!(is_incompressible("image/webp"));
+		assert!(!is_incompressible("image/svg+xml"));
+		assert!(!is_incompressible("application/x-protobuf"));
+	}
 }
@@ -1,7 +1,11 @@
-//! helper function for handling URLs and MIME
+//! helper function for handling URLs, MIME, conditional and range requests
 
+mod conditional;
 mod mime;
+mod range;
 mod url;
 
+pub use conditional::*;
 pub use mime::*;
+pub use range::*;
 pub use url::*;
@@ -0,0 +1,100 @@
+//! helper functions for range requests (`Range`, `Content-Range`)
+
+use crate::types::ByteRange;
+use axum::http::{header::RANGE, HeaderMap};
+
+/// A single byte range of a `Range: bytes=…` header, not yet resolved against the size of the resource.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeRequest {
+	/// `bytes=first-last`, both positions are inclusive
+	Bounded(u64, u64),
+	/// `bytes=first-`
+	From(u64),
+	/// `bytes=-length`, the last `length` bytes
+	Suffix(u64),
+}
+
+impl RangeRequest {
+	/// Parses the `Range` header of a request.
+	/// Missing, malformed and multi-range headers return `None`, so the whole resource is served instead.
+	pub fn from_headers(headers: &HeaderMap) -> Option<RangeRequest> {
+		let value = headers.get(RANGE)?.to_str().ok()?.trim();
+		let spec = value.strip_prefix("bytes=")?.trim();
+		if spec.contains(',') {
+			return None;
+		}
+
+		let (first, last) = spec.split_once('-')?;
+		let (first, last) = (first.trim(), last.trim());
+		match (first.is_empty(), last.is_empty()) {
+			(true, false) => Some(RangeRequest::Suffix(last.parse().ok()?)),
+			(false, true) => Some(RangeRequest::From(first.parse().ok()?)),
+			(false, false) => {
+				let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+				(first <= last).then_some(RangeRequest::Bounded(first, last))
+			}
+			(true, true) => None,
+		}
+	}
+
+	/// Resolves the range against the size of the resource.
+	/// Returns `None` if the range is not satisfiable.
+	pub fn resolve(&self, size: u64) -> Option<ByteRange> {
+		use RangeRequest::*;
+		match *self {
+			Bounded(first, last) if first < size => {
+				Some(ByteRange::new(first, last.min(size - 1) - first + 1))
+			}
+			From(first) if first < size => Some(ByteRange::new(first, size - first)),
+			Suffix(length) if length > 0 && size > 0 => {
+				let length = length.min(size);
+				Some(ByteRange::new(size - length, length))
+			}
+			_ => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use RangeRequest::*;
+
+	fn parse(value: &str) -> Option<RangeRequest> {
+		let mut headers = HeaderMap::new();
+		headers.insert(RANGE, value.parse().unwrap());
+		RangeRequest::from_headers(&headers)
+	}
+
+	#[test]
+	fn from_headers() {
+		assert_eq!(parse("bytes=0-99"), Some(Bounded(0, 99)));
+		assert_eq!(parse("bytes= 10 - 20 "), Some(Bounded(10, 20)));
+		assert_eq!(parse("bytes=100-"), Some(From(100)));
+		assert_eq!(parse("bytes=-100"), Some(Suffix(100)));
+		assert_eq!(parse("bytes=20-10"), None);
+		assert_eq!(parse("bytes=-"), None);
+		assert_eq!(parse("bytes=0-10,20-30"), None);
+		assert_eq!(parse("bytes=a-b"), None);
+		assert_eq!(parse("items=0-10"), None);
+		assert_eq!(RangeRequest::from_headers(&HeaderMap::new()), None);
+	}
+
+	#[test]
+	fn resolve() {
+		let check = |range: RangeRequest, size: u64, expected: Option<(u64, u64)>| {
+			let expected = expected.map(|(offset, length)| ByteRange::new(offset, length));
+			assert_eq!(range.resolve(size), expected, "{range:?} of {size}");
+		};
+
+		check(Bounded(0, 99), 1000, Some((0, 100)));
+		check(Bounded(900, 1999), 1000, Some((900, 100)));
+		check(Bounded(1000, 1999), 1000, None);
+		check(From(10), 1000, Some((10, 990)));
+		check(From(1000), 1000, None);
+		check(Suffix(100), 1000, Some((900, 100)));
+		check(Suffix(2000), 1000, Some((0, 1000)));
+		check(Suffix(0), 1000, None);
+		check(Suffix(10), 0, None);
+	}
+}
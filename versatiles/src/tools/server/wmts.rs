@@ -0,0 +1,373 @@
+//! OGC WMTS 1.0 for GIS clients like QGIS or ArcGIS, served per tile source at "/tiles/$id/wmts"
+//!
+//! `?SERVICE=WMTS&REQUEST=GetCapabilities` describes the source as a single layer
+//! in the "GoogleMapsCompatible" tile matrix set (Web Mercator, 256 pixel tiles).
+//! `?SERVICE=WMTS&REQUEST=GetTile&TILEMATRIX=z&TILEROW=y&TILECOL=x` is answered like "/tiles/$id/z/x/y".
+//! The capabilities also contain RESTful URL templates, so clients can use the normal tile urls.
+
+use super::sources::{get_mime, TileSource, UrlScheme};
+use crate::types::{TileCoord3, TileFormat};
+use std::{collections::BTreeMap, fmt::Write};
+
+const TILE_MATRIX_SET: &str = "GoogleMapsCompatible";
+
+// scale denominator of zoom level 0 for 256 pixel tiles, as defined by the well known scale set
+const SCALE_DENOMINATOR_0: f64 = 559_082_264.028_717_8;
+
+#[derive(Debug, PartialEq)]
+pub enum WmtsRequest {
+	GetCapabilities,
+	GetTile {
+		coord: TileCoord3,
+		format: TileFormat,
+	},
+}
+
+/// An error in the format of an OWS exception report
+#[derive(Debug, PartialEq)]
+pub struct WmtsException {
+	pub status: u16,
+	code: &'static str,
+	locator: String,
+}
+
+impl WmtsException {
+	fn new(code: &'static str, locator: &str) -> WmtsException {
+		let status = match code {
+			"OperationNotSupported" => 501,
+			_ => 400,
+		};
+		WmtsException {
+			status,
+			code,
+			locator: locator.to_owned(),
+		}
+	}
+
+	pub fn as_xml(&self) -> String {
+		format!(
+			"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+			<ExceptionReport xmlns=\"http://www.opengis.net/ows/1.1\" version=\"1.1.0\" xml:lang=\"en\">\
+			<Exception exceptionCode=\"{}\" locator=\"{}\"/></ExceptionReport>",
+			self.code,
+			escape(&self.locator)
+		)
+	}
+}
+
+/// Parses the query string of a KVP request. Parameter names are case insensitive.
+pub fn parse_request(
+	query: &str,
+	source: &TileSource,
+	id: &str,
+) -> Result<WmtsRequest, WmtsException> {
+	let params: BTreeMap<String, String> = query
+		.split('&')
+		.filter(|pair| !pair.is_empty())
+		.map(|pair| {
+			let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+			(decode(key).to_lowercase(), decode(value))
+		})
+		.collect();
+
+	let get = |key: &str| {
+		params
+			.get(key)
+			.map(String::as_str)
+			.filter(|value| !value.is_empty())
+			.ok_or_else(|| WmtsException::new("MissingParameterValue", key))
+	};
+	let invalid = |key: &str| WmtsException::new("InvalidParameterValue", key);
+
+	if !get("service")?.eq_ignore_ascii_case("WMTS") {
+		return Err(invalid("service"));
+	}
+
+	let request = get("request")?;
+	if request.eq_ignore_ascii_case("GetCapabilities") {
+		return Ok(WmtsRequest::GetCapabilities);
+	}
+	if !request.eq_ignore_ascii_case("GetTile") {
+		return Err(WmtsException::new("OperationNotSupported", "request"));
+	}
+
+	if get("layer")? != id {
+		return Err(invalid("layer"));
+	}
+	if get("tilematrixset")? != TILE_MATRIX_SET {
+		return Err(invalid("tilematrixset"));
+	}
+
+	let format = match params.get("format").filter(|format| !format.is_empty()) {
+		None => source.tile_format,
+		Some(mime) => source
+			.get_formats()
+			.into_iter()
+			.find(|format| get_mime(*format) == mime)
+			.ok_or_else(|| invalid("format"))?,
+	};
+
+	let z = get("tilematrix")?
+		.parse::<u8>()
+		.map_err(|_| invalid("tilematrix"))?;
+	let y = get("tilerow")?
+		.parse::<u32>()
+		.map_err(|_| invalid("tilerow"))?;
+	let x = get("tilecol")?
+		.parse::<u32>()
+		.map_err(|_| invalid("tilecol"))?;
+
+	let pyramid = &source.get_parameters().bbox_pyramid;
+	if z > pyramid.get_zoom_max().unwrap_or(0) {
+		return Err(invalid("tilematrix"));
+	}
+	let coord = TileCoord3::new(x, y, z).map_err(|_| invalid("tilerow"))?;
+	if !pyramid.contains_coord(&coord) {
+		return Err(WmtsException::new("TileOutOfRange", "tilerow"));
+	}
+
+	Ok(WmtsRequest::GetTile { coord, format })
+}
+
+/// Builds the capabilities document of a tile source. URLs are absolute if `base_url` (e.g. "http://host:port") is given.
+pub fn get_capabilities(source: &TileSource, id: &str, base_url: &str) -> String {
+	let pyramid = &source.get_parameters().bbox_pyramid;
+	let zoom_max = pyramid.get_zoom_max().unwrap_or(0);
+	let bounds = pyramid.get_geo_bbox();
+	let id = escape(id);
+	let url = escape(&format!("{base_url}{}", source.prefix.as_dir()));
+	let formats = source.get_formats();
+
+	let mut xml = String::new();
+	xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+	xml.push_str(
+		"<Capabilities xmlns=\"http://www.opengis.net/wmts/1.0\" xmlns:ows=\"http://www.opengis.net/ows/1.1\" \
+		xmlns:xlink=\"http://www.w3.org/1999/xlink\" version=\"1.0.0\">",
+	);
+
+	write!(
+		xml,
+		"<ows:ServiceIdentification><ows:Title>{id}</ows:Title>\
+		<ows:ServiceType>OGC WMTS</ows:ServiceType><ows:ServiceTypeVersion>1.0.0</ows:ServiceTypeVersion>\
+		</ows:ServiceIdentification>"
+	)
+	.unwrap();
+
+	xml.push_str("<ows:OperationsMetadata>");
+	for operation in ["GetCapabilities", "GetTile"] {
+		write!(
+			xml,
+			"<ows:Operation name=\"{operation}\"><ows:DCP><ows:HTTP><ows:Get xlink:href=\"{url}wmts?\">\
+			<ows:Constraint name=\"GetEncoding\"><ows:AllowedValues><ows:Value>KVP</ows:Value></ows:AllowedValues></ows:Constraint>\
+			</ows:Get></ows:HTTP></ows:DCP></ows:Operation>"
+		)
+		.unwrap();
+	}
+	xml.push_str("</ows:OperationsMetadata>");
+
+	xml.push_str("<Contents><Layer>");
+	write!(
+		xml,
+		"<ows:Title>{id}</ows:Title>\
+		<ows:WGS84BoundingBox><ows:LowerCorner>{} {}</ows:LowerCorner><ows:UpperCorner>{} {}</ows:UpperCorner></ows:WGS84BoundingBox>\
+		<ows:Identifier>{id}</ows:Identifier>\
+		<Style isDefault=\"true\"><ows:Identifier>default</ows:Identifier></Style>",
+		bounds[0], bounds[1], bounds[2], bounds[3]
+	)
+	.unwrap();
+	for format in formats.iter() {
+		write!(xml, "<Format>{}</Format>", get_mime(*format)).unwrap();
+	}
+
+	write!(
+		xml,
+		"<TileMatrixSetLink><TileMatrixSet>{TILE_MATRIX_SET}</TileMatrixSet><TileMatrixSetLimits>"
+	)
+	.unwrap();
+	for bbox in pyramid.iter_levels() {
+		write!(
+			xml,
+			"<TileMatrixLimits><TileMatrix>{}</TileMatrix>\
+			<MinTileRow>{}</MinTileRow><MaxTileRow>{}</MaxTileRow>\
+			<MinTileCol>{}</MinTileCol><MaxTileCol>{}</MaxTileCol></TileMatrixLimits>",
+			bbox.level, bbox.y_min, bbox.y_max, bbox.x_min, bbox.x_max
+		)
+		.unwrap();
+	}
+	xml.push_str("</TileMatrixSetLimits></TileMatrixSetLink>");
+
+	// TMS rows and quadkeys can not be expressed in a template, so those clients have to use KVP
+	let template = match source.scheme {
+		UrlScheme::Xyz => Some("{TileMatrix}/{TileCol}/{TileRow}"),
+		UrlScheme::Zyx => Some("{TileMatrix}/{TileRow}/{TileCol}"),
+		UrlScheme::Tms | UrlScheme::Quadkey => None,
+	};
+	for format in formats.iter().filter(|_| template.is_some()) {
+		write!(
+			xml,
+			"<ResourceURL format=\"{}\" resourceType=\"tile\" template=\"{url}{}{}\"/>",
+			get_mime(*format),
+			template.unwrap(),
+			format.extension()
+		)
+		.unwrap();
+	}
+	xml.push_str("</Layer>");
+
+	write!(
+		xml,
+		"<TileMatrixSet><ows:Identifier>{TILE_MATRIX_SET}</ows:Identifier>\
+		<ows:SupportedCRS>urn:ogc:def:crs:EPSG::3857</ows:SupportedCRS>\
+		<WellKnownScaleSet>urn:ogc:def:wkss:OGC:1.0:GoogleMapsCompatible</WellKnownScaleSet>"
+	)
+	.unwrap();
+	for z in 0..=zoom_max {
+		let size = 1u64 << z;
+		write!(
+			xml,
+			"<TileMatrix><ows:Identifier>{z}</ows:Identifier><ScaleDenominator>{}</ScaleDenominator>\
+			<TopLeftCorner>-20037508.3427892 20037508.3427892</TopLeftCorner>\
+			<TileWidth>256</TileWidth><TileHeight>256</TileHeight>\
+			<MatrixWidth>{size}</MatrixWidth><MatrixHeight>{size}</MatrixHeight></TileMatrix>",
+			SCALE_DENOMINATOR_0 / size as f64
+		)
+		.unwrap();
+	}
+	xml.push_str("</TileMatrixSet></Contents></Capabilities>");
+
+	xml
+}
+
+fn escape(text: &str) -> String {
+	text
+		.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+// decodes "+" and percent-encoded bytes of a query parameter
+fn decode(text: &str) -> String {
+	let mut bytes = Vec::with_capacity(text.len());
+	let mut iter = text.bytes();
+	while let Some(byte) = iter.next() {
+		match byte {
+			b'+' => bytes.push(b' '),
+			b'%' => {
+				let hex: Vec<u8> = iter.clone().take(2).collect();
+				match std::str::from_utf8(&hex)
+					.ok()
+					.and_then(|hex| u8::from_str_radix(hex, 16).ok())
+				{
+					Some(value) if hex.len() == 2 => {
+						bytes.push(value);
+						iter.nth(1);
+					}
+					_ => bytes.push(byte),
+				}
+			}
+			_ => bytes.push(byte),
+		}
+	}
+	String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		container::{MockTilesReader, MockTilesReaderProfile},
+		tools::server::Url,
+		types::TilesReaderTrait,
+	};
+
+	fn get_source(profile: MockTilesReaderProfile) -> TileSource {
+		let reader = MockTilesReader::new_mock_profile(profile).unwrap();
+		TileSource::from(reader.boxed(), Url::new("/tiles/osm/")).unwrap()
+	}
+
+	#[test]
+	fn requests() {
+		let source = get_source(MockTilesReaderProfile::Png);
+		let parse = |query: &str| parse_request(query, &source, "osm");
+
+		assert_eq!(
+			parse("service=WMTS&request=GetCapabilities"),
+			Ok(WmtsRequest::GetCapabilities)
+		);
+		assert_eq!(
+			parse("SERVICE=WMTS&REQUEST=GetTile&VERSION=1.0.0&LAYER=osm&STYLE=default&TILEMATRIXSET=GoogleMapsCompatible&TILEMATRIX=3&TILEROW=2&TILECOL=5&FORMAT=image%2Fwebp"),
+			Ok(WmtsRequest::GetTile {
+				coord: TileCoord3::new(5, 2, 3).unwrap(),
+				format: TileFormat::WEBP
+			})
+		);
+		assert_eq!(
+			parse("Service=WMTS&Request=GetTile&Layer=osm&TileMatrixSet=GoogleMapsCompatible&TileMatrix=0&TileRow=0&TileCol=0"),
+			Ok(WmtsRequest::GetTile {
+				coord: TileCoord3::new(0, 0, 0).unwrap(),
+				format: TileFormat::PNG
+			})
+		);
+
+		let error = |query: &str| parse(query).unwrap_err().as_xml();
+		assert!(
+			error("request=GetCapabilities").contains("\"MissingParameterValue\" locator=\"service\"")
+		);
+		assert!(error("service=WMS&request=GetMap")
+			.contains("\"InvalidParameterValue\" locator=\"service\""));
+		assert_eq!(
+			parse("service=WMTS&request=GetFeatureInfo")
+				.unwrap_err()
+				.status,
+			501
+		);
+
+		let tile = "service=WMTS&request=GetTile&tilematrixset=GoogleMapsCompatible";
+		assert!(error(&format!(
+			"{tile}&layer=other&tilematrix=0&tilerow=0&tilecol=0"
+		))
+		.contains("locator=\"layer\""));
+		assert!(error(&format!(
+			"{tile}&layer=osm&tilematrix=9&tilerow=0&tilecol=0"
+		))
+		.contains("locator=\"tilematrix\""));
+		assert!(error(&format!(
+			"{tile}&layer=osm&tilematrix=1&tilerow=2&tilecol=0"
+		))
+		.contains("locator=\"tilerow\""));
+		assert!(error(&format!(
+			"{tile}&layer=osm&tilematrix=1&tilerow=0&tilecol=0&format=application/x-protobuf"
+		))
+		.contains("locator=\"format\""));
+	}
+
+	#[test]
+	fn capabilities() {
+		let source = get_source(MockTilesReaderProfile::Pbf);
+		let xml = get_capabilities(&source, "osm", "http://localhost:8080");
+
+		assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Capabilities "));
+		assert!(xml.contains("<ows:Get xlink:href=\"http://localhost:8080/tiles/osm/wmts?\">"));
+		assert!(xml.contains("<ows:Identifier>osm</ows:Identifier>"));
+		assert!(xml.contains("<ows:LowerCorner>-180 -85.05112877980659</ows:LowerCorner>"));
+		assert!(xml.contains("<Format>application/x-protobuf</Format>"));
+		assert!(xml.contains("<TileMatrixLimits><TileMatrix>4</TileMatrix><MinTileRow>0</MinTileRow><MaxTileRow>15</MaxTileRow>"));
+		assert!(xml.contains(
+			"template=\"http://localhost:8080/tiles/osm/{TileMatrix}/{TileCol}/{TileRow}.pbf\""
+		));
+		assert!(xml.contains(
+			"<ows:Identifier>4</ows:Identifier><ScaleDenominator>34942641.50179486</ScaleDenominator>"
+		));
+		assert!(!xml.contains("<ows:Identifier>5</ows:Identifier>"));
+		assert!(xml.ends_with("</TileMatrixSet></Contents></Capabilities>"));
+	}
+
+	#[test]
+	fn decode_query() {
+		assert_eq!(decode("image%2Fpng"), "image/png");
+		assert_eq!(decode("a+b%20c"), "a b c");
+		assert_eq!(decode("100%"), "100%");
+		assert_eq!(decode("%zz"), "%zz");
+	}
+}
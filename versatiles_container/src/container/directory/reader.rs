@@ -20,8 +20,10 @@
 //!
 //! ## Features
 //! - Supports multiple tile formats and compressions
+//! - Verifies tile format and compression by sniffing a sample tile, e.g. to detect gzipped tiles without `.gz` suffix
 //! - Automatically detects and reads metadata files in the directory
 //! - Provides asynchronous methods to fetch tile data
+//! - Reports unknown files, duplicate tiles and tile sizes per zoom level when probed
 //!
 //! ## Usage
 //! ```no_run
@@ -43,12 +45,18 @@
 //! This module includes comprehensive tests to ensure the correct functionality of opening paths, reading metadata, handling different file formats, and edge cases.
 
 use crate::{
+	container::correct_parameters_by_sample,
 	types::{
 		Blob, TileBBoxPyramid, TileCompression, TileCoord3, TileFormat, TilesReaderParameters,
 		TilesReaderTrait,
 	},
 	utils::decompress,
 };
+#[cfg(feature = "cli")]
+use crate::{
+	container::{TileContentsProbe, TileSizeStats},
+	utils::PrettyPrint,
+};
 use anyhow::{bail, ensure, Context, Result};
 use async_trait::async_trait;
 use itertools::Itertools;
@@ -70,6 +78,8 @@ pub struct DirectoryTilesReader {
 	dir: PathBuf,
 	tile_map: HashMap<TileCoord3, PathBuf>,
 	parameters: TilesReaderParameters,
+	duplicate_tiles: Vec<String>,
+	unknown_files: Vec<String>,
 }
 
 impl DirectoryTilesReader {
@@ -97,6 +107,9 @@ impl DirectoryTilesReader {
 		let mut container_form: Option<TileFormat> = None;
 		let mut container_comp: Option<TileCompression> = None;
 		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		let mut duplicate_tiles: Vec<String> = Vec::new();
+		let mut unknown_files: Vec<String> = Vec::new();
+		let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).display().to_string();
 
 		for result1 in fs::read_dir(dir)? {
 			// z level
@@ -106,7 +119,7 @@ impl DirectoryTilesReader {
 			let entry1 = result1?;
 			let name1 = entry1.file_name().into_string().unwrap();
 			let numeric1 = name1.parse::<u8>();
-			if numeric1.is_ok() {
+			if numeric1.is_ok() && entry1.path().is_dir() {
 				let z = numeric1?;
 
 				for result2 in fs::read_dir(entry1.path())? {
@@ -117,7 +130,8 @@ impl DirectoryTilesReader {
 					let entry2 = result2?;
 					let name2 = entry2.file_name().into_string().unwrap();
 					let numeric2 = name2.parse::<u32>();
-					if numeric2.is_err() {
+					if numeric2.is_err() || !entry2.path().is_dir() {
+						unknown_files.push(relative(&entry2.path()));
 						continue;
 					}
 					let x = numeric2?;
@@ -133,12 +147,14 @@ impl DirectoryTilesReader {
 						let this_form = TileFormat::from_filename(&mut filename);
 
 						if this_form.is_none() {
+							unknown_files.push(relative(&entry3.path()));
 							continue;
 						}
 						let file_form = this_form.unwrap();
 
 						let numeric3 = filename.parse::<u32>();
 						if numeric3.is_err() {
+							unknown_files.push(relative(&entry3.path()));
 							continue;
 						}
 						let y = numeric3?;
@@ -163,7 +179,10 @@ impl DirectoryTilesReader {
 
 						let coord3 = TileCoord3::new(x, y, z)?;
 						bbox_pyramid.include_coord(&coord3);
-						tile_map.insert(coord3, entry3.path());
+						// the later file is served, the replaced one is reported
+						if let Some(replaced) = tile_map.insert(coord3, entry3.path()) {
+							duplicate_tiles.push(relative(&replaced));
+						}
 					}
 				}
 			} else {
@@ -188,21 +207,34 @@ impl DirectoryTilesReader {
 					}
 					&_ => {}
 				};
+				unknown_files.push(name1);
 			}
 		}
 
 		if tile_map.is_empty() {
 			bail!("no tiles found");
 		}
+		unknown_files.sort();
 
 		let tile_format = container_form.context("tile format must be specified")?;
 		let tile_compression = container_comp.context("tile compression must be specified")?;
 
+		let mut parameters = TilesReaderParameters::new(tile_format, tile_compression, bbox_pyramid);
+		if let Some(path) = tile_map
+			.iter()
+			.min_by_key(|(coord, _)| coord.get_sort_index())
+			.map(|(_, path)| path)
+		{
+			correct_parameters_by_sample(&mut parameters, &Self::read(path)?, dir.to_str().unwrap());
+		}
+
 		Ok(DirectoryTilesReader {
 			meta,
 			dir: dir.to_path_buf(),
 			tile_map,
-			parameters: TilesReaderParameters::new(tile_format, tile_compression, bbox_pyramid),
+			parameters,
+			duplicate_tiles,
+			unknown_files,
 		})
 	}
 
@@ -237,6 +269,45 @@ impl TilesReaderTrait for DirectoryTilesReader {
 	fn get_name(&self) -> &str {
 		self.dir.to_str().unwrap()
 	}
+
+	// deep probe of the directory structure
+	#[cfg(feature = "cli")]
+	async fn probe_container(&mut self, print: &PrettyPrint) -> Result<()> {
+		print
+			.add_key_value("tile files", &self.tile_map.len())
+			.await;
+		print
+			.add_key_value("meta size", &self.meta.as_ref().map_or(0, |b| b.len()))
+			.await;
+
+		for (key, label, paths) in [
+			("duplicate tiles", "duplicate tile", &self.duplicate_tiles),
+			("unknown files", "unknown file", &self.unknown_files),
+		] {
+			print.add_key_value(key, &paths.len()).await;
+			for path in paths.iter().take(10) {
+				print.add_warning(&format!("{label} {path:?}")).await;
+			}
+		}
+
+		Ok(())
+	}
+
+	// deep probe of tile sizes
+	#[cfg(feature = "cli")]
+	async fn probe_tiles(&mut self, print: &PrettyPrint) -> Result<()> {
+		let mut stats = TileSizeStats::new();
+		for (coord, path) in self.tile_map.iter() {
+			stats.add(coord.z, fs::metadata(path)?.len());
+		}
+		stats.print(print).await;
+		Ok(())
+	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 impl Debug for DirectoryTilesReader {
@@ -402,6 +473,26 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn sniff_format_and_compression() -> Result<()> {
+		let dir = TempDir::new()?;
+		fs::create_dir_all(dir.path().join("1/2"))?;
+		let png = Blob::from(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec());
+		fs::write(
+			dir.path().join("1/2/3.jpg"),
+			compress(png, &TileCompression::Gzip)?.as_slice(),
+		)?;
+
+		let reader = DirectoryTilesReader::open_path(&dir)?;
+		assert_eq!(reader.get_parameters().tile_format, TileFormat::PNG);
+		assert_eq!(
+			reader.get_parameters().tile_compression,
+			TileCompression::Gzip
+		);
+
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_minor_functions() -> Result<()> {
 		let dir = assert_fs::TempDir::new()?;
@@ -434,4 +525,41 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[cfg(feature = "cli")]
+	#[tokio::test]
+	async fn probe() -> Result<()> {
+		use crate::utils::PrettyPrint;
+
+		let dir = assert_fs::TempDir::new()?;
+		dir.child(".DS_Store").write_str("")?;
+		dir.child("meta.json").write_str("{}")?;
+		dir.child("0/0/0.png").write_str(&"a".repeat(100))?;
+		dir.child("1/0/1.png").write_str(&"a".repeat(2000))?;
+		dir.child("1/0/01.png").write_str(&"a".repeat(3000))?;
+		dir.child("1/0/notes.txt").write_str("")?;
+		dir.child("1/x/0.png").write_str("")?;
+
+		let mut reader = DirectoryTilesReader::open_path(dir.path())?;
+
+		let mut printer = PrettyPrint::new();
+		reader
+			.probe_container(&printer.get_category("container").await)
+			.await?;
+		assert_eq!(
+			printer.as_string().await,
+			"container:\n   tile files: 2\n   meta size: 2\n   duplicate tiles: 1\n   duplicate tile \"1/0/01.png\"\n   unknown files: 3\n   unknown file \".DS_Store\"\n   unknown file \"1/0/notes.txt\"\n   unknown file \"1/x\"\n"
+		);
+
+		let mut printer = PrettyPrint::new();
+		reader
+			.probe_tiles(&printer.get_category("tiles").await)
+			.await?;
+		assert_eq!(
+			printer.as_string().await,
+			"tiles:\n   tile count: 2\n   sum of tile sizes: 2_100\n   level 0: count: 1, average: 100, min: 100, max: 100, histogram: [<1K: 1]\n   level 1: count: 1, average: 2000, min: 2000, max: 2000, histogram: [<2K: 1]\n"
+		);
+
+		Ok(())
+	}
 }
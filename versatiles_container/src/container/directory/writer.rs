@@ -21,6 +21,7 @@
 //! ## Features
 //! - Supports writing metadata and tile data in multiple formats and compressions
 //! - Ensures directory structure is created if it does not exist
+//! - Updates existing directories, replacing tiles and merging metadata
 //! - Provides progress feedback during the write process
 //!
 //! ## Usage
@@ -45,8 +46,8 @@
 //! This module includes comprehensive tests to ensure the correct functionality of writing metadata, handling different file formats, and verifying directory structure.
 
 use crate::{
-	container::TilesWriterTrait,
-	types::{Blob, TilesReaderTrait},
+	container::{merge_meta, DirectoryTilesReader, TilesWriterTrait},
+	types::{Blob, TileCompression, TilesReaderTrait},
 	utils::{compress, io::DataWriterTrait, progress::get_progress_bar},
 };
 use anyhow::{bail, ensure, Result};
@@ -77,39 +78,70 @@ impl DirectoryTilesWriter {
 		fs::write(&path, blob.as_slice())?;
 		Ok(())
 	}
-}
 
-#[async_trait]
-impl TilesWriterTrait for DirectoryTilesWriter {
-	/// Writes the tile data and metadata from the given `TilesReader` to the specified directory path.
+	/// Updates an existing directory: tiles of the reader are added or replace existing tiles,
+	/// and the meta data is merged with the existing meta data.
 	///
 	/// # Arguments
 	/// * `reader` - A mutable reference to the `TilesReader` providing the data.
-	/// * `path` - The directory path where the data should be written.
+	/// * `path` - The path of the existing directory.
 	///
 	/// # Errors
-	/// Returns an error if the path is not absolute, if there are issues with file I/O, or if compression fails.
-	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
-		ensure!(path.is_absolute(), "path {path:?} must be absolute");
+	/// Returns an error if the directory can not be read, if the tile format or compression differ from the existing tiles,
+	/// or if there are issues with file I/O.
+	pub async fn update_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		let existing = DirectoryTilesReader::open_path(path)?;
 
-		log::trace!("convert_from");
+		let parameters = existing.get_parameters();
+		let new_parameters = reader.get_parameters();
+		ensure!(
+			parameters.tile_format == new_parameters.tile_format
+				&& parameters.tile_compression == new_parameters.tile_compression,
+			"can not update {path:?} containing {} tiles with {} compression, using {} tiles with {} compression",
+			parameters.tile_format,
+			parameters.tile_compression,
+			new_parameters.tile_format,
+			new_parameters.tile_compression
+		);
 
-		let parameters = reader.get_parameters();
-		let tile_compression = &parameters.tile_compression.clone();
-		let tile_format = &parameters.tile_format.clone();
-		let bbox_pyramid = &reader.get_parameters().bbox_pyramid.clone();
-
-		let extension_format = tile_format.extension();
-		let extension_compression = tile_compression.extension();
+		let meta = merge_meta(existing.get_meta()?, reader.get_meta()?);
+		if meta != existing.get_meta()? {
+			// replace all existing meta data files, since they might use a different name or compression
+			for name in ["meta.json", "tiles.json", "metadata.json"] {
+				for extension in ["", ".gz", ".br"] {
+					let filename = path.join(format!("{name}{extension}"));
+					if filename.is_file() {
+						fs::remove_file(filename)?;
+					}
+				}
+			}
+			Self::write_meta(path, meta, &new_parameters.tile_compression)?;
+		}
 
-		let meta_data_option = reader.get_meta()?;
+		Self::write_tiles(reader, path).await
+	}
 
-		if let Some(meta_data) = meta_data_option {
+	/// Writes the meta data as `tiles.json`, compressed like the tiles.
+	fn write_meta(
+		path: &Path,
+		meta: Option<Blob>,
+		tile_compression: &TileCompression,
+	) -> Result<()> {
+		if let Some(meta_data) = meta {
 			let meta_data = compress(meta_data, tile_compression)?;
-			let filename = format!("tiles.json{extension_compression}");
+			let filename = format!("tiles.json{}", tile_compression.extension());
 
 			Self::write(path.join(filename), meta_data)?;
 		}
+		Ok(())
+	}
+
+	/// Writes all tiles of the reader as `<z>/<x>/<y>.<format>[.<compression>]`.
+	async fn write_tiles(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		let parameters = reader.get_parameters();
+		let extension_format = parameters.tile_format.extension();
+		let extension_compression = parameters.tile_compression.extension();
+		let bbox_pyramid = parameters.bbox_pyramid.clone();
 
 		let mut progress = get_progress_bar("converting tiles", bbox_pyramid.count_tiles());
 
@@ -123,7 +155,7 @@ impl TilesWriterTrait for DirectoryTilesWriter {
 
 				let filename = format!(
 					"{}/{}/{}{}{}",
-					coord.z, coord.y, coord.x, extension_format, extension_compression
+					coord.z, coord.x, coord.y, extension_format, extension_compression
 				);
 
 				// Write blob to file
@@ -135,6 +167,27 @@ impl TilesWriterTrait for DirectoryTilesWriter {
 
 		Ok(())
 	}
+}
+
+#[async_trait]
+impl TilesWriterTrait for DirectoryTilesWriter {
+	/// Writes the tile data and metadata from the given `TilesReader` to the specified directory path.
+	///
+	/// # Arguments
+	/// * `reader` - A mutable reference to the `TilesReader` providing the data.
+	/// * `path` - The directory path where the data should be written.
+	///
+	/// # Errors
+	/// Returns an error if the path is not absolute, if there are issues with file I/O, or if compression fails.
+	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		ensure!(path.is_absolute(), "path {path:?} must be absolute");
+
+		log::trace!("convert_from");
+
+		let tile_compression = reader.get_parameters().tile_compression;
+		Self::write_meta(path, reader.get_meta()?, &tile_compression)?;
+		Self::write_tiles(reader, path).await
+	}
 
 	/// Writes the tile data from the given `TilesReader` to the specified `DataWriterTrait`.
 	///
@@ -157,7 +210,7 @@ mod tests {
 	use super::*;
 	use crate::{
 		container::{MockTilesReader, MOCK_BYTES_PBF},
-		types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
+		types::{TileBBox, TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
 		utils::decompress_gzip,
 	};
 
@@ -192,4 +245,69 @@ mod tests {
 
 		Ok(())
 	}
+
+	/// The writer must use the layout `<z>/<x>/<y>` that `DirectoryTilesReader` expects.
+	#[tokio::test]
+	async fn layout_matches_reader() -> Result<()> {
+		let temp_dir = assert_fs::TempDir::new()?;
+		let temp_path = temp_dir.path();
+
+		let mut pyramid = TileBBoxPyramid::new_empty();
+		pyramid.set_level_bbox(TileBBox::new(3, 5, 1, 6, 2)?);
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PBF,
+			TileCompression::Gzip,
+			pyramid.clone(),
+		))?;
+		DirectoryTilesWriter::write_to_path(&mut mock_reader, temp_path).await?;
+
+		assert!(temp_path.join("3/5/1.pbf.gz").is_file());
+		assert!(!temp_path.join("3/1/5.pbf.gz").exists());
+
+		let reader = DirectoryTilesReader::open_path(temp_path)?;
+		assert_eq!(reader.get_parameters().bbox_pyramid, pyramid);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn update() -> Result<()> {
+		let temp_dir = assert_fs::TempDir::new()?;
+		let temp_path = temp_dir.path();
+
+		let get_mock_reader = |format: TileFormat, pyramid: TileBBoxPyramid| {
+			MockTilesReader::new_mock(TilesReaderParameters::new(
+				format,
+				TileCompression::Gzip,
+				pyramid,
+			))
+		};
+
+		let mut mock_reader = get_mock_reader(TileFormat::PBF, TileBBoxPyramid::new_full(1))?;
+		DirectoryTilesWriter::write_to_path(&mut mock_reader, temp_path).await?;
+
+		let mut pyramid = TileBBoxPyramid::new_empty();
+		pyramid.set_level_bbox(TileBBox::new(3, 5, 1, 6, 1)?);
+		let mut mock_reader = get_mock_reader(TileFormat::PBF, pyramid)?;
+		DirectoryTilesWriter::update_path(&mut mock_reader, temp_path).await?;
+
+		assert!(temp_path.join("3/5/1.pbf.gz").is_file());
+		assert!(temp_path.join("3/6/1.pbf.gz").is_file());
+
+		let reader = DirectoryTilesReader::open_path(temp_path)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 3: [5,1,6,1] (2)]"
+		);
+		assert_eq!(reader.get_meta()?.unwrap().as_str(), "dummy meta data");
+
+		let mut mock_reader = get_mock_reader(TileFormat::PNG, TileBBoxPyramid::new_full(1))?;
+		assert!(
+			DirectoryTilesWriter::update_path(&mut mock_reader, temp_path)
+				.await
+				.is_err()
+		);
+
+		Ok(())
+	}
 }
@@ -5,9 +5,12 @@
 //! The main components of this module are:
 //! - `MBTilesReader`: Reads tiles from an MBTiles SQLite database.
 //! - `MBTilesWriter`: Writes tiles to an MBTiles SQLite database.
+//! - `MBTilesSchema`: The table layout, either a flat `tiles` table or deduplicated `map` and `images` tables.
 
 mod reader;
+mod schema;
 mod writer;
 
 pub use reader::MBTilesReader;
+pub use schema::MBTilesSchema;
 pub use writer::MBTilesWriter;
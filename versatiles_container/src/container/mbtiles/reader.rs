@@ -5,7 +5,9 @@
 //! ## Features
 //! - Supports reading metadata and tile data in multiple formats and compressions
 //! - Provides methods to query the database for tile data based on coordinates or bounding boxes
+//! - Reads both the flat `tiles` table and the deduplicated schema with `map` and `images` tables
 //! - Allows overriding the tile compression method
+//! - Reports the SQLite schema, missing indexes and tile sizes per zoom level when probed
 //!
 //! ## Usage Example
 //! ```rust
@@ -41,7 +43,9 @@
 //! ## Testing
 //! This module includes comprehensive tests to ensure the correct functionality of reading metadata, handling different file formats, and verifying tile data.
 
+use super::MBTilesSchema;
 use crate::{
+	container::correct_parameters_by_sample,
 	types::{
 		Blob, TileBBox, TileBBoxPyramid,
 		TileCompression::{self, *},
@@ -51,8 +55,15 @@ use crate::{
 	},
 	utils::{progress::get_progress_bar, TransformCoord},
 };
+#[cfg(feature = "cli")]
+use crate::{
+	container::{TileContentsProbe, TileSizeStats},
+	utils::PrettyPrint,
+};
 use anyhow::{anyhow, ensure, Result};
 use async_trait::async_trait;
+#[cfg(feature = "cli")]
+use itertools::Itertools;
 use log::trace;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
@@ -62,6 +73,7 @@ use std::path::Path;
 pub struct MBTilesReader {
 	name: String,
 	pool: Pool<SqliteConnectionManager>,
+	schema: MBTilesSchema,
 	meta_data: Option<Blob>,
 	parameters: TilesReaderParameters,
 }
@@ -96,10 +108,13 @@ impl MBTilesReader {
 		let manager = SqliteConnectionManager::file(path);
 		let pool = Pool::builder().max_size(10).build(manager)?;
 		let parameters = TilesReaderParameters::new(PBF, Uncompressed, TileBBoxPyramid::new_empty());
+		let schema = MBTilesSchema::detect(&*pool.get()?)?;
+		trace!("schema: {schema:?}");
 
 		let mut reader = MBTilesReader {
 			name: String::from(path.to_str().unwrap()),
 			pool,
+			schema,
 			meta_data: None,
 			parameters,
 		};
@@ -166,29 +181,54 @@ impl MBTilesReader {
 		self.parameters.tile_compression = compression?;
 		self.parameters.bbox_pyramid = pyramide;
 
+		if let Some(sample) = self.get_sample_tile()? {
+			correct_parameters_by_sample(&mut self.parameters, &sample, &self.name);
+		}
+
 		Ok(())
 	}
 
-	/// Executes a simple query on the MBTiles database.
+	/// Reads any tile to verify the tile format and compression.
+	///
+	/// # Errors
+	/// Returns an error if there is an issue querying the database.
+	fn get_sample_tile(&self) -> Result<Option<Blob>> {
+		let conn = self.pool.get()?;
+		// rows without data can not be sniffed, so they are skipped
+		let mut stmt = conn.prepare(&format!(
+			"SELECT tile_data FROM {} WHERE tile_data IS NOT NULL LIMIT 1",
+			self.schema.get_tile_source()
+		))?;
+		let mut rows = stmt.query([])?;
+		Ok(match rows.next()? {
+			Some(row) => row.get::<_, Option<Vec<u8>>>(0)?.map(Blob::from),
+			None => None,
+		})
+	}
+
+	/// Executes a simple query on the table of tile coordinates.
 	///
 	/// # Arguments
 	/// * `sql1` - The SQL query to execute.
 	/// * `sql2` - Additional SQL conditions.
 	///
+	/// Returns `None` if the result is `NULL`, e.g. when no rows match.
+	///
 	/// # Errors
 	/// Returns an error if there is an issue executing the query.
-	fn simple_query(&self, sql1: &str, sql2: &str) -> Result<i32> {
+	fn simple_query(&self, sql1: &str, sql2: &str) -> Result<Option<i32>> {
+		let table = self.schema.get_coord_table();
 		let sql = if sql2.is_empty() {
-			format!("SELECT {sql1} FROM tiles")
+			format!("SELECT {sql1} FROM {table}")
 		} else {
-			format!("SELECT {sql1} FROM tiles WHERE {sql2}")
+			format!("SELECT {sql1} FROM {table} WHERE {sql2}")
 		};
 
 		trace!("SQL: {}", sql);
 
 		let conn = self.pool.get()?;
 		let mut stmt = conn.prepare(&sql)?;
-		Ok(stmt.query_row([], |row| row.get::<_, i32>(0))?)
+		Ok(stmt.query_row([], |row| row.get::<_, Option<i32>>(0))?)
 	}
 
 	/// Gets the bounding box pyramid from the MBTiles database.
@@ -200,14 +240,29 @@ impl MBTilesReader {
 
 		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
 
-		let z0 = self.simple_query("MIN(zoom_level)", "")?;
-		let z1 = self.simple_query("MAX(zoom_level)", "")?;
+		let (z0, z1) = match (
+			self.simple_query("MIN(zoom_level)", "")?,
+			self.simple_query("MAX(zoom_level)", "")?,
+		) {
+			(Some(z0), Some(z1)) => (z0, z1),
+			_ => return Ok(bbox_pyramid),
+		};
 
 		let mut progress = get_progress_bar("get mbtiles bbox pyramid", (z1 - z0 + 1) as u64);
 
 		for z in z0..=z1 {
-			let x0 = self.simple_query("MIN(tile_column)", &format!("zoom_level = {z}"))?;
-			let x1 = self.simple_query("MAX(tile_column)", &format!("zoom_level = {z}"))?;
+			let level = format!("zoom_level = {z}");
+			let (x0, x1) = match (
+				self.simple_query("MIN(tile_column)", &level)?,
+				self.simple_query("MAX(tile_column)", &level)?,
+			) {
+				(Some(x0), Some(x1)) => (x0, x1),
+				_ => {
+					// zoom level without tiles
+					progress.inc(1);
+					continue;
+				}
+			};
 			let xc = (x0 + x1) / 2;
 
 			/*
@@ -234,11 +289,20 @@ impl MBTilesReader {
 			*/
 
 			let sql_prefix = format!("zoom_level = {z} AND tile_");
-			let mut y0 = self.simple_query("MIN(tile_row)", &format!("{sql_prefix}column = {xc}"))?;
-			let mut y1 = self.simple_query("MAX(tile_row)", &format!("{sql_prefix}column = {xc}"))?;
+			let y0 = self.simple_query("MIN(tile_row)", &format!("{sql_prefix}column = {xc}"))?;
+			let y1 = self.simple_query("MAX(tile_row)", &format!("{sql_prefix}column = {xc}"))?;
 
-			y0 = self.simple_query("MIN(tile_row)", &format!("{sql_prefix}row <= {y0}"))?;
-			y1 = self.simple_query("MAX(tile_row)", &format!("{sql_prefix}row >= {y1}"))?;
+			// if the middle column is empty, there are no estimates and the slow queries are used
+			let y0 = match y0 {
+				Some(y0) => self.simple_query("MIN(tile_row)", &format!("{sql_prefix}row <= {y0}"))?,
+				None => self.simple_query("MIN(tile_row)", &level)?,
+			}
+			.unwrap_or(0);
+			let y1 = match y1 {
+				Some(y1) => self.simple_query("MAX(tile_row)", &format!("{sql_prefix}row >= {y1}"))?,
+				None => self.simple_query("MAX(tile_row)", &level)?,
+			}
+			.unwrap_or(0);
 
 			let max_value = 2i32.pow(z as u32) - 1;
 
@@ -259,6 +323,53 @@ impl MBTilesReader {
 
 		Ok(bbox_pyramid)
 	}
+
+	/// Returns type, name and SQL of all tables, views, indexes and triggers.
+	#[cfg(feature = "cli")]
+	fn get_schema(&self) -> Result<Vec<(String, String, Option<String>)>> {
+		let conn = self.pool.get()?;
+		let mut stmt =
+			conn.prepare("SELECT type, name, sql FROM sqlite_master ORDER BY type, name")?;
+		let entries = stmt
+			.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(entries)
+	}
+
+	/// Checks whether the table of tile coordinates has a unique index on all three coordinates.
+	#[cfg(feature = "cli")]
+	fn has_tile_index(&self) -> Result<bool> {
+		let conn = self.pool.get()?;
+		let mut stmt = conn.prepare("SELECT name FROM pragma_index_list(?) WHERE \"unique\" = 1")?;
+		let indexes = stmt
+			.query_map([self.schema.get_coord_table()], |row| {
+				row.get::<_, String>(0)
+			})?
+			.collect::<Result<Vec<_>, _>>()?;
+
+		for index in indexes {
+			let mut stmt = conn.prepare("SELECT name FROM pragma_index_info(?) ORDER BY seqno")?;
+			let mut columns = stmt
+				.query_map([&index], |row| row.get::<_, String>(0))?
+				.collect::<Result<Vec<_>, _>>()?;
+			columns.sort();
+			if columns == ["tile_column", "tile_row", "zoom_level"] {
+				return Ok(true);
+			}
+		}
+		Ok(false)
+	}
+
+	/// Returns the names of all metadata entries.
+	#[cfg(feature = "cli")]
+	fn get_metadata_keys(&self) -> Result<Vec<String>> {
+		let conn = self.pool.get()?;
+		let mut stmt = conn.prepare("SELECT name FROM metadata ORDER BY name")?;
+		let keys = stmt
+			.query_map([], |row| row.get::<_, String>(0))?
+			.collect::<Result<Vec<_>, _>>()?;
+		Ok(keys)
+	}
 }
 
 #[async_trait]
@@ -305,9 +416,10 @@ impl TilesReaderTrait for MBTilesReader {
 		let z = coord.z as u32;
 
 		let conn = self.pool.get()?;
-		let mut stmt = conn.prepare(
-			"SELECT tile_data FROM tiles WHERE tile_column = ? AND tile_row = ? AND zoom_level = ?",
-		)?;
+		let mut stmt = conn.prepare(&format!(
+			"SELECT tile_data FROM {} WHERE tile_column = ? AND tile_row = ? AND zoom_level = ?",
+			self.schema.get_tile_source()
+		))?;
 
 		if let Ok(vec) = stmt.query_row([x, y, z], |row| row.get::<_, Vec<u8>>(0)) {
 			Ok(Some(Blob::from(vec)))
@@ -336,9 +448,10 @@ impl TilesReaderTrait for MBTilesReader {
 
 		let conn = self.pool.get().unwrap();
 		let mut stmt = conn
-			 .prepare(
-					"SELECT tile_column, tile_row, zoom_level, tile_data FROM tiles WHERE tile_column >= ? AND tile_column <= ? AND tile_row >= ? AND tile_row <= ? AND zoom_level = ?",
-			 )
+			 .prepare(&format!(
+					"SELECT tile_column, tile_row, zoom_level, tile_data FROM {} WHERE tile_column >= ? AND tile_column <= ? AND tile_row >= ? AND tile_row <= ? AND zoom_level = ?",
+					self.schema.get_tile_source()
+			 ))
 			 .unwrap();
 
 		let vec: Vec<(TileCoord3, Blob)> = stmt
@@ -377,6 +490,76 @@ impl TilesReaderTrait for MBTilesReader {
 	fn get_name(&self) -> &str {
 		&self.name
 	}
+
+	// deep probe of the SQLite schema
+	#[cfg(feature = "cli")]
+	async fn probe_container(&mut self, print: &PrettyPrint) -> Result<()> {
+		let (page_count, page_size) = {
+			let conn = self.pool.get()?;
+			let page_count: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+			let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+			(page_count, page_size)
+		};
+		print
+			.add_key_value("database size", &(page_count * page_size))
+			.await;
+
+		let schema = self.get_schema()?;
+		for (kind, name, sql) in schema.iter() {
+			let sql = sql.as_deref().map(|sql| sql.split_whitespace().join(" "));
+			print
+				.add_key_value(&format!("{kind} {name:?}"), &sql.unwrap_or_default())
+				.await;
+		}
+
+		print.add_key_value("layout", &self.schema).await;
+
+		let coord_table = self.schema.get_coord_table();
+		let coord_is_table = schema
+			.iter()
+			.any(|(kind, name, _)| kind == "table" && name == coord_table);
+		if coord_is_table && !self.has_tile_index()? {
+			print
+				.add_warning(&format!("table {coord_table:?} has no unique index on (zoom_level, tile_column, tile_row), so reading tiles will be slow"))
+				.await;
+		}
+
+		let keys = self.get_metadata_keys()?;
+		print.add_key_value("metadata keys", &keys).await;
+		for key in ["name", "format"] {
+			if !keys.iter().any(|k| k == key) {
+				print
+					.add_warning(&format!("metadata is missing the required key {key:?}"))
+					.await;
+			}
+		}
+
+		Ok(())
+	}
+
+	// deep probe of tile sizes
+	#[cfg(feature = "cli")]
+	async fn probe_tiles(&mut self, print: &PrettyPrint) -> Result<()> {
+		let mut stats = TileSizeStats::new();
+		{
+			let conn = self.pool.get()?;
+			let mut stmt = conn.prepare(&format!(
+				"SELECT zoom_level, LENGTH(tile_data) FROM {} WHERE tile_data IS NOT NULL",
+				self.schema.get_tile_source()
+			))?;
+			let mut rows = stmt.query([])?;
+			while let Some(row) = rows.next()? {
+				stats.add(row.get(0)?, row.get(1)?);
+			}
+		}
+		stats.print(print).await;
+		Ok(())
+	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 impl std::fmt::Debug for MBTilesReader {
@@ -435,22 +618,85 @@ pub mod tests {
 		Ok(())
 	}
 
-	// Test tile fetching
+	#[tokio::test]
+	async fn null_tile_data() -> Result<()> {
+		use crate::container::{MBTilesWriter, MockTilesReader, TilesWriterTrait};
+		use assert_fs::NamedTempFile;
+
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			PNG,
+			Uncompressed,
+			TileBBoxPyramid::new_full(1),
+		))?;
+		let filename = NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path(&mut mock_reader, &filename).await?;
+
+		// the first row, which is used for sniffing, has no data
+		let reader = MBTilesReader::open_path(&filename)?;
+		reader.pool.get()?.execute_batch(
+			"UPDATE tiles SET tile_data = NULL WHERE rowid = (SELECT MIN(rowid) FROM tiles);",
+		)?;
+		drop(reader);
+
+		let reader = MBTilesReader::open_path(&filename)?;
+		assert_eq!(reader.get_parameters().tile_format, PNG);
+		assert_eq!(reader.get_parameters().tile_compression, Uncompressed);
+
+		#[cfg(feature = "cli")]
+		{
+			let mut reader = reader;
+			let mut printer = crate::utils::PrettyPrint::new();
+			reader
+				.probe_tiles(&printer.get_category("tiles").await)
+				.await?;
+		}
+
+		Ok(())
+	}
+
 	#[cfg(feature = "cli")]
 	#[tokio::test]
 	async fn probe() -> Result<()> {
-		use crate::utils::PrettyPrint;
+		use crate::{
+			container::{MBTilesWriter, MockTilesReader, TilesWriterTrait},
+			utils::PrettyPrint,
+		};
+		use assert_fs::NamedTempFile;
 
-		let mut reader = MBTilesReader::open_path(&PATH)?;
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			PNG,
+			Uncompressed,
+			TileBBoxPyramid::new_full(2),
+		))?;
+		let filename = NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path(&mut mock_reader, &filename).await?;
+
+		let mut reader = MBTilesReader::open_path(&filename)?;
 
 		let mut printer = PrettyPrint::new();
 		reader
 			.probe_container(&printer.get_category("container").await)
 			.await?;
-		assert_eq!(
-			printer.as_string().await,
-			"container:\n   deep container probing is not implemented for this container format\n"
-		);
+		let text = printer.as_string().await;
+		assert!(text.starts_with("container:\n   database size: "), "{text}");
+		assert!(text.contains("\n   layout: Flat\n"), "{text}");
+		assert!(text.contains("\n   index \"tile_index\": \"CREATE UNIQUE INDEX tile_index on tiles (zoom_level, tile_column, tile_row)\"\n"));
+		assert!(text.contains("\n   table \"tiles\": \"CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB, UNIQUE (zoom_level, tile_column, tile_row))\"\n"));
+		assert!(text.ends_with("\n   metadata keys: [\"bounds\", \"center\", \"format\", \"maxzoom\", \"minzoom\", \"name\", \"type\"]\n"), "{text}");
+
+		reader.pool.get()?.execute_batch(
+			"ALTER TABLE tiles RENAME TO tiles_old;
+				CREATE TABLE tiles AS SELECT * FROM tiles_old;
+				DROP TABLE tiles_old;
+				DELETE FROM metadata WHERE name = 'name';",
+		)?;
+		let mut printer = PrettyPrint::new();
+		reader
+			.probe_container(&printer.get_category("container").await)
+			.await?;
+		let text = printer.as_string().await;
+		assert!(text.contains("\n   table \"tiles\" has no unique index on (zoom_level, tile_column, tile_row), so reading tiles will be slow\n"));
+		assert!(text.ends_with("\n   metadata is missing the required key \"name\"\n"));
 
 		let mut printer = PrettyPrint::new();
 		reader
@@ -458,7 +704,7 @@ pub mod tests {
 			.await?;
 		assert_eq!(
 			printer.as_string().await,
-			"tiles:\n   deep tiles probing is not implemented for this container format\n"
+			"tiles:\n   tile count: 21\n   sum of tile sizes: 2_163\n   level 0: count: 1, average: 103, min: 103, max: 103, histogram: [<1K: 1]\n   level 1: count: 4, average: 103, min: 103, max: 103, histogram: [<1K: 4]\n   level 2: count: 16, average: 103, min: 103, max: 103, histogram: [<1K: 16]\n"
 		);
 
 		Ok(())
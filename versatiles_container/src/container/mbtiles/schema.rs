@@ -0,0 +1,79 @@
+//! Table layouts of MBTiles files.
+//!
+//! Besides the flat `tiles` table, many tools write a deduplicated layout: table `images` stores every
+//! unique tile once, table `map` references it by `tile_id`, and a view `tiles` joins both for compatibility.
+
+use anyhow::Result;
+use r2d2_sqlite::rusqlite::Connection;
+
+/// The table layout of an MBTiles file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MBTilesSchema {
+	/// a single table `tiles` containing coordinates and tile data
+	#[default]
+	Flat,
+	/// tables `map` and `images`, joined by `tile_id`, and a view `tiles`
+	Deduplicated,
+}
+
+impl MBTilesSchema {
+	/// Detects the layout by looking for the tables `map` and `images`.
+	pub(super) fn detect(conn: &Connection) -> Result<MBTilesSchema> {
+		let count: u32 = conn.query_row(
+			"SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('map', 'images')",
+			[],
+			|row| row.get(0),
+		)?;
+		Ok(if count == 2 {
+			MBTilesSchema::Deduplicated
+		} else {
+			MBTilesSchema::Flat
+		})
+	}
+
+	/// SQL statements to create all tables, indexes and views.
+	pub(super) fn get_create_sql(&self) -> &'static str {
+		match self {
+			MBTilesSchema::Flat => "CREATE TABLE metadata (name TEXT, value TEXT, UNIQUE (name));
+				CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB, UNIQUE (zoom_level, tile_column, tile_row));
+				CREATE UNIQUE INDEX tile_index on tiles (zoom_level, tile_column, tile_row);",
+			MBTilesSchema::Deduplicated => "CREATE TABLE metadata (name TEXT, value TEXT, UNIQUE (name));
+				CREATE TABLE map (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_id TEXT);
+				CREATE UNIQUE INDEX map_index ON map (zoom_level, tile_column, tile_row);
+				CREATE TABLE images (tile_data BLOB, tile_id TEXT);
+				CREATE UNIQUE INDEX images_id ON images (tile_id);
+				CREATE VIEW tiles AS SELECT map.zoom_level AS zoom_level, map.tile_column AS tile_column, map.tile_row AS tile_row, images.tile_data AS tile_data FROM map JOIN images ON images.tile_id = map.tile_id;",
+		}
+	}
+
+	/// The table containing the tile coordinates. Querying it directly is much faster than querying the view.
+	pub(super) fn get_coord_table(&self) -> &'static str {
+		match self {
+			MBTilesSchema::Flat => "tiles",
+			MBTilesSchema::Deduplicated => "map",
+		}
+	}
+
+	/// The source of coordinates and tile data, usable in a `FROM` clause.
+	pub(super) fn get_tile_source(&self) -> &'static str {
+		match self {
+			MBTilesSchema::Flat => "tiles",
+			MBTilesSchema::Deduplicated => "map JOIN images ON images.tile_id = map.tile_id",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect() -> Result<()> {
+		for schema in [MBTilesSchema::Flat, MBTilesSchema::Deduplicated] {
+			let conn = Connection::open_in_memory()?;
+			conn.execute_batch(schema.get_create_sql())?;
+			assert_eq!(MBTilesSchema::detect(&conn)?, schema);
+		}
+		Ok(())
+	}
+}
@@ -4,7 +4,10 @@
 //!
 //! ## Features
 //! - Supports writing metadata and tile data in multiple formats and compressions.
+//! - Derives the metadata `bounds`, `center`, `minzoom` and `maxzoom` from the tiles, and copies `name`, `attribution` and `vector_layers` from the source.
 //! - Ensures the necessary tables and indices are created in the SQLite database.
+//! - Updates existing files, replacing tiles and merging metadata.
+//! - Optionally stores each unique tile only once, using the deduplicated schema with `map` and `images` tables.
 //! - Provides progress feedback during the write process.
 //!
 //! ## Usage
@@ -28,21 +31,32 @@
 //! ## Testing
 //! This module includes comprehensive tests to ensure the correct functionality of writing metadata, handling different file formats, and verifying the database structure.
 
+use super::{MBTilesReader, MBTilesSchema};
 use crate::{
-	container::TilesWriterTrait,
-	types::{Blob, TileCompression, TileCoord3, TileFormat, TilesReaderTrait},
-	utils::{io::DataWriterTrait, progress::get_progress_bar},
+	container::{merge_meta, TilesWriterTrait},
+	types::{
+		Blob, TileCompression, TileCoord3, TileFormat, TilesReaderParameters, TilesReaderTrait,
+	},
+	utils::{
+		get_fnv_hash, io::DataWriterTrait, parse_json, progress::get_progress_bar, JsonValue,
+		TransformCoord,
+	},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, ensure, Result};
 use async_trait::async_trait;
 use itertools::Itertools;
+use log::warn;
 use r2d2::Pool;
-use r2d2_sqlite::{rusqlite::params, SqliteConnectionManager};
-use std::{fs::remove_file, path::Path};
+use r2d2_sqlite::{
+	rusqlite::{params, Transaction},
+	SqliteConnectionManager,
+};
+use std::{collections::BTreeMap, fs::remove_file, path::Path};
 
 /// A writer for creating and populating MBTiles databases.
 pub struct MBTilesWriter {
 	pool: Pool<SqliteConnectionManager>,
+	schema: MBTilesSchema,
 }
 
 impl MBTilesWriter {
@@ -50,27 +64,39 @@ impl MBTilesWriter {
 	///
 	/// # Arguments
 	/// * `path` - The path to the MBTiles file.
+	/// * `schema` - The table layout to create.
 	///
 	/// # Errors
 	/// Returns an error if the SQLite connection cannot be established or if the necessary tables cannot be created.
-	fn new(path: &Path) -> Result<Self> {
+	fn new(path: &Path, schema: MBTilesSchema) -> Result<Self> {
 		if path.exists() {
 			remove_file(path)?;
 		}
 		let manager = SqliteConnectionManager::file(path);
 		let pool = Pool::builder().max_size(10).build(manager)?;
 
-		pool.get()?.execute_batch(
-			"CREATE TABLE metadata (name TEXT, value TEXT, UNIQUE (name));
-			CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB, UNIQUE (zoom_level, tile_column, tile_row));
-			CREATE UNIQUE INDEX tile_index on tiles (zoom_level, tile_column, tile_row);",
-		)?;
+		pool.get()?.execute_batch(schema.get_create_sql())?;
 
-		Ok(MBTilesWriter { pool })
+		Ok(MBTilesWriter { pool, schema })
 	}
 
-	/// Adds multiple tiles to the MBTiles file within a single transaction.
-	///s
+	/// Opens an existing MBTiles file for updating.
+	///
+	/// # Arguments
+	/// * `path` - The path to the MBTiles file.
+	///
+	/// # Errors
+	/// Returns an error if the SQLite connection cannot be established.
+	fn open(path: &Path) -> Result<Self> {
+		let manager = SqliteConnectionManager::file(path);
+		let pool = Pool::builder().max_size(10).build(manager)?;
+		let schema = MBTilesSchema::detect(&*pool.get()?)?;
+
+		Ok(MBTilesWriter { pool, schema })
+	}
+
+	/// Adds multiple tiles to the MBTiles file within a single transaction. Existing tiles are replaced.
+	///
 	/// # Arguments
 	/// * `tiles` - A vector of tuples containing tile coordinates and tile data.
 	///
@@ -80,10 +106,25 @@ impl MBTilesWriter {
 		let mut conn = self.pool.get()?;
 		let transaction = conn.transaction()?;
 		for (coords, blob) in tiles {
-			transaction.execute(
-				"INSERT INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
-				params![coords.z, coords.x, coords.y, blob.as_slice()],
-			)?;
+			// MBTiles uses the TMS scheme, so rows are counted from the bottom
+			let mut coords = *coords;
+			coords.flip_y();
+
+			match self.schema {
+				MBTilesSchema::Flat => {
+					transaction.execute(
+						"INSERT OR REPLACE INTO tiles (zoom_level, tile_column, tile_row, tile_data) VALUES (?1, ?2, ?3, ?4)",
+						params![coords.z, coords.x, coords.y, blob.as_slice()],
+					)?;
+				}
+				MBTilesSchema::Deduplicated => {
+					let tile_id = insert_image(&transaction, blob)?;
+					transaction.execute(
+						"INSERT OR REPLACE INTO map (zoom_level, tile_column, tile_row, tile_id) VALUES (?1, ?2, ?3, ?4)",
+						params![coords.z, coords.x, coords.y, tile_id],
+					)?;
+				}
+			}
 		}
 		transaction.commit()?;
 		Ok(())
@@ -104,72 +145,114 @@ impl MBTilesWriter {
 		)?;
 		Ok(())
 	}
-}
 
-#[async_trait]
-impl TilesWriterTrait for MBTilesWriter {
-	/// Writes tiles and metadata to the MBTiles file.
+	/// Adds metadata to the MBTiles file, if the key does not exist yet.
+	///
+	/// # Arguments
+	/// * `name` - The metadata key.
+	/// * `value` - The metadata value.
+	///
+	/// # Errors
+	/// Returns an error if the metadata cannot be inserted.
+	fn add_metadata(&self, name: &str, value: &str) -> Result<()> {
+		self.pool.get()?.execute(
+			"INSERT OR IGNORE INTO metadata (name, value) VALUES (?1, ?2)",
+			params![name, value],
+		)?;
+		Ok(())
+	}
+
+	/// Writes tiles and metadata to the MBTiles file, using the given table layout.
 	///
 	/// # Arguments
 	/// * `reader` - The reader from which to fetch tiles and metadata.
 	/// * `path` - The path to the MBTiles file.
+	/// * `schema` - The table layout, e.g. `MBTilesSchema::Deduplicated` to store each unique tile only once.
 	///
 	/// # Errors
 	/// Returns an error if the file format or compression is not supported, or if there are issues with writing to the SQLite database.
-	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
-		use TileCompression::*;
-		use TileFormat::*;
-
-		let mut writer = MBTilesWriter::new(path)?;
-
-		let parameters = reader.get_parameters().clone();
-
-		let format = match (parameters.tile_format, parameters.tile_compression) {
-			(JPG, Uncompressed) => "jpg",
-			(PBF, Gzip) => "pbf",
-			(PNG, Uncompressed) => "png",
-			(WEBP, Uncompressed) => "webp",
-			_ => bail!(
-				"combination of format ({}) and compression ({}) is not supported. MBTiles supports only uncompressed jpg/png/webp or gzipped pbf",
-				parameters.tile_format,
-				parameters.tile_compression
-			),
-		};
-
-		writer.set_metadata("name", reader.get_name())?;
-		writer.set_metadata("format", format)?;
-		writer.set_metadata("type", "baselayer")?;
-		writer.set_metadata("version", "3.0")?;
-		writer.set_metadata(
-			"bounds",
-			&std::slice::Iter::<f64>::join(
-				&mut reader.get_parameters().bbox_pyramid.get_geo_bbox().iter(),
-				",",
-			),
-		)?;
-		writer.set_metadata(
-			"minzoom",
-			&reader
-				.get_parameters()
-				.bbox_pyramid
-				.get_zoom_min()
-				.unwrap()
-				.to_string(),
-		)?;
-		writer.set_metadata(
-			"maxzoom",
-			&reader
-				.get_parameters()
-				.bbox_pyramid
-				.get_zoom_max()
-				.unwrap()
-				.to_string(),
+	pub async fn write_to_path_with_schema(
+		reader: &mut dyn TilesReaderTrait,
+		path: &Path,
+		schema: MBTilesSchema,
+	) -> Result<()> {
+		let metadata = get_metadata(
+			reader.get_parameters(),
+			reader.get_meta()?,
+			get_default_name(path),
 		)?;
 
-		if let Some(meta_data) = reader.get_meta()? {
-			writer.set_metadata("json", meta_data.as_str())?;
+		let mut writer = MBTilesWriter::new(path, schema)?;
+		for (name, value) in metadata.iter() {
+			writer.set_metadata(name, value)?;
+		}
+
+		writer.write_tiles(reader).await
+	}
+
+	/// Updates an existing MBTiles file: tiles of the reader are inserted or replace existing tiles.
+	///
+	/// The metadata `bounds`, `center`, `minzoom` and `maxzoom` are recalculated for the merged bbox pyramid,
+	/// and the vector layers are merged. Other existing metadata, like `name` or `attribution`, is kept.
+	///
+	/// # Arguments
+	/// * `reader` - The reader from which to fetch tiles and metadata.
+	/// * `path` - The path to the existing MBTiles file.
+	///
+	/// # Errors
+	/// Returns an error if the file does not exist, if the tile format or compression differ from the existing tiles,
+	/// or if there are issues with writing to the SQLite database.
+	pub async fn update_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		let existing = MBTilesReader::open_path(path)?;
+
+		let mut parameters = existing.get_parameters().clone();
+		let new_parameters = reader.get_parameters();
+		ensure!(
+			parameters.tile_format == new_parameters.tile_format
+				&& parameters.tile_compression == new_parameters.tile_compression,
+			"can not update {path:?} containing {} tiles with {} compression, using {} tiles with {} compression",
+			parameters.tile_format,
+			parameters.tile_compression,
+			new_parameters.tile_format,
+			new_parameters.tile_compression
+		);
+		parameters
+			.bbox_pyramid
+			.include_bbox_pyramid(&new_parameters.bbox_pyramid);
+
+		let meta = merge_meta(existing.get_meta()?, reader.get_meta()?);
+		drop(existing);
+
+		let metadata = get_metadata(&parameters, meta, get_default_name(path))?;
+
+		let mut writer = MBTilesWriter::open(path)?;
+		for (name, value) in metadata.iter() {
+			match *name {
+				"bounds" | "center" | "minzoom" | "maxzoom" | "json" => {
+					writer.set_metadata(name, value)?
+				}
+				_ => writer.add_metadata(name, value)?,
+			}
 		}
 
+		writer.write_tiles(reader).await?;
+
+		if writer.schema == MBTilesSchema::Deduplicated {
+			// remove images that are no longer referenced by replaced tiles
+			writer.pool.get()?.execute(
+				"DELETE FROM images WHERE NOT EXISTS (SELECT 1 FROM map WHERE map.tile_id = images.tile_id)",
+				[],
+			)?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes all tiles of the reader.
+	///
+	/// # Errors
+	/// Returns an error if there are issues with writing to the SQLite database.
+	async fn write_tiles(&mut self, reader: &mut dyn TilesReaderTrait) -> Result<()> {
 		let bbox_pyramid = reader.get_parameters().bbox_pyramid.clone();
 		let mut progress = get_progress_bar("converting tiles", bbox_pyramid.count_tiles());
 
@@ -178,7 +261,7 @@ impl TilesWriterTrait for MBTilesWriter {
 
 			stream
 				.for_each_buffered(2000, |v| {
-					writer.add_tiles(&v).unwrap();
+					self.add_tiles(&v).unwrap();
 					progress.inc(v.len() as u64)
 				})
 				.await;
@@ -188,6 +271,155 @@ impl TilesWriterTrait for MBTilesWriter {
 
 		Ok(())
 	}
+}
+
+/// Uses the file name without extension as name of the tileset.
+fn get_default_name(path: &Path) -> &str {
+	path
+		.file_stem()
+		.and_then(|name| name.to_str())
+		.unwrap_or_default()
+}
+
+/// Derives the MBTiles metadata from the parameters and the meta data of the source.
+///
+/// `bounds`, `center`, `minzoom` and `maxzoom` are calculated from the bbox pyramid. `name`, `description`,
+/// `attribution`, `type` and `version` are copied from the source meta data, if available.
+/// For vector tiles the row `json` contains the `vector_layers` of the source meta data.
+///
+/// # Errors
+/// Returns an error if the combination of tile format and compression is not supported, or if the bbox pyramid is empty.
+fn get_metadata(
+	parameters: &TilesReaderParameters,
+	meta: Option<Blob>,
+	default_name: &str,
+) -> Result<BTreeMap<&'static str, String>> {
+	use TileCompression::*;
+	use TileFormat::*;
+
+	let format = match (parameters.tile_format, parameters.tile_compression) {
+		(JPG, Uncompressed) => "jpg",
+		(PBF, Gzip) => "pbf",
+		(PNG, Uncompressed) => "png",
+		(WEBP, Uncompressed) => "webp",
+		_ => bail!(
+			"combination of format ({}) and compression ({}) is not supported. MBTiles supports only uncompressed jpg/png/webp or gzipped pbf",
+			parameters.tile_format,
+			parameters.tile_compression
+		),
+	};
+
+	let pyramid = &parameters.bbox_pyramid;
+	let (zoom_min, zoom_max) = match (pyramid.get_zoom_min(), pyramid.get_zoom_max()) {
+		(Some(zoom_min), Some(zoom_max)) => (zoom_min, zoom_max),
+		_ => bail!("can not write MBTiles without any tiles"),
+	};
+	let bounds = pyramid.get_geo_bbox();
+	let center_zoom = pyramid.get_good_zoom().unwrap_or(zoom_min);
+
+	let mut metadata = BTreeMap::new();
+	metadata.insert("format", format.to_string());
+	metadata.insert("bounds", bounds.iter().join(","));
+	metadata.insert(
+		"center",
+		format!(
+			"{},{},{center_zoom}",
+			(bounds[0] + bounds[2]) / 2.0,
+			(bounds[1] + bounds[3]) / 2.0
+		),
+	);
+	metadata.insert("minzoom", zoom_min.to_string());
+	metadata.insert("maxzoom", zoom_max.to_string());
+	metadata.insert("name", default_name.to_string());
+	metadata.insert("type", String::from("baselayer"));
+
+	let meta = match meta.map(|blob| parse_json(blob.as_str())) {
+		Some(Ok(JsonValue::Object(meta))) => meta,
+		Some(_) => {
+			warn!("meta data is not a JSON object, so it is not copied to MBTiles");
+			BTreeMap::new()
+		}
+		None => BTreeMap::new(),
+	};
+
+	for key in ["name", "description", "attribution", "version"] {
+		if let Some(JsonValue::Str(value)) = meta.get(key) {
+			metadata.insert(key, value.to_string());
+		}
+	}
+	if let Some(JsonValue::Str(value)) = meta.get("type") {
+		if value == "overlay" || value == "baselayer" {
+			metadata.insert("type", value.to_string());
+		}
+	}
+
+	if format == "pbf" {
+		match meta.get("vector_layers") {
+			Some(vector_layers @ JsonValue::Array(_)) => {
+				let json = JsonValue::from(vec![("vector_layers", vector_layers.clone())]);
+				metadata.insert("json", json.stringify());
+			}
+			_ => {
+				warn!("meta data contains no vector_layers, but MBTiles requires them for vector tiles")
+			}
+		}
+	}
+
+	Ok(metadata)
+}
+
+/// Stores the tile data in the `images` table of the deduplicated schema, if it is not stored yet, and returns its `tile_id`.
+///
+/// The `tile_id` is the hash of the tile data. If the hash is already used by different data,
+/// a suffix is appended, so a hash collision can never map a tile to the data of another tile.
+///
+/// # Errors
+/// Returns an error if there are issues with the SQLite database.
+fn insert_image(transaction: &Transaction, blob: &Blob) -> Result<String> {
+	let hash = get_tile_hash(blob);
+	let mut suffix = 0;
+	loop {
+		let tile_id = match suffix {
+			0 => hash.clone(),
+			_ => format!("{hash}-{suffix}"),
+		};
+		let inserted = transaction.execute(
+			"INSERT OR IGNORE INTO images (tile_id, tile_data) VALUES (?1, ?2)",
+			params![tile_id, blob.as_slice()],
+		)?;
+		if inserted > 0 {
+			return Ok(tile_id);
+		}
+		let stored: Vec<u8> = transaction.query_row(
+			"SELECT tile_data FROM images WHERE tile_id = ?1",
+			[&tile_id],
+			|row| row.get(0),
+		)?;
+		if stored == blob.as_slice() {
+			return Ok(tile_id);
+		}
+		suffix += 1;
+	}
+}
+
+/// Calculates the hash of the tile data as hex string, used as `tile_id` in the deduplicated schema.
+fn get_tile_hash(blob: &Blob) -> String {
+	format!("{:032x}", get_fnv_hash(blob.as_slice()))
+}
+
+#[async_trait]
+impl TilesWriterTrait for MBTilesWriter {
+	/// Writes tiles and metadata to the MBTiles file, using a flat `tiles` table.
+	///
+	/// # Arguments
+	/// * `reader` - The reader from which to fetch tiles and metadata.
+	/// * `path` - The path to the MBTiles file.
+	///
+	/// # Errors
+	/// Returns an error if the file format or compression is not supported, or if there are issues with writing to the SQLite database.
+	async fn write_to_path(reader: &mut dyn TilesReaderTrait, path: &Path) -> Result<()> {
+		MBTilesWriter::write_to_path_with_schema(reader, path, MBTilesSchema::Flat).await
+	}
 
 	/// Not implemented: Writes tiles and metadata to a generic data writer.
 	async fn write_to_writer(
@@ -203,9 +435,10 @@ mod tests {
 	use super::*;
 	use crate::{
 		container::{MBTilesReader, MockTilesReader, MockTilesWriter},
-		types::{TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
+		types::{TileBBox, TileBBoxPyramid, TileCompression, TileFormat, TilesReaderParameters},
 	};
 	use assert_fs::NamedTempFile;
+	use r2d2_sqlite::rusqlite::Connection;
 
 	#[tokio::test]
 	async fn read_write() -> Result<()> {
@@ -224,4 +457,227 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn read_write_deduplicated() -> Result<()> {
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
+			bbox_pyramid: TileBBoxPyramid::new_full(4),
+			tile_compression: TileCompression::Uncompressed,
+			tile_format: TileFormat::PNG,
+		})?;
+
+		let filename = NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path_with_schema(
+			&mut mock_reader,
+			&filename,
+			MBTilesSchema::Deduplicated,
+		)
+		.await?;
+
+		let conn = Connection::open(&filename)?;
+		let count = |table: &str| -> Result<u32> {
+			Ok(
+				conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+					row.get(0)
+				})?,
+			)
+		};
+		assert_eq!(count("map")?, 341);
+		assert_eq!(count("images")?, 1);
+		assert_eq!(count("tiles")?, 341);
+
+		let mut reader = MBTilesReader::open_path(&filename)?;
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64), 4: [0,0,15,15] (256)]"
+		);
+		MockTilesWriter::write(&mut reader).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn deduplicated_hash_collision() -> Result<()> {
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full(1),
+		))?;
+		let tile = mock_reader
+			.get_tile_data(&TileCoord3::new(0, 0, 0)?)
+			.await?
+			.unwrap();
+
+		let filename = NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path_with_schema(
+			&mut mock_reader,
+			&filename,
+			MBTilesSchema::Deduplicated,
+		)
+		.await?;
+
+		// other data is stored under the hash of the tile, as if they collided
+		let conn = Connection::open(&filename)?;
+		conn.execute("UPDATE images SET tile_data = x'00'", [])?;
+
+		MBTilesWriter::update_path(&mut mock_reader, &filename).await?;
+
+		let tile_ids: Vec<String> = conn
+			.prepare("SELECT tile_id FROM images")?
+			.query_map([], |row| row.get(0))?
+			.collect::<Result<_, _>>()?;
+		assert_eq!(tile_ids, [format!("{}-1", get_tile_hash(&tile))]);
+
+		let reader = MBTilesReader::open_path(&filename)?;
+		assert_eq!(
+			reader.get_tile_data(&TileCoord3::new(1, 1, 1)?).await?,
+			Some(tile)
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn tms_rows() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.set_level_bbox(TileBBox::new(3, 1, 0, 2, 1)?);
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			bbox_pyramid.clone(),
+		))?;
+
+		let filename = NamedTempFile::new("temp.mbtiles")?;
+		MBTilesWriter::write_to_path(&mut mock_reader, &filename).await?;
+
+		let conn = Connection::open(&filename)?;
+		let rows: (u32, u32) = conn.query_row(
+			"SELECT MIN(tile_row), MAX(tile_row) FROM tiles",
+			[],
+			|row| Ok((row.get(0)?, row.get(1)?)),
+		)?;
+		assert_eq!(rows, (6, 7));
+
+		let reader = MBTilesReader::open_path(&filename)?;
+		assert_eq!(reader.get_parameters().bbox_pyramid, bbox_pyramid);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn update() -> Result<()> {
+		for schema in [MBTilesSchema::Flat, MBTilesSchema::Deduplicated] {
+			let get_mock_reader = |format: TileFormat, pyramid: TileBBoxPyramid| {
+				MockTilesReader::new_mock(TilesReaderParameters::new(
+					format,
+					TileCompression::Uncompressed,
+					pyramid,
+				))
+			};
+
+			let filename = NamedTempFile::new("country.mbtiles")?;
+			let mut mock_reader = get_mock_reader(TileFormat::PNG, TileBBoxPyramid::new_full(1))?;
+			MBTilesWriter::write_to_path_with_schema(&mut mock_reader, &filename, schema).await?;
+
+			let conn = Connection::open(&filename)?;
+			conn.execute(
+				"UPDATE metadata SET value = 'Country' WHERE name = 'name'",
+				[],
+			)?;
+
+			let mut pyramid = TileBBoxPyramid::new_empty();
+			pyramid.set_level_bbox(TileBBox::new(1, 1, 1, 1, 1)?);
+			pyramid.set_level_bbox(TileBBox::new(3, 5, 1, 6, 1)?);
+			let mut mock_reader = get_mock_reader(TileFormat::PNG, pyramid)?;
+			MBTilesWriter::update_path(&mut mock_reader, &filename).await?;
+
+			let reader = MBTilesReader::open_path(&filename)?;
+			assert_eq!(
+				format!("{:?}", reader.get_parameters().bbox_pyramid),
+				"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 3: [5,1,6,1] (2)]"
+			);
+
+			let get_metadata = |name: &str| -> Result<String> {
+				Ok(
+					conn.query_row("SELECT value FROM metadata WHERE name = ?", [name], |row| {
+						row.get(0)
+					})?,
+				)
+			};
+			assert_eq!(get_metadata("name")?, "Country");
+			assert_eq!(get_metadata("minzoom")?, "0");
+			assert_eq!(get_metadata("maxzoom")?, "3");
+
+			let count = |sql: &str| -> Result<u32> { Ok(conn.query_row(sql, [], |row| row.get(0))?) };
+			assert_eq!(count("SELECT COUNT(*) FROM tiles")?, 7);
+			if schema == MBTilesSchema::Deduplicated {
+				assert_eq!(count("SELECT COUNT(*) FROM images")?, 1);
+			}
+
+			let mut mock_reader = get_mock_reader(TileFormat::JPG, TileBBoxPyramid::new_full(1))?;
+			assert!(MBTilesWriter::update_path(&mut mock_reader, &filename)
+				.await
+				.is_err());
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn metadata() -> Result<()> {
+		let mut bbox_pyramid = TileBBoxPyramid::new_full(4);
+		bbox_pyramid.set_zoom_min(2);
+		let parameters =
+			TilesReaderParameters::new(TileFormat::PBF, TileCompression::Gzip, bbox_pyramid);
+		let meta = Blob::from(
+			"{\"name\":\"Test\",\"attribution\":\"OSM\",\"type\":\"overlay\",\"vector_layers\":[{\"id\":\"water\",\"fields\":{}}],\"other\":1}",
+		);
+
+		let metadata = get_metadata(&parameters, Some(meta), "file")?;
+		assert_eq!(
+			metadata.iter().map(|(k, v)| format!("{k}={v}")).join("\n"),
+			"attribution=OSM
+bounds=-180,-85.05112877980659,180,85.05112877980659
+center=0,0,4
+format=pbf
+json={\"vector_layers\":[{\"fields\":{},\"id\":\"water\"}]}
+maxzoom=4
+minzoom=2
+name=Test
+type=overlay"
+		);
+
+		let metadata = get_metadata(&parameters, None, "file")?;
+		assert_eq!(metadata.get("name").unwrap(), "file");
+		assert_eq!(metadata.get("type").unwrap(), "baselayer");
+		assert!(!metadata.contains_key("json"));
+
+		let parameters = TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Gzip,
+			TileBBoxPyramid::new_full(1),
+		);
+		assert!(get_metadata(&parameters, None, "file").is_err());
+
+		let parameters = TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_empty(),
+		);
+		assert!(get_metadata(&parameters, None, "file").is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn tile_hash() {
+		assert_eq!(
+			get_tile_hash(&Blob::new_empty()),
+			"6c62272e07bb014262b821756295c58d"
+		);
+		assert_ne!(
+			get_tile_hash(&Blob::from("a")),
+			get_tile_hash(&Blob::from("b"))
+		);
+	}
 }
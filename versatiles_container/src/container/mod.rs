@@ -33,14 +33,26 @@ mod mock;
 #[cfg(feature = "test")]
 pub use mock::*;
 
+mod overzoom;
+pub use overzoom::*;
+
 mod pmtiles;
 pub use pmtiles::*;
 
 mod tar;
 pub use tar::*;
 
+mod sniffing;
+pub(crate) use sniffing::*;
+
 pub mod tile_converter;
 
+mod tile_contents_probe;
+pub use tile_contents_probe::*;
+
+mod tile_size_stats;
+pub use tile_size_stats::*;
+
 mod directory;
 pub use directory::*;
 
@@ -0,0 +1,230 @@
+//! `overzoom` module serves tiles beyond the maximum zoom level of a container.
+//!
+//! Tiles below the maximum zoom level are cut out of their ancestor at the maximum zoom level:
+//! Vector tiles are rescaled and clipped, raster tiles are cropped and scaled up.
+//!
+//! # Example Usage
+//!
+//! ```rust
+//! use versatiles::container::{MockTilesReader, MockTilesReaderProfile, TilesOverzoomReader};
+//! use versatiles::types::{TileCoord3, TilesReaderTrait};
+//! use anyhow::Result;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     // the mock reader has tiles up to zoom level 4
+//!     let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+//!     let reader = TilesOverzoomReader::new_from_reader(reader.boxed(), 8)?;
+//!
+//!     let tile = reader.get_tile_data(&TileCoord3::new(100, 100, 8)?).await?;
+//!     assert!(tile.is_some());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::types::{
+	Blob, TileBBox, TileCompression, TileCoord3, TileFormat, TilesReaderParameters, TilesReaderTrait,
+};
+use crate::utils::{compress, decompress};
+use anyhow::{bail, ensure, Context, Result};
+use async_trait::async_trait;
+use versatiles_geometry::vector_tile::VectorTile;
+use versatiles_image::helper;
+
+/// A reader that serves tiles up to `max_zoom` by cutting them out of the tiles of another reader.
+#[derive(Debug)]
+pub struct TilesOverzoomReader {
+	reader: Box<dyn TilesReaderTrait>,
+	parameters: TilesReaderParameters,
+	// the maximum zoom level of the original reader
+	source_zoom: u8,
+	max_zoom: u8,
+	container_name: String,
+}
+
+impl TilesOverzoomReader {
+	/// Creates a new overzoom reader from an existing reader.
+	pub fn new_from_reader(
+		reader: Box<dyn TilesReaderTrait>,
+		max_zoom: u8,
+	) -> Result<TilesOverzoomReader> {
+		let container_name = format!("overzoom({})", reader.get_container_name());
+		let mut parameters = reader.get_parameters().clone();
+
+		let format = parameters.tile_format;
+		ensure!(
+			matches!(
+				format,
+				TileFormat::PBF | TileFormat::PNG | TileFormat::JPG | TileFormat::WEBP
+			),
+			"overzoom is only supported for vector tiles and PNG, JPEG or WebP images, not {format}"
+		);
+		ensure!(max_zoom <= 30, "overzoom level ({max_zoom}) must be <= 30");
+
+		let source_zoom = parameters
+			.bbox_pyramid
+			.get_zoom_max()
+			.context("can not overzoom an empty container")?;
+
+		let bbox = parameters.bbox_pyramid.get_level_bbox(source_zoom).clone();
+		for level in source_zoom + 1..=max_zoom {
+			let shift = level - source_zoom;
+			parameters.bbox_pyramid.set_level_bbox(TileBBox::new(
+				level,
+				bbox.x_min << shift,
+				bbox.y_min << shift,
+				((bbox.x_max + 1) << shift) - 1,
+				((bbox.y_max + 1) << shift) - 1,
+			)?);
+		}
+
+		Ok(TilesOverzoomReader {
+			reader,
+			parameters,
+			source_zoom,
+			max_zoom,
+			container_name,
+		})
+	}
+}
+
+#[async_trait]
+impl TilesReaderTrait for TilesOverzoomReader {
+	// the name is kept, because it is e.g. used to find the modification time of the file
+	fn get_name(&self) -> &str {
+		self.reader.get_name()
+	}
+
+	fn get_container_name(&self) -> &str {
+		&self.container_name
+	}
+
+	fn get_parameters(&self) -> &TilesReaderParameters {
+		&self.parameters
+	}
+
+	fn override_compression(&mut self, tile_compression: TileCompression) {
+		self.reader.override_compression(tile_compression);
+		self.parameters.tile_compression = tile_compression;
+	}
+
+	fn get_meta(&self) -> Result<Option<Blob>> {
+		self.reader.get_meta()
+	}
+
+	async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+		if coord.z <= self.source_zoom {
+			return self.reader.get_tile_data(coord).await;
+		}
+		if coord.z > self.max_zoom {
+			return Ok(None);
+		}
+
+		let level_diff = coord.z - self.source_zoom;
+		let ancestor = TileCoord3::new(
+			coord.x >> level_diff,
+			coord.y >> level_diff,
+			self.source_zoom,
+		)?;
+		let Some(blob) = self.reader.get_tile_data(&ancestor).await? else {
+			return Ok(None);
+		};
+
+		// position of the tile inside its ancestor
+		let x = coord.x - (ancestor.x << level_diff);
+		let y = coord.y - (ancestor.y << level_diff);
+		let format = self.parameters.tile_format;
+		let compression = self.parameters.tile_compression;
+
+		// decoding and encoding takes a while, so it must not block other tasks
+		tokio::task::spawn_blocking(move || {
+			let blob = decompress(blob, &compression)?;
+			let blob = match format {
+				TileFormat::PBF => VectorTile::from_blob(&blob)?
+					.overzoom(level_diff, x, y)?
+					.to_blob()?,
+				TileFormat::PNG | TileFormat::JPG | TileFormat::WEBP => {
+					helper::overzoom(&blob, format, level_diff, x, y)?
+				}
+				_ => bail!("overzoom is not supported for {format}"),
+			};
+			Ok(Some(compress(blob, &compression)?))
+		})
+		.await?
+		.with_context(|| format!("failed to overzoom tile {coord:?} from {ancestor:?}"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::container::{MockTilesReader, MockTilesReaderProfile};
+	use versatiles_image::png;
+
+	#[tokio::test]
+	async fn parameters() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let reader = TilesOverzoomReader::new_from_reader(reader.boxed(), 6)?;
+
+		let pyramid = &reader.get_parameters().bbox_pyramid;
+		assert_eq!(pyramid.get_zoom_max(), Some(6));
+		assert_eq!(pyramid.get_level_bbox(6), &TileBBox::new_full(6)?);
+		assert_eq!(reader.get_container_name(), "overzoom(dummy_container)");
+		assert_eq!(reader.get_name(), "dummy_name");
+
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Json)?;
+		assert!(TilesOverzoomReader::new_from_reader(reader.boxed(), 6).is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn raster_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let reader = TilesOverzoomReader::new_from_reader(reader.boxed(), 6)?;
+
+		let tile = reader.get_tile_data(&TileCoord3::new(50, 10, 6)?).await?;
+		let image = png::blob2image(&tile.unwrap())?;
+		assert_eq!((image.width(), image.height()), (256, 256));
+
+		assert!(reader
+			.get_tile_data(&TileCoord3::new(0, 0, 7)?)
+			.await?
+			.is_none());
+
+		let bbox = TileBBox::new(6, 0, 0, 1, 1)?;
+		assert_eq!(
+			reader
+				.get_bbox_tile_stream(bbox)
+				.await
+				.collect()
+				.await
+				.len(),
+			4
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn vector_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let original = reader
+			.get_tile_data(&TileCoord3::new(0, 0, 4)?)
+			.await?
+			.unwrap();
+		let reader = TilesOverzoomReader::new_from_reader(reader.boxed(), 6)?;
+
+		// unchanged up to the maximum zoom level of the container
+		let tile = reader.get_tile_data(&TileCoord3::new(0, 0, 4)?).await?;
+		assert_eq!(tile, Some(original));
+
+		let tile = reader
+			.get_tile_data(&TileCoord3::new(1, 2, 6)?)
+			.await?
+			.unwrap();
+		VectorTile::from_blob(&decompress(tile, &TileCompression::Gzip)?)?;
+
+		Ok(())
+	}
+}
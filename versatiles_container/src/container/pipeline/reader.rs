@@ -7,6 +7,8 @@ use crate::{
 	},
 	utils::io::DataReader,
 };
+#[cfg(feature = "cli")]
+use crate::{container::TileContentsProbe, utils::PrettyPrint};
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use futures::future::BoxFuture;
@@ -55,7 +57,16 @@ impl<'a> PipelineReader {
 			.with_context(|| format!("failed parsing {} as VPL", reader.get_name()))
 	}
 
-	#[cfg(test)]
+	/// Opens a PipelineReader from a vpl string.
+	///
+	/// # Arguments
+	///
+	/// * `vpl` - The vpl configuration.
+	/// * `dir` - The directory used to resolve relative file names in the vpl.
+	///
+	/// # Returns
+	///
+	/// * `Result<PipelineReader>` - The constructed PipelineReader or an error if the configuration is invalid.
 	pub async fn open_str(vpl: &str, dir: &Path) -> Result<PipelineReader> {
 		Self::from_str(vpl, "from str", dir)
 			.await
@@ -122,6 +133,11 @@ impl TilesReaderTrait for PipelineReader {
 	async fn get_bbox_tile_stream(&self, bbox: TileBBox) -> TileStream {
 		self.operation.get_bbox_tile_stream(bbox).await
 	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 impl std::fmt::Debug for PipelineReader {
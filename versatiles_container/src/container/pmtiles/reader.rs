@@ -42,7 +42,7 @@
 
 use super::types::{tile_id_to_coord, EntriesV3, HeaderV3, TileId};
 #[cfg(feature = "cli")]
-use crate::utils::PrettyPrint;
+use crate::{container::TileContentsProbe, utils::PrettyPrint};
 use crate::{
 	types::{
 		Blob, ByteRange, LimitedCache, TileBBoxPyramid, TileCompression, TileCoord3,
@@ -161,7 +161,7 @@ fn calc_bbox_pyramid(
 		for entry in entries.iter() {
 			if entry.range.length > 0 {
 				if entry.run_length > 0 {
-					for i in 1..=entry.run_length as u64 {
+					for i in 0..entry.run_length as u64 {
 						let coord = tile_id_to_coord(i + entry.tile_id)?;
 						bbox_pyramid.include_coord(&coord);
 					}
@@ -249,12 +249,16 @@ impl TilesReaderTrait for PMTilesReader {
 					));
 				} else {
 					let range = entry.range;
-					let mut cache = self.leaves_cache.lock().await;
-					dir_bytes = cache.get_or_set(&range, || {
-						let mut blob = self.leaves_bytes.read_range(&range)?;
-						blob = decompress(blob, &self.internal_compression)?;
-						Ok(Arc::new(blob))
-					})?;
+					let cached = self.leaves_cache.lock().await.get(&range);
+					dir_bytes = match cached {
+						Some(blob) => blob,
+						None => {
+							// decompress without locking the cache, so other requests are not blocked
+							let blob = self.leaves_bytes.read_range(&range)?;
+							let blob = Arc::new(decompress(blob, &self.internal_compression)?);
+							self.leaves_cache.lock().await.add(range, blob)
+						}
+					};
 				}
 			} else {
 				return Ok(None);
@@ -272,6 +276,11 @@ impl TilesReaderTrait for PMTilesReader {
 
 		Ok(())
 	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 #[cfg(test)]
@@ -302,7 +311,7 @@ mod tests {
 
 		assert_wildcard!(
 			format!("{:?}", reader.get_parameters()), 
-			"TilesReaderParameters { bbox_pyramid: [1: [0,0,0,0] (1), * 14: [8786,5360,8819,5387] (952)], tile_compression: Gzip, tile_format: PBF }"
+			"TilesReaderParameters { bbox_pyramid: [0: [0,0,0,0] (1), 1: [1,0,1,0] (1), * 14: [8787,5361,8818,5387] (864)], tile_compression: Gzip, tile_format: PBF }"
 		);
 
 		assert_eq!(
@@ -330,4 +339,30 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn bbox_pyramid_of_written_file() -> Result<()> {
+		use crate::container::{MockTilesReader, PMTilesWriter, TilesWriterTrait};
+		use crate::types::{TileBBox, TileFormat, TilesReaderParameters};
+		use assert_fs::NamedTempFile;
+
+		// the first tile of every run must be included, and no tile after it
+		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		bbox_pyramid.set_level_bbox(TileBBox::new(0, 0, 0, 0, 0)?);
+		bbox_pyramid.set_level_bbox(TileBBox::new(1, 1, 0, 1, 0)?);
+		bbox_pyramid.set_level_bbox(TileBBox::new(3, 1, 2, 4, 5)?);
+
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::PNG,
+			TileCompression::Uncompressed,
+			bbox_pyramid.clone(),
+		))?;
+		let temp_file = NamedTempFile::new("temp.pmtiles")?;
+		PMTilesWriter::write_to_path(&mut mock_reader, &temp_file).await?;
+
+		let reader = PMTilesReader::open_path(&temp_file).await?;
+		assert_eq!(reader.get_parameters().bbox_pyramid, bbox_pyramid);
+
+		Ok(())
+	}
 }
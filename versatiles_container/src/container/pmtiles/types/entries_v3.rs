@@ -90,6 +90,26 @@ impl EntriesV3 {
 		self.entries.push(entry)
 	}
 
+	/// Adds a single tile. If the tile directly follows the last entry and references the same data,
+	/// the run length of the last entry is increased instead of adding a new entry.
+	///
+	/// # Arguments
+	/// * `tile_id` - The tile ID of the tile.
+	/// * `range` - The byte range of the tile data.
+	pub fn push_tile(&mut self, tile_id: u64, range: ByteRange) {
+		if let Some(last) = self.entries.last_mut() {
+			if last.run_length > 0
+				&& last.range == range
+				&& last.tile_id + last.run_length as u64 == tile_id
+				&& last.run_length < u32::MAX
+			{
+				last.run_length += 1;
+				return;
+			}
+		}
+		self.entries.push(EntryV3::new(tile_id, range, 1))
+	}
+
 	/// Returns a slice view into the entries.
 	pub fn as_slice(&self) -> EntriesSliceV3 {
 		EntriesSliceV3 {
@@ -214,8 +234,9 @@ impl EntriesV3 {
 		}
 	}
 
+	/// Returns the number of addressed tiles, i.e. the sum of all run lengths.
 	pub fn tile_count(&self) -> u64 {
-		self.entries.len() as u64
+		self.entries.iter().map(|e| e.run_length as u64).sum()
 	}
 }
 
@@ -404,6 +425,33 @@ mod tests {
 		assert_eq!(entries.len(), 1_000_000);
 	}
 
+	#[test]
+	fn test_push_tile() {
+		let mut entries = EntriesV3::new();
+		let range1 = ByteRange::new(0, 100);
+		let range2 = ByteRange::new(100, 50);
+		entries.push_tile(0, range1);
+		entries.push_tile(1, range1);
+		entries.push_tile(2, range1);
+		entries.push_tile(3, range2);
+		entries.push_tile(5, range2);
+		entries.push_tile(6, range1);
+
+		assert_eq!(
+			entries.iter().cloned().collect::<Vec<_>>(),
+			vec![
+				EntryV3::new(0, range1, 3),
+				EntryV3::new(3, range2, 1),
+				EntryV3::new(5, range2, 1),
+				EntryV3::new(6, range1, 1),
+			]
+		);
+		assert_eq!(entries.len(), 4);
+		assert_eq!(entries.tile_count(), 6);
+		assert_eq!(entries.find_tile(2).unwrap().range, range1);
+		assert_eq!(entries.find_tile(4), None);
+	}
+
 	/// Verifies that `EntriesV3` can handle the maximum allowed number of entries without panicking.
 	#[test]
 	fn test_excessive_entries_panic() {
@@ -5,6 +5,7 @@
 //! ## Features
 //! - Supports writing metadata and tile data with internal compression
 //! - Efficiently organizes and compresses tile data for storage
+//! - Stores repeated tiles only once and merges consecutive identical tiles into run-length entries
 //! - Implements progress feedback during the write process
 //!
 //! ## Usage Example
@@ -28,7 +29,7 @@
 //! ## Testing
 //! This module includes comprehensive tests to ensure the correct functionality of writing metadata, handling different tile formats, and verifying the integrity of the written data.
 
-use super::types::{EntriesV3, EntryV3, HeaderV3, PMTilesCompression, TileId};
+use super::types::{EntriesV3, HeaderV3, PMTilesCompression, TileId};
 use crate::{
 	container::TilesWriterTrait,
 	types::{Blob, ByteRange, TileBBox, TileCompression, TilesReaderTrait},
@@ -36,6 +37,7 @@ use crate::{
 };
 use anyhow::Result;
 use async_trait::async_trait;
+use std::collections::HashMap;
 
 /// A struct that provides functionality to write tile data to a PMTiles container.
 pub struct PMTilesWriter {}
@@ -83,17 +85,43 @@ impl TilesWriterTrait for PMTilesWriter {
 
 		let tile_data_start = writer.get_position()?;
 
+		let mut tile_hash_lookup: HashMap<Vec<u8>, ByteRange> = HashMap::new();
+		let mut tile_contents_count: u64 = 0;
+
 		for bbox in blocks.iter() {
+			let mut block_tiles: Vec<(u64, ByteRange)> = Vec::new();
+
 			let mut stream = reader.get_bbox_tile_stream(bbox.clone()).await;
 			while let Some((coord, blob)) = stream.next().await {
 				progress.inc(1);
-				let id = coord.get_tile_id().unwrap();
-				let range = writer.append(&blob).unwrap();
-				entries.push(EntryV3::new(
-					id,
-					range.get_shifted_backward(tile_data_start),
-					1,
-				));
+				let id = coord.get_tile_id()?;
+
+				// only small tiles (like empty or ocean tiles) are usually repeated
+				let known_range = if blob.len() < 1000 {
+					tile_hash_lookup.get(blob.as_slice()).copied()
+				} else {
+					None
+				};
+
+				let range = match known_range {
+					Some(range) => range,
+					None => {
+						let range = writer.append(&blob)?.get_shifted_backward(tile_data_start);
+						tile_contents_count += 1;
+						if blob.len() < 1000 {
+							tile_hash_lookup.insert(blob.into_vec(), range);
+						}
+						range
+					}
+				};
+
+				block_tiles.push((id, range));
+			}
+
+			// blocks are sorted by tile id, but tiles within a block may arrive in any order
+			block_tiles.sort_unstable_by_key(|(id, _)| *id);
+			for (id, range) in block_tiles {
+				entries.push_tile(id, range);
 			}
 
 			tile_count += bbox.count_tiles();
@@ -116,7 +144,7 @@ impl TilesWriterTrait for PMTilesWriter {
 		header.internal_compression = PMTilesCompression::from_value(INTERNAL_COMPRESSION)?;
 		header.addressed_tiles_count = entries.tile_count();
 		header.tile_entries_count = entries.len() as u64;
-		header.tile_contents_count = entries.len() as u64;
+		header.tile_contents_count = tile_contents_count;
 
 		writer.write_start(&header.serialize()?)?;
 
@@ -132,7 +160,7 @@ mod tests {
 			mock::{MockTilesReader, MockTilesWriter},
 			pmtiles::PMTilesReader,
 		},
-		types::{TileBBoxPyramid, TileFormat, TilesReaderParameters},
+		types::{TileBBoxPyramid, TileCoord3, TileFormat, TilesReaderParameters},
 		utils::io::{DataReaderBlob, DataWriterBlob},
 	};
 
@@ -153,4 +181,39 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn deduplication() -> Result<()> {
+		// the mock reader returns the same tile for every coordinate
+		let mut mock_reader = MockTilesReader::new_mock(TilesReaderParameters {
+			bbox_pyramid: TileBBoxPyramid::new_full(4),
+			tile_compression: TileCompression::Uncompressed,
+			tile_format: TileFormat::PNG,
+		})?;
+
+		let mut data_writer = DataWriterBlob::new()?;
+		PMTilesWriter::write_to_writer(&mut mock_reader, &mut data_writer).await?;
+
+		let data_reader = DataReaderBlob::from(data_writer);
+		let reader = PMTilesReader::open_reader(Box::new(data_reader)).await?;
+
+		let header = &reader.header;
+		assert_eq!(header.addressed_tiles_count, 341);
+		assert_eq!(header.tile_entries_count, 1);
+		assert_eq!(header.tile_contents_count, 1);
+		assert_eq!(header.tile_data.length, 103);
+
+		assert_eq!(
+			format!("{:?}", reader.get_parameters().bbox_pyramid),
+			"[0: [0,0,0,0] (1), 1: [0,0,1,1] (4), 2: [0,0,3,3] (16), 3: [0,0,7,7] (64), 4: [0,0,15,15] (256)]"
+		);
+
+		let tile = reader
+			.get_tile_data(&TileCoord3::new(5, 7, 4)?)
+			.await?
+			.unwrap();
+		assert_eq!(tile.len(), 103);
+
+		Ok(())
+	}
 }
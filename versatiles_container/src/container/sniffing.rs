@@ -0,0 +1,81 @@
+//! Verifies the tile format and compression of a container by sniffing a sample tile.
+//!
+//! Containers like tar files or directories derive format and compression from file names,
+//! MBTiles from the metadata table. Both can be wrong, e.g. if gzipped tiles are stored without a `.gz` suffix.
+
+use crate::{
+	types::{Blob, TilesReaderParameters},
+	utils::{is_format_compatible, sniff_tile},
+};
+use log::warn;
+
+/// Detects the real format and compression of a sample tile. If they differ from the parameters,
+/// a warning is logged and the parameters are corrected.
+pub(crate) fn correct_parameters_by_sample(
+	parameters: &mut TilesReaderParameters,
+	sample: &Blob,
+	name: &str,
+) {
+	let (tile_format, tile_compression) = match sniff_tile(sample) {
+		Some(result) => result,
+		None => return,
+	};
+
+	if !is_format_compatible(parameters.tile_format, tile_format) {
+		warn!(
+			"tiles in {name:?} are declared as {}, but they look like {}, so using {}",
+			parameters.tile_format, tile_format, tile_format
+		);
+		parameters.tile_format = tile_format;
+	}
+
+	if parameters.tile_compression != tile_compression {
+		warn!(
+			"tiles in {name:?} are declared with compression {}, but they look like {}, so using {}",
+			parameters.tile_compression, tile_compression, tile_compression
+		);
+		parameters.tile_compression = tile_compression;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		types::{
+			TileBBoxPyramid,
+			TileCompression::{self, *},
+			TileFormat::{self, *},
+		},
+		utils::compress_gzip,
+	};
+
+	fn check(
+		format: TileFormat,
+		compression: TileCompression,
+		sample: &Blob,
+	) -> TilesReaderParameters {
+		let mut parameters =
+			TilesReaderParameters::new(format, compression, TileBBoxPyramid::new_empty());
+		correct_parameters_by_sample(&mut parameters, sample, "test");
+		parameters
+	}
+
+	#[test]
+	fn correct_parameters() {
+		let json = Blob::from("{\"type\":\"FeatureCollection\"}");
+		let gzip_json = compress_gzip(&json).unwrap();
+
+		let p = check(GEOJSON, Uncompressed, &json);
+		assert_eq!((p.tile_format, p.tile_compression), (GEOJSON, Uncompressed));
+
+		let p = check(GEOJSON, Uncompressed, &gzip_json);
+		assert_eq!((p.tile_format, p.tile_compression), (GEOJSON, Gzip));
+
+		let p = check(PNG, Brotli, &gzip_json);
+		assert_eq!((p.tile_format, p.tile_compression), (JSON, Gzip));
+
+		let p = check(BIN, Brotli, &Blob::from("unknown"));
+		assert_eq!((p.tile_format, p.tile_compression), (BIN, Brotli));
+	}
+}
@@ -1,15 +1,26 @@
 //! Provides functionality for reading tile data from a tar archive.
 
 use crate::{
+	container::correct_parameters_by_sample,
 	types::{
 		Blob, ByteRange, TileBBoxPyramid, TileCompression, TileCoord3, TileFormat,
 		TilesReaderParameters, TilesReaderTrait,
 	},
 	utils::decompress,
 };
+#[cfg(feature = "cli")]
+use crate::{
+	container::{TileContentsProbe, TileSizeStats},
+	utils::PrettyPrint,
+};
 use anyhow::{bail, Result};
 use async_trait::async_trait;
-use std::{collections::HashMap, fmt::Debug, io::Read, path::Path};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Debug,
+	io::Read,
+	path::Path,
+};
 use tar::{Archive, EntryType};
 use versatiles_core::utils::io::{DataReaderFile, DataReaderTrait};
 
@@ -20,6 +31,9 @@ pub struct TarTilesReader {
 	reader: Box<DataReaderFile>,
 	tile_map: HashMap<TileCoord3, ByteRange>,
 	parameters: TilesReaderParameters,
+	entry_counts: BTreeMap<String, u64>,
+	duplicate_tiles: Vec<String>,
+	unknown_files: Vec<String>,
 }
 
 impl TarTilesReader {
@@ -39,11 +53,16 @@ impl TarTilesReader {
 		let mut tile_format: Option<TileFormat> = None;
 		let mut tile_compression: Option<TileCompression> = None;
 		let mut bbox_pyramid = TileBBoxPyramid::new_empty();
+		let mut sample: Option<Blob> = None;
+		let mut entry_counts: BTreeMap<String, u64> = BTreeMap::new();
+		let mut duplicate_tiles: Vec<String> = Vec::new();
+		let mut unknown_files: Vec<String> = Vec::new();
 
 		for entry in archive.entries()? {
 			let mut entry = entry?;
-			let header = entry.header();
-			if header.entry_type() != EntryType::Regular {
+			let entry_type = entry.header().entry_type();
+			*entry_counts.entry(format!("{entry_type:?}")).or_default() += 1;
+			if entry_type != EntryType::Regular {
 				continue;
 			}
 
@@ -67,6 +86,7 @@ impl TarTilesReader {
 				let this_format = TileFormat::from_filename(&mut filename);
 
 				if this_format.is_none() {
+					unknown_files.push(path_tmp_string);
 					continue;
 				}
 				let this_format = this_format.unwrap();
@@ -90,7 +110,18 @@ impl TarTilesReader {
 
 				let coord3 = TileCoord3::new(x, y, z)?;
 				bbox_pyramid.include_coord(&coord3);
-				tile_map.insert(coord3, ByteRange { offset, length });
+				if tile_map
+					.insert(coord3, ByteRange { offset, length })
+					.is_some()
+				{
+					duplicate_tiles.push(path_tmp_string.clone());
+				}
+
+				if sample.is_none() {
+					let mut blob: Vec<u8> = Vec::new();
+					entry.read_to_end(&mut blob)?;
+					sample = Some(Blob::from(blob));
+				}
 				continue;
 			}
 
@@ -119,18 +150,28 @@ impl TarTilesReader {
 			}
 
 			log::warn!("unknown file in tar: {path_tmp_string:?}");
+			unknown_files.push(path_tmp_string);
+		}
+
+		let name = path.to_str().unwrap().to_string();
+		let mut parameters = TilesReaderParameters::new(
+			tile_format.unwrap(),
+			tile_compression.unwrap(),
+			bbox_pyramid,
+		);
+		if let Some(sample) = sample {
+			correct_parameters_by_sample(&mut parameters, &sample, &name);
 		}
 
 		Ok(TarTilesReader {
 			meta,
-			name: path.to_str().unwrap().to_string(),
-			parameters: TilesReaderParameters::new(
-				tile_format.unwrap(),
-				tile_compression.unwrap(),
-				bbox_pyramid,
-			),
+			name,
+			parameters,
 			reader,
 			tile_map,
+			entry_counts,
+			duplicate_tiles,
+			unknown_files,
 		})
 	}
 }
@@ -187,6 +228,46 @@ impl TilesReaderTrait for TarTilesReader {
 	fn get_name(&self) -> &str {
 		&self.name
 	}
+
+	// deep probe of tar entries
+	#[cfg(feature = "cli")]
+	async fn probe_container(&mut self, print: &PrettyPrint) -> Result<()> {
+		print.add_key_value("entries", &self.entry_counts).await;
+		print
+			.add_key_value("tile entries", &self.tile_map.len())
+			.await;
+		print
+			.add_key_value("meta size", &self.meta.as_ref().map_or(0, |b| b.len()))
+			.await;
+
+		for (key, label, paths) in [
+			("duplicate tiles", "duplicate tile", &self.duplicate_tiles),
+			("unknown files", "unknown file", &self.unknown_files),
+		] {
+			print.add_key_value(key, &paths.len()).await;
+			for path in paths.iter().take(10) {
+				print.add_warning(&format!("{label} {path:?}")).await;
+			}
+		}
+
+		Ok(())
+	}
+
+	// deep probe of tile sizes
+	#[cfg(feature = "cli")]
+	async fn probe_tiles(&mut self, print: &PrettyPrint) -> Result<()> {
+		let mut stats = TileSizeStats::new();
+		for (coord, range) in self.tile_map.iter() {
+			stats.add(coord.z, range.length);
+		}
+		stats.print(print).await;
+		Ok(())
+	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 impl Debug for TarTilesReader {
@@ -204,7 +285,7 @@ pub mod tests {
 	use crate::utils::PrettyPrint;
 	use crate::{
 		container::{make_test_file, MockTilesWriter, MOCK_BYTES_PBF},
-		utils::decompress_gzip,
+		utils::{compress_gzip, decompress_gzip},
 	};
 
 	#[tokio::test]
@@ -256,11 +337,49 @@ pub mod tests {
 		Ok(())
 	}
 
-	// Test tile fetching
+	#[tokio::test]
+	async fn sniff_compression() -> Result<()> {
+		let temp_file = assert_fs::NamedTempFile::new("tiles.tar")?;
+		let mut builder = tar::Builder::new(std::fs::File::create(&temp_file)?);
+		let blob = compress_gzip(&Blob::from(MOCK_BYTES_PBF.to_vec()))?;
+		let mut header = tar::Header::new_gnu();
+		header.set_size(blob.len());
+		header.set_mode(0o644);
+		builder.append_data(&mut header, "3/2/1.pbf", blob.as_slice())?;
+		builder.finish()?;
+		drop(builder);
+
+		let reader = TarTilesReader::open_path(&temp_file)?;
+		assert_eq!(reader.get_parameters().tile_format, TileFormat::PBF);
+		assert_eq!(
+			reader.get_parameters().tile_compression,
+			TileCompression::Gzip
+		);
+
+		Ok(())
+	}
+
 	#[cfg(feature = "cli")]
 	#[tokio::test]
 	async fn probe() -> Result<()> {
-		let temp_file = make_test_file(TileFormat::PBF, TileCompression::Gzip, 4, "tar").await?;
+		let temp_file = assert_fs::NamedTempFile::new("tiles.tar")?;
+		let mut builder = tar::Builder::new(std::fs::File::create(&temp_file)?);
+		let mut append = |path: &str, size: usize| -> Result<()> {
+			let mut header = tar::Header::new_gnu();
+			header.set_size(size as u64);
+			header.set_mode(0o644);
+			builder.append_data(&mut header, path, vec![0u8; size].as_slice())?;
+			Ok(())
+		};
+		append("0/0/0.png", 100)?;
+		append("1/0/0.png", 2000)?;
+		append("1/0/1.png", 3000)?;
+		append("1/0/1.png", 4000)?;
+		append("1/1/readme.txt", 10)?;
+		append("meta.json", 20)?;
+		append("notes.md", 30)?;
+		builder.finish()?;
+		drop(builder);
 
 		let mut reader = TarTilesReader::open_path(&temp_file)?;
 
@@ -270,7 +389,7 @@ pub mod tests {
 			.await?;
 		assert_eq!(
 			printer.as_string().await,
-			"container:\n   deep container probing is not implemented for this container format\n"
+			"container:\n   entries: {\"Regular\": 7}\n   tile entries: 3\n   meta size: 20\n   duplicate tiles: 1\n   duplicate tile \"1/0/1.png\"\n   unknown files: 2\n   unknown file \"1/1/readme.txt\"\n   unknown file \"notes.md\"\n"
 		);
 
 		let mut printer = PrettyPrint::new();
@@ -279,7 +398,7 @@ pub mod tests {
 			.await?;
 		assert_eq!(
 			printer.as_string().await,
-			"tiles:\n   deep tiles probing is not implemented for this container format\n"
+			"tiles:\n   tile count: 3\n   sum of tile sizes: 6_100\n   level 0: count: 1, average: 100, min: 100, max: 100, histogram: [<1K: 1]\n   level 1: count: 2, average: 3000, min: 2000, max: 4000, histogram: [<2K: 1, <4K: 1]\n"
 		);
 
 		Ok(())
@@ -0,0 +1,447 @@
+//! Deep probing of tile contents by decoding a sample of tiles.
+//!
+//! For every zoom level up to [`SAMPLES_PER_LEVEL`] tiles are read, evenly spread over the bbox of the level.
+//! Vector tiles are analysed by layer (features per geometry type, property keys and their value types, extents),
+//! raster tiles by their pixel dimensions, color types and the share of fully transparent or uniform tiles.
+
+#[cfg(feature = "cli")]
+use crate::utils::PrettyPrint;
+use crate::{
+	types::{Blob, TileBBox, TileCoord3, TileFormat, TilesReaderTrait},
+	utils::{decompress, JsonValue},
+};
+use anyhow::{bail, Result};
+use image::DynamicImage;
+use std::collections::{BTreeMap, BTreeSet};
+use versatiles_geometry::vector_tile::VectorTile;
+use versatiles_image::{jpeg, png, webp};
+
+/// Maximum number of tiles that are sampled per zoom level.
+pub const SAMPLES_PER_LEVEL: u64 = 20;
+
+/// Statistics of a single vector tile layer, summed up over all sampled tiles.
+#[derive(Debug, Default, PartialEq)]
+pub struct VectorLayerStats {
+	/// number of sampled tiles containing this layer
+	pub tile_count: u64,
+	/// number of features per geometry type, e.g. "MultiPolygon"
+	pub geometry_types: BTreeMap<String, u64>,
+	/// property keys and the types of their values, e.g. "string" or "uint"
+	pub properties: BTreeMap<String, BTreeSet<String>>,
+	/// all extents used by this layer
+	pub extents: BTreeSet<u32>,
+}
+
+impl VectorLayerStats {
+	pub fn as_json(&self) -> JsonValue {
+		JsonValue::from(vec![
+			("tile_count", JsonValue::from(self.tile_count)),
+			(
+				"geometry_types",
+				JsonValue::Object(
+					self
+						.geometry_types
+						.iter()
+						.map(|(k, v)| (k.clone(), JsonValue::from(*v)))
+						.collect(),
+				),
+			),
+			(
+				"properties",
+				JsonValue::Object(
+					self
+						.properties
+						.iter()
+						.map(|(k, v)| {
+							(
+								k.clone(),
+								JsonValue::from(v.iter().map(|t| t.as_str()).collect::<Vec<_>>()),
+							)
+						})
+						.collect(),
+				),
+			),
+			(
+				"extents",
+				JsonValue::from(self.extents.iter().copied().collect::<Vec<u32>>()),
+			),
+		])
+	}
+}
+
+/// Statistics of raster tiles, summed up over all sampled tiles.
+#[derive(Debug, Default, PartialEq)]
+pub struct RasterStats {
+	/// number of tiles per pixel dimension, e.g. "256x256"
+	pub sizes: BTreeMap<String, u64>,
+	/// number of tiles per color type, e.g. "Rgba8"
+	pub color_types: BTreeMap<String, u64>,
+	/// number of tiles in which every pixel is fully transparent
+	pub transparent_count: u64,
+	/// number of tiles in which every pixel has the same color
+	pub uniform_count: u64,
+}
+
+/// Statistics about the contents of a sample of tiles.
+#[derive(Debug, PartialEq)]
+pub struct TileContentsProbe {
+	pub tile_format: TileFormat,
+	/// number of coordinates that have been sampled
+	pub sample_count: u64,
+	/// number of sampled coordinates without a tile
+	pub missing_count: u64,
+	/// errors that occurred while decoding tiles
+	pub errors: Vec<String>,
+	/// vector tile statistics, grouped by layer name
+	pub vector_layers: BTreeMap<String, VectorLayerStats>,
+	/// raster tile statistics
+	pub raster: RasterStats,
+}
+
+impl TileContentsProbe {
+	/// Reads and analyses up to `samples_per_level` tiles per zoom level.
+	pub async fn new(
+		reader: &dyn TilesReaderTrait,
+		samples_per_level: u64,
+	) -> Result<TileContentsProbe> {
+		let parameters = reader.get_parameters().clone();
+		let mut probe = TileContentsProbe {
+			tile_format: parameters.tile_format,
+			sample_count: 0,
+			missing_count: 0,
+			errors: Vec::new(),
+			vector_layers: BTreeMap::new(),
+			raster: RasterStats::default(),
+		};
+
+		for bbox in parameters.bbox_pyramid.iter_levels() {
+			for coord in get_sample_coords(bbox, samples_per_level)? {
+				probe.sample_count += 1;
+
+				let Some(blob) = reader.get_tile_data(&coord).await? else {
+					probe.missing_count += 1;
+					continue;
+				};
+
+				let result = decompress(blob, &parameters.tile_compression)
+					.and_then(|blob| probe.add_tile(&blob));
+				if let Err(err) = result {
+					probe.errors.push(format!("{coord:?}: {err}"));
+				}
+			}
+		}
+
+		Ok(probe)
+	}
+
+	/// Samples [`SAMPLES_PER_LEVEL`] tiles per zoom level and prints the statistics.
+	/// The readers use this to implement `TilesReaderTrait::probe_tile_contents`.
+	#[cfg(feature = "cli")]
+	pub async fn probe_and_print(reader: &dyn TilesReaderTrait, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::new(reader, SAMPLES_PER_LEVEL)
+			.await?
+			.print(print)
+			.await;
+		Ok(())
+	}
+
+	/// Returns true if the contents of this tile format can be analysed.
+	pub fn is_supported(&self) -> bool {
+		use TileFormat::*;
+		matches!(self.tile_format, PBF | PNG | JPG | WEBP)
+	}
+
+	fn add_tile(&mut self, blob: &Blob) -> Result<()> {
+		use TileFormat::*;
+		match self.tile_format {
+			PBF => self.add_vector_tile(blob),
+			PNG => self.add_image(png::blob2image(blob)?),
+			JPG => self.add_image(jpeg::blob2image(blob)?),
+			WEBP => self.add_image(webp::blob2image(blob)?),
+			_ => Ok(()),
+		}
+	}
+
+	fn add_vector_tile(&mut self, blob: &Blob) -> Result<()> {
+		let tile = VectorTile::from_blob(blob)?;
+		for layer in tile.layers.iter() {
+			let stats = self.vector_layers.entry(layer.name.clone()).or_default();
+			stats.tile_count += 1;
+			stats.extents.insert(layer.extent);
+			for feature in layer.to_features()? {
+				*stats
+					.geometry_types
+					.entry(feature.geometry.get_type_name().to_string())
+					.or_default() += 1;
+				for (key, value) in feature.properties.iter() {
+					stats
+						.properties
+						.entry(key.to_string())
+						.or_default()
+						.insert(value.type_as_str().to_string());
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn add_image(&mut self, image: DynamicImage) -> Result<()> {
+		let stats = &mut self.raster;
+		*stats
+			.sizes
+			.entry(format!("{}x{}", image.width(), image.height()))
+			.or_default() += 1;
+		*stats
+			.color_types
+			.entry(format!("{:?}", image.color()))
+			.or_default() += 1;
+
+		let image = image.into_rgba8();
+		let mut pixels = image.pixels();
+		let first = match pixels.next() {
+			Some(pixel) => *pixel,
+			None => bail!("image is empty"),
+		};
+		let (mut transparent, mut uniform) = (first[3] == 0, true);
+		for pixel in pixels {
+			transparent &= pixel[3] == 0;
+			uniform &= *pixel == first;
+			if !transparent && !uniform {
+				break;
+			}
+		}
+		if transparent {
+			stats.transparent_count += 1;
+		}
+		if uniform {
+			stats.uniform_count += 1;
+		}
+		Ok(())
+	}
+
+	#[cfg(feature = "cli")]
+	pub async fn print(&self, print: &PrettyPrint) {
+		if !self.is_supported() {
+			print
+				.add_warning(&format!(
+					"probing the contents of {} tiles is not supported",
+					self.tile_format
+				))
+				.await;
+			return;
+		}
+
+		print
+			.add_key_value("sampled tiles", &self.sample_count)
+			.await;
+		print
+			.add_key_value("missing tiles", &self.missing_count)
+			.await;
+		for error in self.errors.iter() {
+			print.add_warning(error).await;
+		}
+
+		if self.tile_format == TileFormat::PBF {
+			for (name, stats) in self.vector_layers.iter() {
+				print
+					.add_key_value_json(&format!("layer {name:?}"), stats, stats.as_json())
+					.await;
+			}
+		} else {
+			let stats = &self.raster;
+			print.add_key_value("sizes", &stats.sizes).await;
+			print.add_key_value("color types", &stats.color_types).await;
+			print
+				.add_key_value("fully transparent tiles", &stats.transparent_count)
+				.await;
+			print
+				.add_key_value("uniform tiles", &stats.uniform_count)
+				.await;
+		}
+	}
+}
+
+/// Returns up to `count` coordinates, spread as an even grid over the bbox.
+fn get_sample_coords(bbox: &TileBBox, count: u64) -> Result<Vec<TileCoord3>> {
+	if bbox.is_empty() || count == 0 {
+		return Ok(Vec::new());
+	}
+	let width = bbox.width() as u64;
+	let height = bbox.height() as u64;
+
+	// the grid has roughly the aspect ratio of the bbox
+	let columns =
+		((count as f64 * width as f64 / height as f64).sqrt().round() as u64).clamp(1, width);
+	let rows = (count / columns).clamp(1, height);
+
+	// the center of each grid cell
+	let center = |i: u64, cells: u64, size: u64| ((2 * i + 1) * size / (2 * cells)) as u32;
+
+	let mut coords = Vec::new();
+	for row in 0..rows {
+		for column in 0..columns {
+			coords.push(TileCoord3::new(
+				bbox.x_min + center(column, columns, width),
+				bbox.y_min + center(row, rows, height),
+				bbox.level,
+			)?);
+		}
+	}
+	Ok(coords)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		container::{MockTilesReader, MockTilesReaderProfile},
+		types::{TileBBoxPyramid, TileCompression, TilesReaderParameters},
+	};
+
+	#[test]
+	fn sample_coords() -> Result<()> {
+		let coords = |bbox: TileBBox, count: u64| -> Result<Vec<String>> {
+			Ok(get_sample_coords(&bbox, count)?
+				.iter()
+				.map(|c| format!("{},{}", c.x, c.y))
+				.collect())
+		};
+
+		assert_eq!(
+			coords(TileBBox::new(4, 2, 3, 3, 4)?, 20)?,
+			["2,3", "3,3", "2,4", "3,4"]
+		);
+		assert_eq!(
+			coords(TileBBox::new(4, 0, 0, 3, 3)?, 4)?,
+			["1,1", "3,1", "1,3", "3,3"]
+		);
+		assert_eq!(coords(TileBBox::new(4, 0, 0, 3, 3)?, 16)?.len(), 16);
+		assert_eq!(
+			coords(TileBBox::new(4, 0, 0, 7, 1)?, 4)?,
+			["1,1", "3,1", "5,1", "7,1"]
+		);
+		assert_eq!(coords(TileBBox::new(4, 5, 0, 5, 9)?, 2)?, ["5,2", "5,7"]);
+		assert!(coords(TileBBox::new_empty(4)?, 20)?.is_empty());
+
+		let bbox = TileBBox::new_full(30)?;
+		assert_eq!(get_sample_coords(&bbox, 20)?.len(), 20);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn vector_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Pbf)?;
+		let probe = TileContentsProbe::new(&reader, 2).await?;
+
+		assert_eq!(probe.sample_count, 9);
+		assert_eq!(probe.missing_count, 0);
+		assert!(probe.errors.is_empty());
+		assert!(!probe.vector_layers.is_empty());
+		for stats in probe.vector_layers.values() {
+			assert_eq!(stats.tile_count, 9);
+			assert!(!stats.extents.is_empty());
+		}
+		assert_eq!(probe.raster, RasterStats::default());
+		Ok(())
+	}
+
+	#[test]
+	fn vector_layer_json() {
+		let stats = VectorLayerStats {
+			tile_count: 2,
+			geometry_types: BTreeMap::from([(String::from("MultiPoint"), 3)]),
+			properties: BTreeMap::from([(
+				String::from("name"),
+				BTreeSet::from([String::from("string"), String::from("null")]),
+			)]),
+			extents: BTreeSet::from([4096]),
+		};
+		assert_eq!(
+			stats.as_json().stringify(),
+			"{\"extents\":[4096],\"geometry_types\":{\"MultiPoint\":3},\"properties\":{\"name\":[\"null\",\"string\"]},\"tile_count\":2}"
+		);
+	}
+
+	#[tokio::test]
+	async fn raster_tiles() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let probe = TileContentsProbe::new(&reader, 20).await?;
+
+		assert_eq!(probe.sample_count, 1 + 4 + 16 + 20 + 20);
+		assert!(probe.errors.is_empty());
+		assert_eq!(probe.raster.sizes.values().sum::<u64>(), 61);
+		assert_eq!(probe.raster.color_types.values().sum::<u64>(), 61);
+		assert!(probe.vector_layers.is_empty());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn broken_tiles() -> Result<()> {
+		// a reader whose tiles claim to be gzipped, but are not
+		#[derive(Debug)]
+		struct BrokenReader(MockTilesReader, TilesReaderParameters);
+
+		#[async_trait::async_trait]
+		impl TilesReaderTrait for BrokenReader {
+			fn get_name(&self) -> &str {
+				self.0.get_name()
+			}
+			fn get_container_name(&self) -> &str {
+				self.0.get_container_name()
+			}
+			fn get_parameters(&self) -> &TilesReaderParameters {
+				&self.1
+			}
+			fn override_compression(&mut self, tile_compression: TileCompression) {
+				self.1.tile_compression = tile_compression;
+			}
+			fn get_meta(&self) -> Result<Option<Blob>> {
+				self.0.get_meta()
+			}
+			async fn get_tile_data(&self, coord: &TileCoord3) -> Result<Option<Blob>> {
+				self.0.get_tile_data(coord).await
+			}
+		}
+
+		let mock = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let mut parameters = mock.get_parameters().clone();
+		parameters.tile_compression = TileCompression::Gzip;
+		let probe = TileContentsProbe::new(&BrokenReader(mock, parameters), 1).await?;
+
+		assert_eq!(probe.sample_count, 5);
+		assert_eq!(probe.errors.len(), 5);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn unsupported_format() -> Result<()> {
+		let reader = MockTilesReader::new_mock(TilesReaderParameters::new(
+			TileFormat::JSON,
+			TileCompression::Uncompressed,
+			TileBBoxPyramid::new_full(2),
+		))?;
+		let probe = TileContentsProbe::new(&reader, 20).await?;
+		assert!(!probe.is_supported());
+		assert_eq!(probe.sample_count, 21);
+		assert!(probe.errors.is_empty());
+		Ok(())
+	}
+
+	#[tokio::test]
+	#[cfg(feature = "cli")]
+	async fn print() -> Result<()> {
+		let reader = MockTilesReader::new_mock_profile(MockTilesReaderProfile::Png)?;
+		let probe = TileContentsProbe::new(&reader, 1).await?;
+
+		let mut printer = PrettyPrint::new();
+		probe
+			.print(&printer.get_category("tile contents").await)
+			.await;
+		let text = printer.as_string().await;
+		assert!(
+			text.starts_with("tile contents:\n   sampled tiles: 5\n   missing tiles: 0\n   sizes: {")
+		);
+		assert!(text.contains("fully transparent tiles: "));
+		Ok(())
+	}
+}
@@ -0,0 +1,177 @@
+//! Statistics of tile sizes per zoom level, used by deep probing of containers.
+//!
+//! Sizes are sorted into a histogram with power-of-two buckets: `<1K`, `<2K`, `<4K`, …
+
+use crate::utils::JsonValue;
+use std::{collections::BTreeMap, fmt::Debug};
+
+#[cfg(feature = "cli")]
+use crate::utils::PrettyPrint;
+
+/// Size statistics of the tiles of a single zoom level.
+#[derive(Clone, Default, PartialEq)]
+pub struct LevelSizeStats {
+	pub count: u64,
+	pub sum: u64,
+	pub min: u64,
+	pub max: u64,
+	/// number of tiles per bucket, bucket `i` contains sizes below `1024 << i` bytes
+	pub histogram: Vec<u64>,
+}
+
+impl LevelSizeStats {
+	fn add(&mut self, size: u64) {
+		if self.count == 0 || size < self.min {
+			self.min = size;
+		}
+		self.max = self.max.max(size);
+		self.count += 1;
+		self.sum += size;
+
+		let bucket = get_bucket(size);
+		if self.histogram.len() <= bucket {
+			self.histogram.resize(bucket + 1, 0);
+		}
+		self.histogram[bucket] += 1;
+	}
+
+	pub fn average(&self) -> u64 {
+		self.sum.checked_div(self.count).unwrap_or(0)
+	}
+
+	pub fn as_json(&self) -> JsonValue {
+		let histogram: Vec<(String, JsonValue)> = self
+			.histogram
+			.iter()
+			.enumerate()
+			.filter(|(_, count)| **count > 0)
+			.map(|(bucket, count)| (get_bucket_label(bucket), JsonValue::from(*count)))
+			.collect();
+		JsonValue::from(vec![
+			("count", JsonValue::from(self.count)),
+			("sum", JsonValue::from(self.sum)),
+			("average", JsonValue::from(self.average())),
+			("min", JsonValue::from(self.min)),
+			("max", JsonValue::from(self.max)),
+			(
+				"histogram",
+				JsonValue::Object(histogram.into_iter().collect()),
+			),
+		])
+	}
+}
+
+impl Debug for LevelSizeStats {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let histogram = self
+			.histogram
+			.iter()
+			.enumerate()
+			.filter(|(_, count)| **count > 0)
+			.map(|(bucket, count)| format!("{}: {count}", get_bucket_label(bucket)))
+			.collect::<Vec<_>>()
+			.join(", ");
+		write!(
+			f,
+			"count: {}, average: {}, min: {}, max: {}, histogram: [{histogram}]",
+			self.count,
+			self.average(),
+			self.min,
+			self.max
+		)
+	}
+}
+
+/// Collects the sizes of tiles, grouped by zoom level.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TileSizeStats {
+	pub levels: BTreeMap<u8, LevelSizeStats>,
+}
+
+impl TileSizeStats {
+	pub fn new() -> TileSizeStats {
+		TileSizeStats::default()
+	}
+
+	pub fn add(&mut self, level: u8, size: u64) {
+		self.levels.entry(level).or_default().add(size);
+	}
+
+	pub fn count(&self) -> u64 {
+		self.levels.values().map(|l| l.count).sum()
+	}
+
+	pub fn sum(&self) -> u64 {
+		self.levels.values().map(|l| l.sum).sum()
+	}
+
+	#[cfg(feature = "cli")]
+	pub async fn print(&self, print: &PrettyPrint) {
+		print.add_key_value("tile count", &self.count()).await;
+		print.add_key_value("sum of tile sizes", &self.sum()).await;
+		for (level, stats) in self.levels.iter() {
+			print
+				.add_key_value_json(&format!("level {level}"), stats, stats.as_json())
+				.await;
+		}
+	}
+}
+
+fn get_bucket(size: u64) -> usize {
+	(u64::BITS - (size >> 10).leading_zeros()) as usize
+}
+
+fn get_bucket_label(bucket: usize) -> String {
+	let limit = 1u64 << bucket;
+	match limit {
+		0..=512 => format!("<{limit}K"),
+		1024..=524288 => format!("<{}M", limit >> 10),
+		_ => format!("<{}G", limit >> 20),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn buckets() {
+		assert_eq!(get_bucket(0), 0);
+		assert_eq!(get_bucket(1023), 0);
+		assert_eq!(get_bucket(1024), 1);
+		assert_eq!(get_bucket(2047), 1);
+		assert_eq!(get_bucket(2048), 2);
+		assert_eq!(get_bucket(1 << 20), 11);
+
+		assert_eq!(get_bucket_label(0), "<1K");
+		assert_eq!(get_bucket_label(1), "<2K");
+		assert_eq!(get_bucket_label(9), "<512K");
+		assert_eq!(get_bucket_label(10), "<1M");
+		assert_eq!(get_bucket_label(11), "<2M");
+		assert_eq!(get_bucket_label(20), "<1G");
+	}
+
+	#[test]
+	fn stats() {
+		let mut stats = TileSizeStats::new();
+		stats.add(3, 100);
+		stats.add(3, 3000);
+		stats.add(3, 200);
+		stats.add(5, 5000);
+
+		assert_eq!(stats.count(), 4);
+		assert_eq!(stats.sum(), 8300);
+		assert_eq!(
+			format!("{:?}", stats.levels[&3]),
+			"count: 3, average: 1100, min: 100, max: 3000, histogram: [<1K: 2, <4K: 1]"
+		);
+		assert_eq!(
+			format!("{:?}", stats.levels[&5]),
+			"count: 1, average: 5000, min: 5000, max: 5000, histogram: [<8K: 1]"
+		);
+		assert_eq!(
+			stats.levels[&3].as_json().stringify(),
+			"{\"average\":1100,\"count\":3,\"histogram\":{\"<1K\":2,\"<4K\":1},\"max\":3000,\"min\":100,\"sum\":3300}"
+		);
+	}
+}
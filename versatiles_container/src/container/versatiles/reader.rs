@@ -45,7 +45,10 @@
 
 use super::types::{BlockDefinition, BlockIndex, FileHeader, TileIndex};
 #[cfg(feature = "cli")]
-use crate::utils::PrettyPrint;
+use crate::{
+	container::{TileContentsProbe, TileSizeStats},
+	utils::PrettyPrint,
+};
 use crate::{
 	types::{
 		Blob, ByteRange, LimitedCache, TileBBox, TileCompression, TileCoord2, TileCoord3, TileStream,
@@ -143,19 +146,22 @@ impl VersaTilesReader {
 	async fn get_block_tile_index(&self, block: &BlockDefinition) -> Result<Arc<TileIndex>> {
 		let block_coord = block.get_coord3();
 
-		let mut cache = self.tile_index_cache.lock().await;
+		if let Some(value) = self.tile_index_cache.lock().await.get(block_coord) {
+			return Ok(value);
+		}
 
-		Ok(if let Some(value) = cache.get(block_coord) {
-			value
-		} else {
-			let blob = self.reader.read_range(block.get_index_range()).await?;
-			let mut tile_index = TileIndex::from_brotli_blob(blob)?;
-			tile_index.add_offset(block.get_tiles_range().offset);
+		// the cache is not locked while reading, so other requests are not blocked
+		let blob = self.reader.read_range(block.get_index_range()).await?;
+		let mut tile_index = TileIndex::from_brotli_blob(blob)?;
+		tile_index.add_offset(block.get_tiles_range().offset);
 
-			assert_eq!(tile_index.len(), block.count_tiles() as usize);
+		assert_eq!(tile_index.len(), block.count_tiles() as usize);
 
-			cache.add(*block_coord, Arc::new(tile_index))
-		})
+		Ok(self
+			.tile_index_cache
+			.lock()
+			.await
+			.add(*block_coord, Arc::new(tile_index)))
 	}
 
 	/// Retrieves the size of the index.
@@ -439,6 +445,7 @@ impl TilesReaderTrait for VersaTilesReader {
 		let mut min_size: u64 = 0;
 		let mut size_sum: u64 = 0;
 		let mut tile_count: u64 = 0;
+		let mut size_stats = TileSizeStats::new();
 
 		let block_index = self.block_index.clone();
 		let mut progress = get_progress_bar("scanning blocks", block_index.len() as u64);
@@ -450,6 +457,7 @@ impl TilesReaderTrait for VersaTilesReader {
 
 				tile_count += 1;
 				size_sum += size;
+				size_stats.add(block.get_z(), size);
 
 				if size < min_size {
 					continue;
@@ -464,7 +472,7 @@ impl TilesReaderTrait for VersaTilesReader {
 					y: coord.y,
 					z: coord.z,
 				});
-				biggest_tiles.sort_by(|a, b| b.size.cmp(&a.size));
+				biggest_tiles.sort_by_key(|t| std::cmp::Reverse(t.size));
 				while biggest_tiles.len() > 10 {
 					biggest_tiles.pop();
 				}
@@ -484,8 +492,15 @@ impl TilesReaderTrait for VersaTilesReader {
 				.await;
 		}
 
+		size_stats.print(print).await;
+
 		Ok(())
 	}
+
+	#[cfg(feature = "cli")]
+	async fn probe_tile_contents(&mut self, print: &PrettyPrint) -> Result<()> {
+		TileContentsProbe::probe_and_print(&*self, print).await
+	}
 }
 
 // Implement Debug for TilesReader
@@ -5,11 +5,15 @@
 //!
 
 use crate::{
-	types::TilesReaderTrait,
-	utils::io::{DataWriterFile, DataWriterTrait},
+	types::{Blob, TilesReaderTrait},
+	utils::{
+		io::{DataWriterFile, DataWriterTrait},
+		parse_json, JsonValue,
+	},
 };
 use anyhow::Result;
 use async_trait::async_trait;
+use log::warn;
 use std::path::Path;
 
 /// Trait defining the behavior of a tile writer.
@@ -26,3 +30,95 @@ pub trait TilesWriterTrait: Send {
 		writer: &mut dyn DataWriterTrait,
 	) -> Result<()>;
 }
+
+/// Merges the meta data of an existing container with the meta data of new tiles, used when updating a container.
+///
+/// Keys of the existing meta data are kept and missing keys are added. Vector layers are merged by their `id`,
+/// extending the zoom range of layers that exist in both.
+/// If the existing meta data is not a JSON object, it is kept unchanged.
+pub(crate) fn merge_meta(existing: Option<Blob>, new: Option<Blob>) -> Option<Blob> {
+	let (existing, new) = match (existing, new) {
+		(Some(existing), Some(new)) => (existing, new),
+		(existing, new) => return existing.or(new),
+	};
+
+	let (mut existing_obj, new_obj) = match (parse_json(existing.as_str()), parse_json(new.as_str()))
+	{
+		(Ok(JsonValue::Object(existing)), Ok(JsonValue::Object(new))) => (existing, new),
+		_ => {
+			warn!("meta data is not a JSON object, so it can not be merged");
+			return Some(existing);
+		}
+	};
+
+	for (key, value) in new_obj {
+		match (existing_obj.get_mut(&key), value) {
+			(Some(JsonValue::Array(existing_layers)), JsonValue::Array(new_layers))
+				if key == "vector_layers" =>
+			{
+				merge_vector_layers(existing_layers, new_layers)
+			}
+			(Some(_), _) => {}
+			(None, value) => {
+				existing_obj.insert(key, value);
+			}
+		}
+	}
+
+	Some(Blob::from(JsonValue::Object(existing_obj).stringify()))
+}
+
+fn merge_vector_layers(existing_layers: &mut Vec<JsonValue>, new_layers: Vec<JsonValue>) {
+	let get_id = |layer: &JsonValue| match layer {
+		JsonValue::Object(layer) => layer.get("id").cloned(),
+		_ => None,
+	};
+
+	for new_layer in new_layers {
+		let id = get_id(&new_layer);
+		let existing_layer = existing_layers
+			.iter_mut()
+			.find(|layer| id.is_some() && get_id(layer) == id);
+
+		match (existing_layer, new_layer) {
+			(Some(JsonValue::Object(layer)), JsonValue::Object(new_layer)) => {
+				for (key, pick_min) in [("minzoom", true), ("maxzoom", false)] {
+					if let (Some(JsonValue::Num(a)), Some(JsonValue::Num(b))) =
+						(layer.get(key), new_layer.get(key))
+					{
+						let zoom = if pick_min { a.min(*b) } else { a.max(*b) };
+						layer.insert(key.to_string(), JsonValue::Num(zoom));
+					}
+				}
+			}
+			(Some(_), _) => {}
+			(None, new_layer) => existing_layers.push(new_layer),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn merge(existing: Option<&str>, new: Option<&str>) -> Option<String> {
+		merge_meta(existing.map(Blob::from), new.map(Blob::from)).map(|b| b.as_str().to_string())
+	}
+
+	#[test]
+	fn merge_meta_data() {
+		assert_eq!(merge(None, None), None);
+		assert_eq!(merge(Some("{\"a\":1}"), None).unwrap(), "{\"a\":1}");
+		assert_eq!(merge(None, Some("{\"a\":1}")).unwrap(), "{\"a\":1}");
+		assert_eq!(merge(Some("dummy"), Some("{\"a\":1}")).unwrap(), "dummy");
+
+		assert_eq!(
+			merge(
+				Some("{\"name\":\"country\",\"vector_layers\":[{\"id\":\"water\",\"minzoom\":0,\"maxzoom\":10},{\"id\":\"land\"}]}"),
+				Some("{\"name\":\"city\",\"attribution\":\"OSM\",\"vector_layers\":[{\"id\":\"water\",\"minzoom\":5,\"maxzoom\":14},{\"id\":\"streets\"}]}")
+			)
+			.unwrap(),
+			"{\"attribution\":\"OSM\",\"name\":\"country\",\"vector_layers\":[{\"id\":\"water\",\"maxzoom\":14,\"minzoom\":0},{\"id\":\"land\"},{\"id\":\"streets\"}]}"
+		);
+	}
+}
@@ -1,8 +1,11 @@
 //! This module provides a generic limited cache that stores key-value pairs up to a specified size limit.
 //!
-//! The `LimitedCache` manages entries to ensure that it does not exceed a predefined number of elements.
+//! The `LimitedCache` manages entries to ensure that it does not exceed a predefined size in bytes.
 //! It uses a Least Recently Used (LRU) strategy for cache eviction once the limit is reached.
 //!
+//! By default every entry is counted with the size of its types. Values that own heap memory,
+//! like blobs, can be counted with their real size using `with_size_function`.
+//!
 //! # Type Parameters
 //! - `K`: The type of the keys stored in the cache.
 //! - `V`: The type of the values stored in the cache.
@@ -18,12 +21,27 @@
 //! ```
 
 use anyhow::Result;
-use std::{collections::HashMap, fmt::Debug, hash::Hash, mem::size_of, ops::Div};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Debug,
+	hash::Hash,
+	mem::size_of,
+};
+
+struct Entry<V> {
+	value: V,
+	size: usize,
+	last_access: u64,
+}
 
 /// A generic limited cache that stores key-value pairs up to a specified size limit.
 pub struct LimitedCache<K, V> {
-	cache: HashMap<K, (V, u64)>,
-	max_length: usize,
+	cache: HashMap<K, Entry<V>>,
+	// keys ordered by their last access, the first one is the least recently used
+	access_order: BTreeMap<u64, K>,
+	size_of_entry: fn(&K, &V) -> usize,
+	size: usize,
+	maximum_size: usize,
 	last_index: u64,
 }
 
@@ -34,7 +52,7 @@ where
 {
 	/// Creates a new `LimitedCache` with a specified maximum size.
 	///
-	/// The size limit is determined by the size of the stored types and the provided `maximum_size`.
+	/// Every entry is counted with the size of the stored types.
 	///
 	/// # Arguments
 	/// * `maximum_size` - The maximum size of the cache, in bytes.
@@ -42,13 +60,24 @@ where
 	/// # Panics
 	/// Panics if the `maximum_size` is smaller than the size of a single element.
 	pub fn with_maximum_size(maximum_size: usize) -> Self {
-		let max_length = maximum_size.div(size_of::<K>() + size_of::<V>());
-		if max_length < 1 {
+		if maximum_size < size_of::<K>() + size_of::<V>() {
 			panic!("size is too small to store a single element");
 		}
+		Self::with_size_function(maximum_size, |_, _| size_of::<K>() + size_of::<V>())
+	}
+
+	/// Creates a new `LimitedCache` that calculates the size of every entry with `size_of_entry`.
+	///
+	/// # Arguments
+	/// * `maximum_size` - The maximum size of the cache, in bytes.
+	/// * `size_of_entry` - Returns the number of bytes used by a key and its value.
+	pub fn with_size_function(maximum_size: usize, size_of_entry: fn(&K, &V) -> usize) -> Self {
 		Self {
 			cache: HashMap::new(),
-			max_length,
+			access_order: BTreeMap::new(),
+			size_of_entry,
+			size: 0,
+			maximum_size,
 			last_index: 0,
 		}
 	}
@@ -60,13 +89,15 @@ where
 	/// # Arguments
 	/// * `key` - A reference to the key of the value to retrieve.
 	pub fn get(&mut self, key: &K) -> Option<V> {
-		if let Some(value) = self.cache.get_mut(key) {
-			self.last_index += 1;
-			value.1 = self.last_index;
-			Some(value.0.clone())
-		} else {
-			None
-		}
+		let entry = self.cache.get_mut(key)?;
+		self.last_index += 1;
+		let key = self
+			.access_order
+			.remove(&entry.last_access)
+			.expect("every entry should have an access index");
+		entry.last_access = self.last_index;
+		self.access_order.insert(self.last_index, key);
+		Some(entry.value.clone())
 	}
 
 	pub fn get_or_set<F>(&mut self, key: &K, callback: F) -> Result<V>
@@ -85,6 +116,8 @@ where
 	///
 	/// If adding this key-value pair causes the cache to exceed its maximum size,
 	/// the least recently accessed item(s) will be removed.
+	/// If the key is already present, the existing value is kept.
+	/// Entries larger than the maximum size of the cache are not stored.
 	///
 	/// # Arguments
 	/// * `key` - The key to insert.
@@ -92,35 +125,66 @@ where
 	///
 	/// Returns the value just inserted (for chaining or further manipulation).
 	pub fn add(&mut self, key: K, value: V) -> V {
-		if self.cache.len() >= self.max_length {
-			self.cleanup();
+		if let Some(existing) = self.get(&key) {
+			return existing;
+		}
+
+		let size = (self.size_of_entry)(&key, &value);
+		if size > self.maximum_size {
+			return value;
+		}
+
+		while self.size + size > self.maximum_size {
+			self.remove_least_recently_used();
 		}
 
 		self.last_index += 1;
-		self
-			.cache
-			.entry(key)
-			.or_insert((value, self.last_index))
-			.0
-			.clone()
+		self.size += size;
+		self.access_order.insert(self.last_index, key.clone());
+		self.cache.insert(
+			key,
+			Entry {
+				value: value.clone(),
+				size,
+				last_access: self.last_index,
+			},
+		);
+		value
 	}
 
-	/// A private method to clean up the cache by removing least recently accessed items.
-	///
-	/// This method calculates the median access index and removes any items that have an access index
-	/// less than or equal to the median.
-	fn cleanup(&mut self) {
-		let mut latest_access: Vec<u64> = self.cache.values().map(|e| e.1).collect();
-		latest_access.sort_unstable();
-		let median = latest_access[latest_access.len().div(2)];
-		self.cache.retain(|_, e| {
-			if e.1 <= median {
-				false
-			} else {
-				e.1 = 0;
-				true
-			}
-		});
+	/// Removes all entries.
+	pub fn clear(&mut self) {
+		self.cache.clear();
+		self.access_order.clear();
+		self.size = 0;
+	}
+
+	/// Returns the number of entries.
+	pub fn len(&self) -> usize {
+		self.cache.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.cache.is_empty()
+	}
+
+	/// Returns the size of all entries in bytes.
+	pub fn size(&self) -> usize {
+		self.size
+	}
+
+	pub fn maximum_size(&self) -> usize {
+		self.maximum_size
+	}
+
+	fn remove_least_recently_used(&mut self) {
+		if let Some((_, key)) = self.access_order.pop_first() {
+			let entry = self
+				.cache
+				.remove(&key)
+				.expect("every access index should have an entry");
+			self.size -= entry.size;
+		}
 	}
 }
 
@@ -128,7 +192,8 @@ impl<K, V> Debug for LimitedCache<K, V> {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		f.debug_struct("LimitedCache")
 			.field("length", &self.cache.len())
-			.field("max_length", &self.max_length)
+			.field("size", &self.size)
+			.field("maximum_size", &self.maximum_size)
 			.finish()
 	}
 }
@@ -140,10 +205,15 @@ mod tests {
 	#[test]
 	fn test_cache_initialization() {
 		let cache: LimitedCache<u64, i32> = LimitedCache::with_maximum_size(100);
-		assert_eq!(
-			cache.max_length,
-			100 / (std::mem::size_of::<u64>() + std::mem::size_of::<i32>())
-		);
+		assert_eq!(cache.maximum_size(), 100);
+		assert_eq!(cache.size(), 0);
+		assert!(cache.is_empty());
+	}
+
+	#[test]
+	#[should_panic]
+	fn test_too_small() {
+		LimitedCache::<u64, u64>::with_maximum_size(15);
 	}
 
 	#[test]
@@ -157,6 +227,16 @@ mod tests {
 		assert_eq!(cache.get(&1), Some(100));
 		assert_eq!(cache.get(&2), Some(200));
 		assert_eq!(cache.get(&3), None); // Key 3 was never added
+		assert_eq!(cache.len(), 2);
+		assert_eq!(cache.size(), 16);
+
+		// existing values are kept
+		assert_eq!(cache.add(1, 300), 100);
+		assert_eq!(cache.len(), 2);
+
+		cache.clear();
+		assert_eq!(cache.get(&1), None);
+		assert_eq!(cache.size(), 0);
 	}
 
 	#[test]
@@ -175,15 +255,50 @@ mod tests {
 			assert_eq!(list.as_slice(), result, "error for test index {max}");
 		};
 
+		// the cache has room for 5 entries, so only the 5 most recent ones are kept
 		test(0, &[1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
 		test(1, &[1, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
 		test(2, &[1, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
 		test(3, &[1, 1, 1, 1, 0, 0, 0, 0, 0, 0]);
 		test(4, &[1, 1, 1, 1, 1, 0, 0, 0, 0, 0]);
-		test(5, &[0, 0, 0, 1, 1, 1, 0, 0, 0, 0]);
-		test(6, &[0, 0, 0, 1, 1, 1, 1, 0, 0, 0]);
+		test(5, &[0, 1, 1, 1, 1, 1, 0, 0, 0, 0]);
+		test(6, &[0, 0, 1, 1, 1, 1, 1, 0, 0, 0]);
 		test(7, &[0, 0, 0, 1, 1, 1, 1, 1, 0, 0]);
-		test(8, &[0, 0, 0, 0, 0, 0, 1, 1, 1, 0]);
-		test(9, &[0, 0, 0, 0, 0, 0, 1, 1, 1, 1]);
+		test(8, &[0, 0, 0, 0, 1, 1, 1, 1, 1, 0]);
+		test(9, &[0, 0, 0, 0, 0, 1, 1, 1, 1, 1]);
+	}
+
+	#[test]
+	fn test_least_recently_used() {
+		let mut cache: LimitedCache<u64, u64> = LimitedCache::with_maximum_size(3 * 16);
+		cache.add(1, 1);
+		cache.add(2, 2);
+		cache.add(3, 3);
+		cache.get(&1);
+		cache.add(4, 4);
+
+		assert_eq!(cache.get(&1), Some(1));
+		assert_eq!(cache.get(&2), None);
+		assert_eq!(cache.get(&3), Some(3));
+		assert_eq!(cache.get(&4), Some(4));
+	}
+
+	#[test]
+	fn test_size_function() {
+		let mut cache: LimitedCache<u8, Vec<u8>> =
+			LimitedCache::with_size_function(100, |_, value| value.len());
+		cache.add(1, vec![0; 40]);
+		cache.add(2, vec![0; 40]);
+		assert_eq!(cache.size(), 80);
+
+		// evicts the first entry to make room
+		cache.add(3, vec![0; 30]);
+		assert_eq!(cache.get(&1), None);
+		assert_eq!(cache.size(), 70);
+
+		// too large to be cached at all
+		assert_eq!(cache.add(4, vec![0; 101]).len(), 101);
+		assert_eq!(cache.get(&4), None);
+		assert_eq!(cache.len(), 2);
 	}
 }
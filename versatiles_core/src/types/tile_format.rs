@@ -30,7 +30,7 @@ use std::fmt::Display;
 // Enum representing supported tile formats
 #[allow(clippy::upper_case_acronyms)]
 #[cfg_attr(feature = "cli", derive(ValueEnum))]
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum TileFormat {
 	AVIF,
 	BIN,
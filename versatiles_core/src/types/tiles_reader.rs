@@ -4,7 +4,7 @@ use crate::types::{
 	Blob, TileBBox, TileCompression, TileCoord3, TileStream, TilesReaderParameters,
 };
 #[cfg(feature = "cli")]
-use crate::utils::PrettyPrint;
+use crate::utils::{parse_json, JsonValue, PrettyPrint};
 use anyhow::Result;
 use async_trait::async_trait;
 use futures::lock::Mutex;
@@ -52,9 +52,13 @@ pub trait TilesReaderTrait: Debug + Send + Sync + Unpin {
 	/// probe container
 	#[cfg(feature = "cli")]
 	async fn probe(&mut self, level: ProbeDepth) -> Result<()> {
-		use ProbeDepth::*;
+		self.probe_with(level, &mut PrettyPrint::new()).await
+	}
 
-		let mut print = PrettyPrint::new();
+	/// probe container and write the results to `print`, e.g. to collect them as JSON
+	#[cfg(feature = "cli")]
+	async fn probe_with(&mut self, level: ProbeDepth, print: &mut PrettyPrint) -> Result<()> {
+		use ProbeDepth::*;
 
 		let cat = print.get_category("meta_data").await;
 		cat.add_key_value("name", self.get_name()).await;
@@ -63,7 +67,8 @@ pub trait TilesReaderTrait: Debug + Send + Sync + Unpin {
 
 		let meta_option = self.get_meta()?;
 		if let Some(meta) = meta_option {
-			cat.add_key_value("meta", meta.as_str()).await;
+			let json = parse_json(meta.as_str()).unwrap_or_else(|_| JsonValue::from(meta.as_str()));
+			cat.add_key_value_json("meta", meta.as_str(), json).await;
 		} else {
 			cat.add_key_value("meta", &meta_option).await;
 		}
@@ -96,12 +101,22 @@ pub trait TilesReaderTrait: Debug + Send + Sync + Unpin {
 		let parameters = self.get_parameters();
 		let p = print.get_list("bbox_pyramid").await;
 		for level in parameters.bbox_pyramid.iter_levels() {
-			p.add_value(level).await
+			let json = JsonValue::from(vec![
+				("level", JsonValue::from(level.level)),
+				("x_min", JsonValue::from(level.x_min)),
+				("y_min", JsonValue::from(level.y_min)),
+				("x_max", JsonValue::from(level.x_max)),
+				("y_max", JsonValue::from(level.y_max)),
+				("count", JsonValue::from(level.count_tiles())),
+			]);
+			p.add_value_json(level, json).await
 		}
+		let geo_bbox = parameters.bbox_pyramid.get_geo_bbox();
 		print
-			.add_key_value(
+			.add_key_value_json(
 				"bbox",
-				&format!("{:?}", parameters.bbox_pyramid.get_geo_bbox()),
+				&format!("{:?}", geo_bbox),
+				JsonValue::from(geo_bbox.to_vec()),
 			)
 			.await;
 		print
@@ -208,4 +223,19 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	#[cfg(feature = "cli")]
+	async fn probe_json() -> Result<()> {
+		let mut reader = TestReader::new_dummy();
+		let mut print = PrettyPrint::new_json();
+		reader.probe_with(ProbeDepth::Container, &mut print).await?;
+
+		let json = print.as_json().await.unwrap().stringify();
+		assert_eq!(
+			json,
+			"{\"container\":{\"warnings\":[\"deep container probing is not implemented for this container format\"]},\"meta_data\":{\"container\":\"test container name\",\"meta\":\"test metadata\",\"name\":\"dummy\"},\"parameters\":{\"bbox\":[-180,-85.05112877980659,180,85.05112877980659],\"bbox_pyramid\":[{\"count\":1,\"level\":0,\"x_max\":0,\"x_min\":0,\"y_max\":0,\"y_min\":0},{\"count\":4,\"level\":1,\"x_max\":1,\"x_min\":0,\"y_max\":1,\"y_min\":0},{\"count\":16,\"level\":2,\"x_max\":3,\"x_min\":0,\"y_max\":3,\"y_min\":0},{\"count\":64,\"level\":3,\"x_max\":7,\"x_min\":0,\"y_max\":7,\"y_min\":0}],\"tile compression\":\"Gzip\",\"tile format\":\"PBF\"}}"
+		);
+		Ok(())
+	}
 }
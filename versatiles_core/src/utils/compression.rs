@@ -45,50 +45,31 @@ pub fn optimize_compression(
 	input: &TileCompression,
 	target: TargetCompression,
 ) -> Result<(Blob, TileCompression)> {
+	let output = get_optimal_compression(input, &target)?;
+	Ok((recompress(blob, input, &output)?, output))
+}
+
+/// Returns the compression that `optimize_compression` would choose, without touching any data.
+/// Brotli is preferred over gzip, and gzip over no compression.
+pub fn get_optimal_compression(
+	input: &TileCompression,
+	target: &TargetCompression,
+) -> Result<TileCompression> {
 	if target.compressions.is_empty() {
 		bail!("no compression allowed");
 	}
 
 	if !target.best_compression && target.compressions.contains(*input) {
-		return Ok((blob, *input));
+		return Ok(*input);
 	}
 
-	match input {
-		Uncompressed => {
-			if target.compressions.contains(Brotli) {
-				return Ok((compress_brotli(&blob)?, Brotli));
-			}
-
-			if target.compressions.contains(Gzip) {
-				return Ok((compress_gzip(&blob)?, Gzip));
-			}
-
-			Ok((blob, Uncompressed))
-		}
-		Gzip => {
-			if target.compressions.contains(Brotli) {
-				return Ok((compress_brotli(&decompress_gzip(&blob)?)?, Brotli));
-			}
-
-			if target.compressions.contains(Gzip) {
-				return Ok((blob, Gzip));
-			}
-
-			Ok((decompress_gzip(&blob)?, Uncompressed))
-		}
-		Brotli => {
-			if target.compressions.contains(Brotli) {
-				return Ok((blob, Brotli));
-			}
-			let data = decompress_brotli(&blob)?;
-
-			if target.compressions.contains(Gzip) {
-				return Ok((compress_gzip(&data)?, Gzip));
-			}
-
-			Ok((data, Uncompressed))
-		}
-	}
+	Ok(if target.compressions.contains(Brotli) {
+		Brotli
+	} else if target.compressions.contains(Gzip) {
+		Gzip
+	} else {
+		Uncompressed
+	})
 }
 
 pub fn recompress(
@@ -257,6 +238,10 @@ mod tests {
 				Gzip => blob_gzip.clone(),
 				Brotli => blob_brotli.clone(),
 			};
+			assert_eq!(
+				get_optimal_compression(&compression_in, &target)?,
+				compression_exp
+			);
 			let (data_res, compression_res) = optimize_compression(data_in, &compression_in, target)?;
 			assert_eq!(
 				compression_res, compression_exp,
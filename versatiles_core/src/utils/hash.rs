@@ -0,0 +1,23 @@
+//! Non-cryptographic hash of binary data, e.g. to deduplicate tiles or to build ETags.
+
+/// Calculates the 128 bit FNV-1a hash of `data`.
+pub fn get_fnv_hash(data: &[u8]) -> u128 {
+	let mut hash: u128 = 0x6c62272e07bb014262b821756295c58d;
+	for byte in data {
+		hash ^= *byte as u128;
+		hash = hash.wrapping_mul(0x0000000001000000000000000000013b);
+	}
+	hash
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn fnv_hash() {
+		assert_eq!(get_fnv_hash(b""), 0x6c62272e07bb014262b821756295c58d);
+		assert_eq!(get_fnv_hash(b"a"), 0xd228cb696f1a8caf78912b704e4a8964);
+		assert_ne!(get_fnv_hash(b"tile"), get_fnv_hash(b"tilf"));
+	}
+}
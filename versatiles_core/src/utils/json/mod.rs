@@ -1,5 +1,6 @@
 mod parse;
 mod read;
+mod stringify;
 mod types;
 
 pub use parse::*;
@@ -0,0 +1,100 @@
+use super::JsonValue;
+use std::fmt::Write;
+
+impl JsonValue {
+	/// Serializes the value as compact JSON.
+	pub fn stringify(&self) -> String {
+		let mut text = String::new();
+		write_json(&mut text, self);
+		text
+	}
+}
+
+fn write_json(text: &mut String, value: &JsonValue) {
+	use JsonValue::*;
+	match value {
+		Array(array) => {
+			text.push('[');
+			for (index, entry) in array.iter().enumerate() {
+				if index > 0 {
+					text.push(',');
+				}
+				write_json(text, entry);
+			}
+			text.push(']');
+		}
+		Boolean(b) => text.push_str(if *b { "true" } else { "false" }),
+		Null => text.push_str("null"),
+		Num(n) => write_number(text, *n),
+		Object(object) => {
+			text.push('{');
+			for (index, (key, entry)) in object.iter().enumerate() {
+				if index > 0 {
+					text.push(',');
+				}
+				write_string(text, key);
+				text.push(':');
+				write_json(text, entry);
+			}
+			text.push('}');
+		}
+		Str(s) => write_string(text, s),
+	}
+}
+
+fn write_number(text: &mut String, n: f64) {
+	if !n.is_finite() {
+		text.push_str("null");
+	} else if n.fract() == 0.0 && n.abs() < 1e15 {
+		write!(text, "{}", n as i64).unwrap();
+	} else {
+		write!(text, "{n}").unwrap();
+	}
+}
+
+fn write_string(text: &mut String, s: &str) {
+	text.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => text.push_str("\\\""),
+			'\\' => text.push_str("\\\\"),
+			'\n' => text.push_str("\\n"),
+			'\r' => text.push_str("\\r"),
+			'\t' => text.push_str("\\t"),
+			c if (c as u32) < 0x20 => write!(text, "\\u{:04x}", c as u32).unwrap(),
+			c => text.push(c),
+		}
+	}
+	text.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::parse_json;
+
+	#[test]
+	fn simple_values() {
+		assert_eq!(JsonValue::Null.stringify(), "null");
+		assert_eq!(JsonValue::from(true).stringify(), "true");
+		assert_eq!(JsonValue::from(42).stringify(), "42");
+		assert_eq!(JsonValue::from(-0.5).stringify(), "-0.5");
+		assert_eq!(JsonValue::from(f64::NAN).stringify(), "null");
+		assert_eq!(
+			JsonValue::from("a \"quoted\"\n\\ \u{1}").stringify(),
+			"\"a \\\"quoted\\\"\\n\\\\ \\u0001\""
+		);
+	}
+
+	#[test]
+	fn nested_values() {
+		let value = JsonValue::from(vec![
+			("list", JsonValue::from(vec![1, 2, 3])),
+			("name", JsonValue::from("tiles")),
+			("empty", JsonValue::Object(Default::default())),
+		]);
+		let text = value.stringify();
+		assert_eq!(text, "{\"empty\":{},\"list\":[1,2,3],\"name\":\"tiles\"}");
+		assert_eq!(parse_json(&text).unwrap(), value);
+	}
+}
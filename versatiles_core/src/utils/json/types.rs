@@ -74,6 +74,24 @@ impl From<i32> for JsonValue {
 	}
 }
 
+impl From<u8> for JsonValue {
+	fn from(input: u8) -> Self {
+		JsonValue::Num(input as f64)
+	}
+}
+
+impl From<u32> for JsonValue {
+	fn from(input: u32) -> Self {
+		JsonValue::Num(input as f64)
+	}
+}
+
+impl From<u64> for JsonValue {
+	fn from(input: u64) -> Self {
+		JsonValue::Num(input as f64)
+	}
+}
+
 impl<T> From<Vec<(&str, T)>> for JsonValue
 where
 	JsonValue: From<T>,
@@ -134,6 +152,13 @@ mod tests {
 		assert_eq!(result, JsonValue::Num(42.0));
 	}
 
+	#[test]
+	fn test_from_unsigned() {
+		assert_eq!(JsonValue::from(7u8), JsonValue::Num(7.0));
+		assert_eq!(JsonValue::from(7u32), JsonValue::Num(7.0));
+		assert_eq!(JsonValue::from(7u64), JsonValue::Num(7.0));
+	}
+
 	#[test]
 	fn test_from_vec_of_tuples() {
 		let result: JsonValue = vec![("key1", "value1"), ("key2", "value2")].into();
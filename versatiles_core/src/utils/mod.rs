@@ -1,17 +1,21 @@
 mod byte_iterator;
 mod compression;
 mod csv;
+mod hash;
 pub mod io;
 mod json;
 #[cfg(feature = "cli")]
 mod pretty_print;
 pub mod progress;
+mod sniffing;
 mod transform_coord;
 
 pub use byte_iterator::*;
 pub use compression::*;
 pub use csv::*;
+pub use hash::*;
 pub use json::*;
 #[cfg(feature = "cli")]
 pub use pretty_print::*;
+pub use sniffing::*;
 pub use transform_coord::*;
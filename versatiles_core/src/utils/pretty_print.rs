@@ -1,4 +1,6 @@
+use super::{parse_json, JsonValue};
 use colored::*;
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Display};
 use std::io::Write;
 use std::sync::Arc;
@@ -6,6 +8,8 @@ use tokio::sync::Mutex;
 
 struct PrettyPrinter {
 	indention: String,
+	/// if set, everything is collected into a JSON document instead of being printed
+	json: Option<Mutex<JsonValue>>,
 	#[cfg(not(any(test, feature = "test")))]
 	output: Arc<Mutex<Box<dyn Write + Send>>>,
 	#[cfg(any(test, feature = "test"))]
@@ -19,6 +23,7 @@ impl PrettyPrinter {
 
 		Self {
 			indention: String::from("   "),
+			json: None,
 
 			#[cfg(not(any(test, feature = "test")))]
 			output: Arc::new(Mutex::new(Box::new(stderr()))),
@@ -50,6 +55,8 @@ pub struct PrettyPrint {
 	prefix: String,
 	suffix: String,
 	printer: Arc<PrettyPrinter>,
+	/// keys of the JSON node this instance writes to
+	path: Vec<String>,
 }
 
 impl PrettyPrint {
@@ -58,39 +65,159 @@ impl PrettyPrint {
 			prefix: String::from(""),
 			suffix: String::from("\n"),
 			printer: Arc::new(PrettyPrinter::new()),
+			path: Vec::new(),
 		}
 	}
 
-	fn new_indented(&mut self) -> Self {
+	/// Creates a printer that collects all categories, lists and values into one JSON document.
+	/// Use [`PrettyPrint::as_json`] to get the result.
+	pub fn new_json() -> Self {
+		let mut printer = PrettyPrinter::new();
+		printer.json = Some(Mutex::new(JsonValue::Object(BTreeMap::new())));
+		Self {
+			printer: Arc::new(printer),
+			..Self::new()
+		}
+	}
+
+	fn new_indented(&mut self, key: &str) -> Self {
+		let mut path = self.path.clone();
+		path.push(key.to_string());
 		Self {
 			prefix: format!("{}{}", self.prefix, self.printer.indention),
 			suffix: self.suffix.clone(),
 			printer: self.printer.clone(),
+			path,
 		}
 	}
 
 	pub async fn get_category(&mut self, text: &str) -> PrettyPrint {
-		self.write_line(text.white().bold().to_string() + ":").await;
-		self.new_indented()
+		if !self
+			.add_json(JsonEntry::KeyValue(
+				text,
+				JsonValue::Object(BTreeMap::new()),
+			))
+			.await
+		{
+			self.write_line(text.white().bold().to_string() + ":").await;
+		}
+		self.new_indented(text)
 	}
 
 	pub async fn get_list(&mut self, text: &str) -> PrettyPrint {
-		self.write_line(text.white().to_string() + ":").await;
-		self.new_indented()
+		if !self
+			.add_json(JsonEntry::KeyValue(text, JsonValue::Array(Vec::new())))
+			.await
+		{
+			self.write_line(text.white().to_string() + ":").await;
+		}
+		self.new_indented(text)
 	}
 
 	pub async fn add_warning(&self, text: &str) {
-		self.write_line(text.yellow().bold()).await;
+		if !self.add_json(JsonEntry::Warning(text)).await {
+			self.write_line(text.yellow().bold()).await;
+		}
 	}
 
 	pub async fn add_key_value<K: Display + ?Sized, V: Debug + ?Sized>(&self, key: &K, value: &V) {
 		self
-			.write_line(format!("{key}: {}", get_formatted_value(value)))
+			.add_key_value_json(key, value, debug_to_json(value))
 			.await;
 	}
 
+	/// Like [`PrettyPrint::add_key_value`], but uses `json` as value of the JSON document.
+	pub async fn add_key_value_json<K: Display + ?Sized, V: Debug + ?Sized>(
+		&self,
+		key: &K,
+		value: &V,
+		json: JsonValue,
+	) {
+		if !self
+			.add_json(JsonEntry::KeyValue(&key.to_string(), json))
+			.await
+		{
+			self
+				.write_line(format!("{key}: {}", get_formatted_value(value)))
+				.await;
+		}
+	}
+
 	pub async fn add_value<V: Debug>(&self, value: &V) {
-		self.write_line(get_formatted_value(value)).await;
+		self.add_value_json(value, debug_to_json(value)).await;
+	}
+
+	/// Like [`PrettyPrint::add_value`], but uses `json` as value of the JSON document.
+	pub async fn add_value_json<V: Debug>(&self, value: &V, json: JsonValue) {
+		if !self.add_json(JsonEntry::Value(json)).await {
+			self.write_line(get_formatted_value(value)).await;
+		}
+	}
+
+	/// Returns the collected JSON document, if this printer was created with [`PrettyPrint::new_json`].
+	pub async fn as_json(&self) -> Option<JsonValue> {
+		match &self.printer.json {
+			Some(json) => Some(json.lock().await.clone()),
+			None => None,
+		}
+	}
+
+	/// Adds an entry to the JSON document. Returns false if this printer is not in JSON mode.
+	async fn add_json(&self, entry: JsonEntry<'_>) -> bool {
+		let mut root = match &self.printer.json {
+			Some(json) => json.lock().await,
+			None => return false,
+		};
+
+		// missing nodes of categories and lists are created
+		let mut node = &mut *root;
+		for key in self.path.iter() {
+			node = match node {
+				JsonValue::Object(object) => object
+					.entry(key.clone())
+					.or_insert_with(|| JsonValue::Object(BTreeMap::new())),
+				JsonValue::Array(array) => {
+					let index = array.iter().rposition(
+						|entry| matches!(entry, JsonValue::Object(object) if object.contains_key(key)),
+					);
+					let index = index.unwrap_or_else(|| {
+						array.push(JsonValue::from(vec![(
+							key.as_str(),
+							JsonValue::Object(BTreeMap::new()),
+						)]));
+						array.len() - 1
+					});
+					match &mut array[index] {
+						JsonValue::Object(object) => match object.get_mut(key) {
+							Some(child) => child,
+							None => return true,
+						},
+						_ => return true,
+					}
+				}
+				// the category has been overwritten by a value, so there is no place for this entry
+				_ => return true,
+			};
+		}
+
+		match (node, entry) {
+			(JsonValue::Object(object), JsonEntry::KeyValue(key, value)) => {
+				object.insert(key.to_string(), value);
+			}
+			(JsonValue::Object(object), JsonEntry::Value(value)) => {
+				push_to_array(object, "values", value);
+			}
+			(JsonValue::Object(object), JsonEntry::Warning(text)) => {
+				push_to_array(object, "warnings", JsonValue::from(text));
+			}
+			(JsonValue::Array(array), JsonEntry::KeyValue(key, value)) => {
+				array.push(JsonValue::from(vec![(key, value)]));
+			}
+			(JsonValue::Array(array), JsonEntry::Value(value)) => array.push(value),
+			(JsonValue::Array(array), JsonEntry::Warning(text)) => array.push(JsonValue::from(text)),
+			_ => {}
+		}
+		true
 	}
 
 	async fn write_line<T: Display>(&self, line: T) {
@@ -117,6 +244,44 @@ impl Default for PrettyPrint {
 	}
 }
 
+enum JsonEntry<'a> {
+	KeyValue(&'a str, JsonValue),
+	Value(JsonValue),
+	Warning(&'a str),
+}
+
+fn push_to_array(object: &mut BTreeMap<String, JsonValue>, key: &str, value: JsonValue) {
+	match object
+		.entry(key.to_string())
+		.or_insert_with(|| JsonValue::Array(Vec::new()))
+	{
+		JsonValue::Array(array) => array.push(value),
+		other => *other = JsonValue::Array(vec![other.clone(), value]),
+	}
+}
+
+/// Converts the Debug representation of a value into JSON.
+/// Numbers, booleans, strings, lists and maps of them are converted, anything else becomes a string.
+fn debug_to_json<V: Debug + ?Sized>(value: &V) -> JsonValue {
+	let text = format!("{value:?}");
+	match text.as_str() {
+		"true" => return JsonValue::Boolean(true),
+		"false" => return JsonValue::Boolean(false),
+		"None" => return JsonValue::Null,
+		_ => {}
+	}
+	if let Ok(number) = text.parse::<f64>() {
+		return JsonValue::Num(number);
+	}
+	let is_enclosed = |a: char, b: char| text.len() >= 2 && text.starts_with(a) && text.ends_with(b);
+	if is_enclosed('"', '"') || is_enclosed('[', ']') || is_enclosed('{', '}') {
+		if let Ok(json) = parse_json(&text) {
+			return json;
+		}
+	}
+	JsonValue::Str(text)
+}
+
 fn get_formatted_value<V: Debug + ?Sized>(value: &V) -> ColoredString {
 	let type_name = std::any::type_name::<V>();
 	if type_name.starts_with("versatiles_lib::shared::") {
@@ -176,6 +341,51 @@ mod tests {
 		);
 	}
 
+	#[tokio::test]
+	async fn test_json() {
+		let mut printer = PrettyPrint::new_json();
+
+		printer.add_warning("test_warning_1").await;
+		let mut cat = printer.get_category("category").await;
+		let list = cat.get_list("list").await;
+		list.add_value(&"value").await;
+		list
+			.add_value_json(&"custom", JsonValue::from(vec![("a", 1)]))
+			.await;
+		cat.add_key_value("number", &1234).await;
+		cat.add_key_value("string", "text").await;
+		cat.add_key_value("map", &BTreeMap::from([("b", vec![1, 2])]))
+			.await;
+		cat.add_key_value("option", &None::<u8>).await;
+		cat.add_key_value("debug", &Some(5)).await;
+		cat.add_warning("test_warning_2").await;
+
+		assert_eq!(printer.as_string().await, "");
+		assert_eq!(
+			printer.as_json().await.unwrap().stringify(),
+			"{\"category\":{\"debug\":\"Some(5)\",\"list\":[\"value\",{\"a\":1}],\"map\":{\"b\":[1,2]},\"number\":1234,\"option\":null,\"string\":\"text\",\"warnings\":[\"test_warning_2\"]},\"warnings\":[\"test_warning_1\"]}"
+		);
+		assert!(PrettyPrint::new().as_json().await.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_json_overwritten_category() {
+		let mut printer = PrettyPrint::new_json();
+
+		let mut cat = printer.get_category("category").await;
+		printer.add_key_value("category", &1).await;
+		cat.add_key_value("dropped", &2).await;
+		cat.get_list("list").await.add_value(&3).await;
+
+		let mut list = printer.get_list("list").await;
+		list.get_category("entry").await.add_value(&4).await;
+
+		assert_eq!(
+			printer.as_json().await.unwrap().stringify(),
+			"{\"category\":1,\"list\":[{\"entry\":{\"values\":[4]}}]}"
+		);
+	}
+
 	#[test]
 	#[should_panic] // everybody should panic
 	fn x() {
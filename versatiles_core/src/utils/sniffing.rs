@@ -0,0 +1,185 @@
+//! Detects the format and the compression of a tile by inspecting its content.
+//!
+//! Formats are detected by their magic bytes (PNG, JPEG, WebP, AVIF), by their structure (vector tiles
+//! as protobuf) or by their first character (JSON, SVG). Gzip is detected by its header. Brotli has no
+//! header, so it is only assumed if the blob can be decompressed and contains a detectable format.
+
+use crate::{
+	types::{Blob, TileCompression, TileFormat},
+	utils::{decompress_brotli, decompress_gzip},
+};
+
+/// Detects the format and the compression of a tile.
+/// Returns `None` if the content could not be identified.
+pub fn sniff_tile(blob: &Blob) -> Option<(TileFormat, TileCompression)> {
+	let data = blob.as_slice();
+
+	if data.starts_with(&[0x1f, 0x8b]) {
+		let blob = decompress_gzip(blob).ok()?;
+		return sniff_format(&blob).map(|format| (format, TileCompression::Gzip));
+	}
+
+	if let Some(format) = sniff_format(blob) {
+		return Some((format, TileCompression::Uncompressed));
+	}
+
+	let blob = decompress_brotli(blob).ok()?;
+	sniff_format(&blob).map(|format| (format, TileCompression::Brotli))
+}
+
+/// Detects the format of an uncompressed tile.
+/// Returns `None` if the content could not be identified.
+pub fn sniff_format(blob: &Blob) -> Option<TileFormat> {
+	let data = blob.as_slice();
+
+	if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+		return Some(TileFormat::PNG);
+	}
+
+	if data.starts_with(&[0xff, 0xd8, 0xff]) {
+		return Some(TileFormat::JPG);
+	}
+
+	if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+		return Some(TileFormat::WEBP);
+	}
+
+	if data.len() >= 12 && &data[4..8] == b"ftyp" && matches!(&data[8..12], b"avif" | b"avis") {
+		return Some(TileFormat::AVIF);
+	}
+
+	if is_vector_tile(data) {
+		return Some(TileFormat::PBF);
+	}
+
+	let text = data.trim_ascii_start();
+	if text.starts_with(b"{") || text.starts_with(b"[") {
+		return Some(TileFormat::JSON);
+	}
+	if text.starts_with(b"<svg") || (text.starts_with(b"<?xml") && contains(text, b"<svg")) {
+		return Some(TileFormat::SVG);
+	}
+
+	None
+}
+
+/// Returns true if both formats can be stored in the same way, e.g. GeoJSON is JSON.
+pub fn is_format_compatible(format: TileFormat, sniffed_format: TileFormat) -> bool {
+	use TileFormat::*;
+	match sniffed_format {
+		JSON => matches!(format, JSON | GEOJSON | TOPOJSON),
+		_ => format == sniffed_format,
+	}
+}
+
+/// A vector tile is a protobuf message that contains only layers (field 3, length delimited).
+fn is_vector_tile(data: &[u8]) -> bool {
+	if data.is_empty() {
+		return false;
+	}
+
+	let mut pos = 0;
+	while pos < data.len() {
+		match read_varint(data, &mut pos) {
+			Some(0x1a) => {}
+			_ => return false,
+		}
+		let length = match read_varint(data, &mut pos) {
+			Some(length) => length as usize,
+			None => return false,
+		};
+		pos = match pos.checked_add(length) {
+			Some(pos) if pos <= data.len() => pos,
+			_ => return false,
+		};
+	}
+	true
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+	let mut value = 0u64;
+	for shift in (0..64).step_by(7) {
+		let byte = *data.get(*pos)?;
+		*pos += 1;
+		value |= ((byte & 0x7f) as u64) << shift;
+		if byte & 0x80 == 0 {
+			return Some(value);
+		}
+	}
+	None
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+	haystack
+		.windows(needle.len())
+		.any(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::utils::{compress_brotli, compress_gzip};
+	use TileCompression::*;
+	use TileFormat::*;
+
+	fn pbf() -> Blob {
+		Blob::from(vec![0x1a, 0x03, 0x0a, 0x01, 0x61, 0x1a, 0x00])
+	}
+
+	#[test]
+	fn formats() {
+		let check = |data: &[u8], format: Option<TileFormat>| {
+			assert_eq!(sniff_format(&Blob::from(data.to_vec())), format, "{data:?}");
+		};
+
+		check(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", Some(PNG));
+		check(&[0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10], Some(JPG));
+		check(b"RIFF\x24\0\0\0WEBPVP8L", Some(WEBP));
+		check(b"\0\0\0\x1cftypavif\0\0\0\0", Some(AVIF));
+		check(pbf().as_slice(), Some(PBF));
+		check(b" \n{\"type\":\"FeatureCollection\"}", Some(JSON));
+		check(b"[1,2,3]", Some(JSON));
+		check(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>", Some(SVG));
+		check(b"<?xml version=\"1.0\"?>\n<svg/>", Some(SVG));
+		check(b"<?xml version=\"1.0\"?>\n<html/>", None);
+		check(b"", None);
+		check(b"hello world", None);
+		check(&[0x1a, 0x05, 0x0a], None);
+		check(&[0x1a, 0xff], None);
+		check(&[0x0a, 0x00], None);
+	}
+
+	#[test]
+	fn compressions() {
+		let blob = pbf();
+		assert_eq!(sniff_tile(&blob), Some((PBF, Uncompressed)));
+		assert_eq!(
+			sniff_tile(&compress_gzip(&blob).unwrap()),
+			Some((PBF, Gzip))
+		);
+		assert_eq!(
+			sniff_tile(&compress_brotli(&blob).unwrap()),
+			Some((PBF, Brotli))
+		);
+
+		let blob = Blob::from("{\"hello\":\"world\"}");
+		assert_eq!(
+			sniff_tile(&compress_brotli(&blob).unwrap()),
+			Some((JSON, Brotli))
+		);
+
+		let blob = Blob::from("unknown content");
+		assert_eq!(sniff_tile(&blob), None);
+		assert_eq!(sniff_tile(&compress_gzip(&blob).unwrap()), None);
+		assert_eq!(sniff_tile(&Blob::new_empty()), None);
+	}
+
+	#[test]
+	fn compatibility() {
+		assert!(is_format_compatible(GEOJSON, JSON));
+		assert!(is_format_compatible(TOPOJSON, JSON));
+		assert!(is_format_compatible(PNG, PNG));
+		assert!(!is_format_compatible(JSON, GEOJSON));
+		assert!(!is_format_compatible(PBF, JSON));
+	}
+}
@@ -1,5 +1,57 @@
 use super::GeoFeature;
+use crate::utils::JsonValue;
 
 pub struct GeoCollection {
 	pub features: Vec<GeoFeature>,
 }
+
+impl GeoCollection {
+	/// Returns the collection as a GeoJSON FeatureCollection.
+	pub fn to_json(&self) -> JsonValue {
+		JsonValue::from(vec![
+			("type", JsonValue::from("FeatureCollection")),
+			(
+				"features",
+				JsonValue::Array(self.features.iter().map(GeoFeature::to_json).collect()),
+			),
+		])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{GeoProperties, GeoValue, Geometry};
+
+	#[test]
+	fn to_json() {
+		let mut feature = GeoFeature::new(Geometry::new_line_string(vec![[1, 2], [3, 4]]));
+		feature.set_id(GeoValue::from(7));
+		feature.set_properties(GeoProperties::from(vec![
+			("name", GeoValue::from("A \"quoted\" name")),
+			("height", GeoValue::from(-1.5)),
+			("open", GeoValue::from(true)),
+		]));
+		let point = GeoFeature::new(Geometry::new_point([13.4, 52.5]));
+
+		let collection = GeoCollection {
+			features: vec![feature, point],
+		};
+		assert_eq!(
+			collection.to_json().stringify(),
+			"{\"features\":[{\"geometry\":{\"coordinates\":[[1,2],[3,4]],\"type\":\"LineString\"},\"id\":7,\"properties\":{\"height\":-1.5,\"name\":\"A \\\"quoted\\\" name\",\"open\":true},\"type\":\"Feature\"},{\"geometry\":{\"coordinates\":[13.4,52.5],\"type\":\"Point\"},\"properties\":{},\"type\":\"Feature\"}],\"type\":\"FeatureCollection\"}"
+		);
+	}
+
+	#[test]
+	fn map_coordinates() {
+		let geometry = Geometry::new_example().map_coordinates(|[x, y]| [x * 2.0, -y]);
+		let Geometry::MultiPolygon(polygons) = geometry else {
+			panic!("should still be a multi polygon")
+		};
+		assert_eq!(
+			polygons.0[0][0][..3],
+			[[0.0, 0.0], [10.0, 0.0], [5.0, -4.0]]
+		);
+	}
+}
@@ -3,6 +3,7 @@
 use std::fmt::Debug;
 
 use super::*;
+use crate::utils::JsonValue;
 
 #[derive(Clone, Debug)]
 pub struct GeoFeature {
@@ -35,6 +36,19 @@ impl GeoFeature {
 		self.properties.insert(key, GeoValue::from(value));
 	}
 
+	/// Returns the feature as a GeoJSON object.
+	pub fn to_json(&self) -> JsonValue {
+		let mut entries = vec![
+			("type", JsonValue::from("Feature")),
+			("geometry", self.geometry.to_json()),
+			("properties", self.properties.to_json()),
+		];
+		if let Some(id) = &self.id {
+			entries.push(("id", id.to_json()));
+		}
+		JsonValue::from(entries)
+	}
+
 	#[cfg(test)]
 	pub fn new_example() -> Self {
 		Self {
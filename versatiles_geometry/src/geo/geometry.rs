@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use super::*;
+use crate::utils::JsonValue;
 use std::fmt::Debug;
 
 #[derive(Clone, PartialEq)]
@@ -54,6 +55,52 @@ impl Geometry {
 		}
 	}
 
+	/// Applies `f` to every coordinate, e.g. to project the geometry.
+	pub fn map_coordinates(self, f: impl Fn(Coordinates0) -> Coordinates0) -> Self {
+		let map1 = |c: Coordinates1| c.into_iter().map(&f).collect::<Coordinates1>();
+		let map2 = |c: Coordinates2| c.into_iter().map(map1).collect::<Coordinates2>();
+		let map3 = |c: Coordinates3| c.into_iter().map(map2).collect::<Coordinates3>();
+		match self {
+			Geometry::Point(g) => Geometry::Point(PointGeometry(f(g.0))),
+			Geometry::LineString(g) => Geometry::LineString(LineStringGeometry(map1(g.0))),
+			Geometry::Polygon(g) => Geometry::Polygon(PolygonGeometry(map2(g.0))),
+			Geometry::MultiPoint(g) => Geometry::MultiPoint(MultiPointGeometry(map1(g.0))),
+			Geometry::MultiLineString(g) => {
+				Geometry::MultiLineString(MultiLineStringGeometry(map2(g.0)))
+			}
+			Geometry::MultiPolygon(g) => Geometry::MultiPolygon(MultiPolygonGeometry(map3(g.0))),
+		}
+	}
+
+	/// Returns the geometry as a GeoJSON object.
+	pub fn to_json(&self) -> JsonValue {
+		fn json0(c: &Coordinates0) -> JsonValue {
+			JsonValue::from(c.to_vec())
+		}
+		fn json1(c: &Coordinates1) -> JsonValue {
+			JsonValue::Array(c.iter().map(json0).collect())
+		}
+		fn json2(c: &Coordinates2) -> JsonValue {
+			JsonValue::Array(c.iter().map(json1).collect())
+		}
+		fn json3(c: &Coordinates3) -> JsonValue {
+			JsonValue::Array(c.iter().map(json2).collect())
+		}
+
+		let coordinates = match self {
+			Geometry::Point(g) => json0(&g.0),
+			Geometry::LineString(g) => json1(&g.0),
+			Geometry::Polygon(g) => json2(&g.0),
+			Geometry::MultiPoint(g) => json1(&g.0),
+			Geometry::MultiLineString(g) => json2(&g.0),
+			Geometry::MultiPolygon(g) => json3(&g.0),
+		};
+		JsonValue::from(vec![
+			("type", JsonValue::from(self.get_type_name())),
+			("coordinates", coordinates),
+		])
+	}
+
 	pub fn new_example() -> Self {
 		Self::new_multi_polygon(vec![
 			vec![
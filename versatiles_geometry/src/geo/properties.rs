@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 
 use super::GeoValue;
+use crate::utils::JsonValue;
 use std::{
 	collections::{btree_map, BTreeMap},
 	fmt::Debug,
@@ -40,6 +41,15 @@ impl GeoProperties {
 	pub fn iter(&self) -> btree_map::Iter<String, GeoValue> {
 		self.properties.iter()
 	}
+	pub fn to_json(&self) -> JsonValue {
+		JsonValue::Object(
+			self
+				.properties
+				.iter()
+				.map(|(key, value)| (key.to_owned(), value.to_json()))
+				.collect(),
+		)
+	}
 }
 
 impl IntoIterator for GeoProperties {
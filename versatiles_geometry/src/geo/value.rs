@@ -1,3 +1,4 @@
+use crate::utils::JsonValue;
 use anyhow::{bail, Result};
 use lazy_static::lazy_static;
 use regex::{Regex, RegexBuilder};
@@ -170,6 +171,18 @@ impl GeoValue {
 		}
 	}
 
+	pub fn type_as_str(&self) -> &str {
+		match self {
+			GeoValue::String(_) => "string",
+			GeoValue::Float(_) => "float",
+			GeoValue::Double(_) => "double",
+			GeoValue::Int(_) => "int",
+			GeoValue::UInt(_) => "uint",
+			GeoValue::Bool(_) => "bool",
+			GeoValue::Null => "null",
+		}
+	}
+
 	pub fn parse_str(value: &str) -> Self {
 		lazy_static! {
 			static ref REG_DOUBLE: Regex = RegexBuilder::new(r"^\-?\d*\.\d+$").build().unwrap();
@@ -195,6 +208,18 @@ impl GeoValue {
 		}
 	}
 
+	pub fn to_json(&self) -> JsonValue {
+		match self {
+			GeoValue::Bool(v) => JsonValue::Boolean(*v),
+			GeoValue::Double(v) => JsonValue::Num(*v),
+			GeoValue::Float(v) => JsonValue::Num(*v as f64),
+			GeoValue::Int(v) => JsonValue::Num(*v as f64),
+			GeoValue::Null => JsonValue::Null,
+			GeoValue::String(v) => JsonValue::from(v.as_str()),
+			GeoValue::UInt(v) => JsonValue::Num(*v as f64),
+		}
+	}
+
 	pub fn as_u64(&self) -> Result<u64> {
 		match self {
 			GeoValue::Int(v) => Ok(*v as u64),
@@ -208,6 +233,17 @@ impl GeoValue {
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_type_as_str() {
+		assert_eq!(GeoValue::from("a").type_as_str(), "string");
+		assert_eq!(GeoValue::from(1.0f32).type_as_str(), "float");
+		assert_eq!(GeoValue::from(1.0f64).type_as_str(), "double");
+		assert_eq!(GeoValue::from(-1).type_as_str(), "int");
+		assert_eq!(GeoValue::from(1u64).type_as_str(), "uint");
+		assert_eq!(GeoValue::from(true).type_as_str(), "bool");
+		assert_eq!(GeoValue::Null.type_as_str(), "null");
+	}
+
 	#[test]
 	fn test_geo_value_ord() {
 		// Test ordering within the same variant
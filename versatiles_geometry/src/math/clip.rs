@@ -0,0 +1,263 @@
+use crate::geo::*;
+
+/// Rectangle as `[x_min, y_min, x_max, y_max]`
+pub type ClipBox = [f64; 4];
+
+pub fn clip_points(c: &Coordinates1, bbox: &ClipBox) -> Coordinates1 {
+	c.iter()
+		.filter(|p| p[0] >= bbox[0] && p[0] <= bbox[2] && p[1] >= bbox[1] && p[1] <= bbox[3])
+		.cloned()
+		.collect()
+}
+
+/// Clips a line string with the Liang–Barsky algorithm. A line that leaves and enters the box is split into several lines.
+pub fn clip_line(c: &Coordinates1, bbox: &ClipBox) -> Coordinates2 {
+	let mut lines: Coordinates2 = Vec::new();
+	let mut line: Coordinates1 = Vec::new();
+
+	for segment in c.windows(2) {
+		match clip_segment(segment[0], segment[1], bbox) {
+			Some((p0, p1)) => {
+				if line.last() != Some(&p0) {
+					if line.len() >= 2 {
+						lines.push(line);
+					}
+					line = vec![p0];
+				}
+				line.push(p1);
+			}
+			None => {
+				if line.len() >= 2 {
+					lines.push(line);
+				}
+				line = Vec::new();
+			}
+		}
+	}
+	if line.len() >= 2 {
+		lines.push(line);
+	}
+
+	lines
+}
+
+fn clip_segment(
+	p0: Coordinates0,
+	p1: Coordinates0,
+	bbox: &ClipBox,
+) -> Option<(Coordinates0, Coordinates0)> {
+	let d = [p1[0] - p0[0], p1[1] - p0[1]];
+	let mut t0 = 0.0f64;
+	let mut t1 = 1.0f64;
+
+	for (p, q) in [
+		(-d[0], p0[0] - bbox[0]),
+		(d[0], bbox[2] - p0[0]),
+		(-d[1], p0[1] - bbox[1]),
+		(d[1], bbox[3] - p0[1]),
+	] {
+		if p == 0.0 {
+			if q < 0.0 {
+				return None;
+			}
+		} else {
+			let t = q / p;
+			if p < 0.0 {
+				t0 = t0.max(t);
+			} else {
+				t1 = t1.min(t);
+			}
+		}
+	}
+
+	if t0 > t1 {
+		return None;
+	}
+	let at = |t: f64| {
+		if t == 0.0 {
+			p0
+		} else if t == 1.0 {
+			p1
+		} else {
+			[p0[0] + t * d[0], p0[1] + t * d[1]]
+		}
+	};
+	Some((at(t0), at(t1)))
+}
+
+/// Clips a closed ring with the Sutherland–Hodgman algorithm, keeping its orientation.
+/// Returns an empty ring if nothing is left.
+pub fn clip_ring(c: &Coordinates1, bbox: &ClipBox) -> Coordinates1 {
+	let mut ring: Coordinates1 = c.clone();
+	if ring.len() > 1 && ring.first() == ring.last() {
+		ring.pop();
+	}
+
+	// (axis, limit, whether the limit is a maximum)
+	for (axis, limit, is_max) in [
+		(0, bbox[0], false),
+		(0, bbox[2], true),
+		(1, bbox[1], false),
+		(1, bbox[3], true),
+	] {
+		if ring.is_empty() {
+			break;
+		}
+		let inside = |p: &Coordinates0| {
+			if is_max {
+				p[axis] <= limit
+			} else {
+				p[axis] >= limit
+			}
+		};
+		let intersect = |a: &Coordinates0, b: &Coordinates0| {
+			let t = (limit - a[axis]) / (b[axis] - a[axis]);
+			let mut p = [a[0] + t * (b[0] - a[0]), a[1] + t * (b[1] - a[1])];
+			p[axis] = limit;
+			p
+		};
+
+		let input = std::mem::take(&mut ring);
+		let mut previous = input.last().unwrap();
+		for point in input.iter() {
+			match (inside(previous), inside(point)) {
+				(true, true) => ring.push(*point),
+				(true, false) => ring.push(intersect(previous, point)),
+				(false, true) => {
+					ring.push(intersect(previous, point));
+					ring.push(*point);
+				}
+				(false, false) => {}
+			}
+			previous = point;
+		}
+	}
+
+	if ring.len() < 3 {
+		return Vec::new();
+	}
+	ring.push(ring[0]);
+	ring
+}
+
+/// Clips a geometry to the box. Returns `None` if nothing is left.
+pub fn clip_geometry(geometry: Geometry, bbox: &ClipBox) -> Option<Geometry> {
+	let clip_polygon = |polygon: &Coordinates2| -> Option<Coordinates2> {
+		let mut rings = polygon.iter().map(|ring| clip_ring(ring, bbox));
+		// without the outer ring, the holes are meaningless
+		let outer = rings.next().filter(|ring| !ring.is_empty())?;
+		Some(
+			std::iter::once(outer)
+				.chain(rings.filter(|ring| !ring.is_empty()))
+				.collect(),
+		)
+	};
+
+	let geometry = match geometry.into_multi() {
+		Geometry::MultiPoint(g) => Geometry::MultiPoint(MultiPointGeometry(clip_points(&g.0, bbox))),
+		Geometry::MultiLineString(g) => Geometry::MultiLineString(MultiLineStringGeometry(
+			g.0.iter().flat_map(|line| clip_line(line, bbox)).collect(),
+		)),
+		Geometry::MultiPolygon(g) => Geometry::MultiPolygon(MultiPolygonGeometry(
+			g.0.iter().filter_map(clip_polygon).collect(),
+		)),
+		_ => unreachable!("geometry should be a multi geometry"),
+	};
+
+	let is_empty = match &geometry {
+		Geometry::MultiPoint(g) => g.0.is_empty(),
+		Geometry::MultiLineString(g) => g.0.is_empty(),
+		Geometry::MultiPolygon(g) => g.0.is_empty(),
+		_ => false,
+	};
+	(!is_empty).then_some(geometry)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const BBOX: ClipBox = [0.0, 0.0, 10.0, 10.0];
+
+	#[test]
+	fn points() {
+		let points = vec![[-1.0, 5.0], [5.0, 5.0], [10.0, 10.0], [11.0, 0.0]];
+		assert_eq!(clip_points(&points, &BBOX), vec![[5.0, 5.0], [10.0, 10.0]]);
+	}
+
+	#[test]
+	fn line() {
+		// enters, leaves, enters again
+		let line = vec![
+			[-5.0, 5.0],
+			[5.0, 5.0],
+			[5.0, 15.0],
+			[8.0, 15.0],
+			[8.0, 5.0],
+		];
+		assert_eq!(
+			clip_line(&line, &BBOX),
+			vec![
+				vec![[0.0, 5.0], [5.0, 5.0], [5.0, 10.0]],
+				vec![[8.0, 10.0], [8.0, 5.0]]
+			]
+		);
+
+		let outside = vec![[-5.0, -5.0], [-5.0, 15.0]];
+		assert!(clip_line(&outside, &BBOX).is_empty());
+	}
+
+	#[test]
+	fn ring() {
+		let ring = vec![
+			[5.0, 5.0],
+			[15.0, 5.0],
+			[15.0, 15.0],
+			[5.0, 15.0],
+			[5.0, 5.0],
+		];
+		assert_eq!(
+			clip_ring(&ring, &BBOX),
+			vec![
+				[5.0, 10.0],
+				[5.0, 5.0],
+				[10.0, 5.0],
+				[10.0, 10.0],
+				[5.0, 10.0]
+			]
+		);
+
+		let outside = vec![[20.0, 20.0], [30.0, 20.0], [30.0, 30.0], [20.0, 20.0]];
+		assert!(clip_ring(&outside, &BBOX).is_empty());
+	}
+
+	#[test]
+	fn geometry() {
+		let polygons = Geometry::new_multi_polygon(vec![
+			vec![
+				vec![
+					[-5.0, -5.0],
+					[15.0, -5.0],
+					[15.0, 15.0],
+					[-5.0, 15.0],
+					[-5.0, -5.0],
+				],
+				vec![[20.0, 20.0], [20.0, 30.0], [30.0, 30.0], [20.0, 20.0]],
+			],
+			vec![vec![[20.0, 20.0], [30.0, 20.0], [30.0, 30.0], [20.0, 20.0]]],
+		]);
+		assert_eq!(
+			clip_geometry(polygons, &BBOX),
+			Some(Geometry::new_multi_polygon(vec![vec![vec![
+				[0.0, 10.0],
+				[0.0, 0.0],
+				[10.0, 0.0],
+				[10.0, 10.0],
+				[0.0, 10.0]
+			]]]))
+		);
+
+		let point = Geometry::new_point([20, 20]);
+		assert_eq!(clip_geometry(point, &BBOX), None);
+	}
+}
@@ -1,2 +1,5 @@
 mod area;
 pub use area::*;
+
+mod clip;
+pub use clip::*;
@@ -5,5 +5,6 @@ mod property_manager;
 mod tile;
 mod value;
 
+pub use feature::VectorTileFeature;
 pub use layer::VectorTileLayer;
 pub use tile::VectorTile;
@@ -2,10 +2,13 @@
 
 use super::layer::VectorTileLayer;
 use crate::{
-	types::Blob,
+	math::clip_geometry,
+	types::{Blob, TileCoord3},
 	utils::io::{ValueReader, ValueReaderSlice, ValueWriter, ValueWriterBlob},
+	GeoFeature, GeoValue,
 };
 use anyhow::{bail, Context, Result};
+use std::f64::consts::PI;
 
 #[derive(Debug, Default, PartialEq)]
 pub struct VectorTile {
@@ -59,6 +62,84 @@ impl VectorTile {
 
 		Ok(writer.into_blob())
 	}
+
+	/// Converts the features of all layers to WGS84 coordinates, assuming this is the tile at `coord`.
+	/// The name of the layer is added to every feature as the property "layer".
+	pub fn to_geo_features(&self, coord: &TileCoord3) -> Result<Vec<GeoFeature>> {
+		let size = 2.0f64.powi(coord.z as i32);
+		let mut features = Vec::new();
+
+		for layer in self.layers.iter() {
+			let extent = layer.extent as f64;
+			// tile-local coordinates to longitude and latitude in Web Mercator
+			let unproject = |[x, y]: [f64; 2]| {
+				let x = (coord.x as f64 + x / extent) / size;
+				let y = (coord.y as f64 + y / extent) / size;
+				[
+					round(x * 360.0 - 180.0),
+					round((PI * (1.0 - 2.0 * y)).sinh().atan().to_degrees()),
+				]
+			};
+
+			for feature in layer.features.iter() {
+				let mut feature = feature
+					.to_feature(layer)
+					.with_context(|| format!("Failed to decode feature in layer \"{}\"", layer.name))?;
+				feature.geometry = feature.geometry.map_coordinates(unproject);
+				feature.set_property(String::from("layer"), GeoValue::from(&layer.name));
+				features.push(feature);
+			}
+		}
+
+		Ok(features)
+	}
+
+	/// Returns the part of this tile that covers the descendant tile `level_diff` levels deeper at position `x`, `y`,
+	/// e.g. `overzoom(2, 3, 0)` is the top right of the 16 tiles two levels below.
+	/// Features are scaled up and clipped, keeping a small buffer around the tile.
+	pub fn overzoom(&self, level_diff: u8, x: u32, y: u32) -> Result<VectorTile> {
+		let scale = 2.0f64.powi(level_diff as i32);
+		let mut layers = Vec::new();
+
+		for layer in self.layers.iter() {
+			let extent = layer.extent as f64;
+			let buffer = extent / 64.0;
+			let bbox = [-buffer, -buffer, extent + buffer, extent + buffer];
+			let transform = |[px, py]: [f64; 2]| {
+				[
+					px * scale - x as f64 * extent,
+					py * scale - y as f64 * extent,
+				]
+			};
+
+			let features = layer
+				.to_features()
+				.with_context(|| format!("Failed to decode features in layer \"{}\"", layer.name))?
+				.into_iter()
+				.filter_map(|mut feature| {
+					let geometry = feature.geometry.map_coordinates(transform);
+					feature.geometry = clip_geometry(geometry, &bbox)?;
+					Some(feature)
+				})
+				.collect::<Vec<GeoFeature>>();
+
+			if !features.is_empty() {
+				layers.push(VectorTileLayer::from_features(
+					layer.name.clone(),
+					features,
+					layer.extent,
+					layer.version,
+				)?);
+			}
+		}
+
+		Ok(VectorTile::new(layers))
+	}
+}
+
+// 7 decimals are about 1 cm, more would only bloat the JSON
+fn round(value: f64) -> f64 {
+	(value * 1e7).round() / 1e7
 }
 
 #[cfg(test)]
@@ -96,4 +177,105 @@ mod tests {
 		assert_eq!(tile1, tile2);
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn to_geo_features() -> Result<()> {
+		let tile = get_tile().await?;
+		let coord = TileCoord3::new(8800, 5373, 14)?;
+		let features = tile.to_geo_features(&coord)?;
+
+		let count: usize = tile.layers.iter().map(|layer| layer.features.len()).sum();
+		assert_eq!(features.len(), count);
+		assert_eq!(
+			features[0].properties.get("layer"),
+			Some(&GeoValue::from(&tile.layers[0].name))
+		);
+
+		// everything is inside the tile, apart from the buffer
+		let [x0, y1, x1, y0] = coord.as_geo_bbox();
+		let margin = (x1 - x0) / 4.0;
+		for feature in features.iter() {
+			let crate::Geometry::MultiPoint(points) = &feature.geometry else {
+				continue;
+			};
+			for [x, y] in points.0.iter() {
+				assert!(*x > x0 - margin && *x < x1 + margin, "{x}");
+				assert!(*y > y0 - margin && *y < y1 + margin, "{y}");
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn unproject_corners() -> Result<()> {
+		let mut layer = VectorTileLayer::new(String::from("corners"), 4096, 2);
+		layer
+			.features
+			.push(crate::vector_tile::VectorTileFeature::from_geometry(
+				None,
+				vec![],
+				crate::Geometry::new_multi_point(vec![[0, 0], [4096, 4096]]),
+			)?);
+		let tile = VectorTile::new(vec![layer]);
+
+		let coord = TileCoord3::new(1, 1, 2)?;
+		let features = tile.to_geo_features(&coord)?;
+		let crate::Geometry::MultiPoint(points) = &features[0].geometry else {
+			panic!("should be points")
+		};
+		let bbox = coord.as_geo_bbox();
+		assert_eq!(points.0[0], [round(bbox[0]), round(bbox[1])]);
+		assert_eq!(points.0[1], [round(bbox[2]), round(bbox[3])]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn overzoom() -> Result<()> {
+		let mut layer = VectorTileLayer::new(String::from("points"), 4096, 2);
+		layer
+			.features
+			.push(crate::vector_tile::VectorTileFeature::from_geometry(
+				Some(7),
+				vec![],
+				crate::Geometry::new_multi_point(vec![[1000, 3000], [3000, 3000]]),
+			)?);
+		let tile = VectorTile::new(vec![layer]);
+
+		// bottom left quarter
+		let child = tile.overzoom(1, 0, 1)?;
+		let features = child.layers[0].to_features()?;
+		assert_eq!(features[0].id, Some(GeoValue::from(7u64)));
+		assert_eq!(
+			features[0].geometry,
+			crate::Geometry::new_multi_point(vec![[2000, 1904]])
+		);
+
+		// nothing in the top right
+		assert!(tile.overzoom(1, 1, 0)?.layers.is_empty());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn overzoom_real_tile() -> Result<()> {
+		let tile = get_tile().await?;
+		let child = VectorTile::from_blob(&tile.overzoom(2, 1, 2)?.to_blob()?)?;
+		assert!(!child.layers.is_empty());
+
+		for layer in child.layers.iter() {
+			// rounding can move points by half a unit
+			let (min, max) = (-64.5, layer.extent as f64 + 64.5);
+			for feature in layer.to_features()? {
+				feature.geometry.map_coordinates(|[x, y]| {
+					assert!(x >= min && x <= max, "{x}");
+					assert!(y >= min && y <= max, "{y}");
+					[x, y]
+				});
+			}
+		}
+
+		Ok(())
+	}
 }
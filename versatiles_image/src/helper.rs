@@ -1,7 +1,10 @@
 use crate::format::*;
 use crate::types::{Blob, TileFormat};
-use anyhow::Result;
-use image::{DynamicImage, GrayAlphaImage, GrayImage, Luma, LumaA, Rgb, RgbImage, Rgba, RgbaImage};
+use anyhow::{bail, Result};
+use image::{
+	imageops::FilterType, ColorType, DynamicImage, GrayAlphaImage, GrayImage, Luma, LumaA, Rgb,
+	RgbImage, Rgba, RgbaImage,
+};
 
 /// Generate a DynamicImage with RGBA colors
 pub fn create_image_rgba() -> DynamicImage {
@@ -96,3 +99,106 @@ pub fn image2blob_fast(image: &DynamicImage, format: TileFormat) -> Result<Blob>
 		TileFormat::WEBP => webp::image2blob(image),
 	}
 }
+
+pub fn blob2image(blob: &Blob, format: TileFormat) -> Result<DynamicImage> {
+	match format {
+		TileFormat::JPG => jpeg::blob2image(blob),
+		TileFormat::PNG => png::blob2image(blob),
+		TileFormat::WEBP => webp::blob2image(blob),
+		_ => bail!("{format} is not a supported raster format"),
+	}
+}
+
+/// Decode a raster tile and encode it in another format, e.g. PNG to WebP
+/// Colors are converted if needed, since JPEG has no alpha channel and lossy WebP supports only RGB and RGBA.
+/// The fast encoders are used, because tiles are usually transcoded while a client waits for them.
+pub fn transcode(blob: &Blob, from: TileFormat, to: TileFormat) -> Result<Blob> {
+	if !matches!(to, TileFormat::JPG | TileFormat::PNG | TileFormat::WEBP) {
+		bail!("{to} is not a supported raster format");
+	}
+
+	let image = blob2image(blob, from)?;
+	let image = match (to, image.color()) {
+		(TileFormat::JPG, ColorType::L8 | ColorType::Rgb8) => image,
+		(TileFormat::JPG, _) => DynamicImage::ImageRgb8(image.to_rgb8()),
+		(TileFormat::WEBP, ColorType::Rgb8 | ColorType::Rgba8) => image,
+		(TileFormat::WEBP, color) if color.has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8()),
+		(TileFormat::WEBP, _) => DynamicImage::ImageRgb8(image.to_rgb8()),
+		_ => image,
+	};
+	image2blob_fast(&image, to)
+}
+
+/// Crop the part of a raster tile that covers the descendant tile `level_diff` levels deeper at position `x`, `y`,
+/// and scale it up to the size of the tile.
+pub fn overzoom(blob: &Blob, format: TileFormat, level_diff: u8, x: u32, y: u32) -> Result<Blob> {
+	let image = blob2image(blob, format)?;
+	let (width, height) = (image.width(), image.height());
+	// in u64, since the position times the size overflows u32 for deep levels
+	let scale = 1u64 << level_diff.min(32);
+	let offset = |position: u32, size: u32| (position as u64 % scale * size as u64 / scale) as u32;
+	let crop_size = |size: u32| ((size as u64 / scale) as u32).max(1);
+	let image = image
+		.crop_imm(
+			offset(x, width),
+			offset(y, height),
+			crop_size(width),
+			crop_size(height),
+		)
+		.resize_exact(width, height, FilterType::Triangle);
+	image2blob_fast(&image, format)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn transcode_formats() -> Result<()> {
+		let png = png::image2blob(&create_image_rgba(), false)?;
+
+		let webp = transcode(&png, TileFormat::PNG, TileFormat::WEBP)?;
+		compare_images(webp::blob2image(&webp)?, create_image_rgba(), 6);
+
+		// the alpha channel is dropped
+		let jpeg = transcode(&png, TileFormat::PNG, TileFormat::JPG)?;
+		assert_eq!(jpeg::blob2image(&jpeg)?.color(), ColorType::Rgb8);
+
+		// grey images are converted for lossy WebP
+		let grey = png::image2blob(&create_image_grey(), false)?;
+		let webp = transcode(&grey, TileFormat::PNG, TileFormat::WEBP)?;
+		assert_eq!(webp::blob2image(&webp)?.color(), ColorType::Rgb8);
+
+		let png = transcode(&jpeg, TileFormat::JPG, TileFormat::PNG)?;
+		assert_eq!(png::blob2image(&png)?.width(), 256);
+
+		assert!(transcode(&png, TileFormat::PNG, TileFormat::PBF).is_err());
+		assert!(transcode(&png, TileFormat::PBF, TileFormat::PNG).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn overzoom_image() -> Result<()> {
+		// in create_image_rgb red grows from left to right and blue from top to bottom
+		let png = png::image2blob(&create_image_rgb(), false)?;
+
+		let child = png::blob2image(&overzoom(&png, TileFormat::PNG, 1, 1, 0)?)?;
+		assert_eq!((child.width(), child.height()), (256, 256));
+		let pixel = child.to_rgb8().get_pixel(128, 128).0;
+		// the center of the top right quarter
+		assert!(pixel[0].abs_diff(192) <= 2, "{pixel:?}");
+		assert!(pixel[2].abs_diff(64) <= 2, "{pixel:?}");
+
+		// e.g. a tile at zoom level 30 of a source with maximum zoom level 4
+		let (x, y) = ((1 << 26) - 1, 1 << 25);
+		let child = png::blob2image(&overzoom(&png, TileFormat::PNG, 26, x, y)?)?;
+		assert_eq!((child.width(), child.height()), (256, 256));
+		// a single pixel at the right edge and the vertical center
+		let pixel = child.to_rgb8().get_pixel(0, 0).0;
+		assert_eq!((pixel[0], pixel[2]), (255, 128));
+
+		assert!(overzoom(&png, TileFormat::PBF, 1, 0, 0).is_err());
+		Ok(())
+	}
+}